@@ -29,14 +29,18 @@ public class FlashFuzzy implements AutoCloseable {
     }
 
     // Native methods
-    private static native void nativeInit(float threshold, int maxErrors, int maxResults);
+    private static native boolean nativeInit(float threshold, int maxErrors, int maxResults);
     private static native boolean nativeAdd(int id, String text);
+    private static native boolean nativeAddBytes(int id, byte[] text);
     private static native SearchResult[] nativeSearch(String query);
+    private static native SearchResult[] nativeSearchBytes(byte[] query);
+    private static native int nativeSearchInto(String query, int[] idsOut, float[] scoresOut, int[] startsOut, int[] endsOut);
     private static native boolean nativeRemove(int id);
     private static native void nativeReset();
     private static native int nativeGetCount();
     private static native void nativeSetThreshold(float threshold);
     private static native void nativeSetMaxErrors(int maxErrors);
+    private static native int nativeGetMaxErrors();
     private static native void nativeSetMaxResults(int maxResults);
 
     private float threshold;
@@ -82,6 +86,16 @@ public class FlashFuzzy implements AutoCloseable {
         return nativeAdd(id, text);
     }
 
+    /**
+     * Add a record from raw bytes, indexed as-is (need not be valid UTF-8).
+     */
+    public boolean addBytes(int id, byte[] text) {
+        if (text == null || text.length == 0) {
+            return false;
+        }
+        return nativeAddBytes(id, text);
+    }
+
     /**
      * Add multiple records.
      */
@@ -106,6 +120,28 @@ public class FlashFuzzy implements AutoCloseable {
         return results != null ? results : new SearchResult[0];
     }
 
+    /**
+     * Search with a raw byte query; result offsets are byte offsets.
+     */
+    public SearchResult[] searchBytes(byte[] query) {
+        if (query == null || query.length == 0) {
+            return new SearchResult[0];
+        }
+        SearchResult[] results = nativeSearchBytes(query);
+        return results != null ? results : new SearchResult[0];
+    }
+
+    /**
+     * Search into caller-provided arrays without allocating result objects.
+     * Result i goes to index i of each array; returns the number written.
+     */
+    public int searchInto(String query, int[] idsOut, float[] scoresOut, int[] startsOut, int[] endsOut) {
+        if (query == null || query.isEmpty()) {
+            return 0;
+        }
+        return nativeSearchInto(query, idsOut, scoresOut, startsOut, endsOut);
+    }
+
     /**
      * Remove a record by ID.
      */
@@ -146,6 +182,12 @@ public class FlashFuzzy implements AutoCloseable {
     public int getMaxErrors() { return maxErrors; }
     public int getMaxResults() { return maxResults; }
 
+    /**
+     * Max errors the native engine is using, after clamping to its ceiling
+     * (MAX_ERRORS_CEIL, 8), the same value ff_get_max_errors reports.
+     */
+    public int getEffectiveMaxErrors() { return nativeGetMaxErrors(); }
+
     @Override
     public void close() {
         reset();
@@ -46,6 +46,28 @@ suspend fun FlashFuzzy.searchAsync(query: String): Array<SearchResult> {
     }
 }
 
+/**
+ * Reusable primitive arrays for [searchInto], e.g. one per list adapter
+ */
+class ResultBuffer(capacity: Int) {
+    val ids = IntArray(capacity)
+    val scores = FloatArray(capacity)
+    val starts = IntArray(capacity)
+    val ends = IntArray(capacity)
+
+    /** Number of results written by the last search */
+    var count = 0
+        internal set
+}
+
+/**
+ * Search into [buffer] without allocating a result object per hit
+ */
+fun FlashFuzzy.searchInto(query: String, buffer: ResultBuffer): Int {
+    buffer.count = searchInto(query, buffer.ids, buffer.scores, buffer.starts, buffer.ends)
+    return buffer.count
+}
+
 /**
  * Extension to convert results to a List
  */
@@ -8,24 +8,89 @@ package flashfuzzy
 
 // FFI function declarations
 void ff_init(void);
+void ff_destroy_all(void);
+uint32_t ff_warmup(void);
+typedef struct { uint32_t max_records, max_results, string_pool_size, scratchpad_size, max_pattern_len, max_errors; } ff_limits;
+int32_t ff_get_limits(ff_limits* out);
 uint8_t* ff_get_write_buffer(uint32_t size);
 void ff_commit_write(uint32_t len);
 int32_t ff_add_record(uint32_t id);
+int32_t ff_add_record_with_bloom(uint32_t id, uint64_t bloom);
+typedef struct { uint32_t id; int32_t reason; } ff_failed_record;
+int32_t ff_add_records(const uint32_t* ids, const uint32_t* lens, uint32_t count, ff_failed_record* failed, uint32_t failed_cap, uint32_t* failed_count);
+void ff_attach_corpus(const uint8_t* ptr, uint32_t len);
+int32_t ff_add_record_ref(uint32_t id, uint32_t offset, uint32_t len);
+int32_t ff_add_delimited(const uint8_t* ptr, uint32_t len, uint8_t delimiter, uint32_t base_id);
+int32_t ff_update_record(uint32_t id);
 int32_t ff_remove_record(uint32_t id);
+void ff_set_auto_compact_ratio(float ratio);
 void ff_set_max_errors(uint32_t errors);
+uint32_t ff_get_max_errors(void);
 void ff_set_threshold(uint32_t threshold);
+void ff_set_auto_threshold(uint32_t enabled);
+void ff_set_separators(const uint8_t* ptr, uint32_t len);
 void ff_set_max_results(uint32_t max);
+void ff_set_collapse_whitespace(uint32_t enabled);
+uint32_t ff_original_offset(const uint8_t* ptr, uint32_t len, uint32_t pos);
+// 0 = low bits (default), 1 = multiplicative, 2 = nibble fold
+void ff_set_bloom_hash(uint32_t scheme);
+void ff_set_fold_case(uint32_t enabled);
+void ff_set_fold_diacritics(uint32_t enabled);
+void ff_set_min_score_gap(uint32_t gap);
+void ff_set_min_pattern_bits(uint32_t bits);
+void ff_set_min_match_ratio(float ratio);
 uint32_t ff_get_record_count(void);
-void ff_prepare_pattern(void);
+uint32_t ff_count_matches(const uint8_t* ptr, uint32_t len);
+int32_t ff_get_record_ids(uint32_t* out, uint32_t cap);
+uint64_t ff_index_digest(void);
+uint32_t ff_prepare_pattern(void);
 uint32_t ff_search(void);
+uint32_t ff_search_limited(uint32_t max_records);
+uint32_t ff_search_topk(uint32_t k);
+uint32_t ff_search_window(uint32_t max_offset);
+void ff_cancel(void);
+uint32_t ff_search_cancelled(void);
+uint32_t ff_search_too_broad(void);
+typedef int32_t (*ff_match_callback)(uint32_t id, uint32_t score, uint32_t start, uint32_t end);
+uint32_t ff_search_callback(ff_match_callback cb);
+typedef uint32_t (*ff_rerank_callback)(uint32_t id, uint32_t text_score);
+uint32_t ff_search_rerank(ff_rerank_callback cb);
+int32_t ff_suggest(uint8_t* out, uint32_t cap);
+void ff_set_candidate_cache(uint32_t enabled);
+int32_t ff_add_multi_pattern(void);
+void ff_clear_multi_patterns(void);
+uint32_t ff_search_multi(void);
+uint32_t ff_get_multi_result_count(uint32_t pattern);
+uint32_t ff_get_multi_result_id(uint32_t pattern, uint32_t index);
+uint32_t ff_get_multi_result_score(uint32_t pattern, uint32_t index);
+uint32_t ff_get_multi_match_mask(uint32_t id);
+uint32_t ff_rethreshold(uint32_t threshold);
+uint32_t ff_result_diff(uint32_t* out_added, uint32_t* out_removed, uint32_t cap);
+// out receives base, position, boundary and exact bonuses, penalty, score
+int32_t ff_explain_result(uint32_t index, uint32_t* out);
+int32_t ff_find_in_record(uint32_t id, uint32_t from_offset);
+int32_t ff_get_result_edit_profile(uint32_t index, uint32_t* out);
+uint32_t ff_get_char_mask(uint32_t byte);
 uint32_t ff_get_result_id(uint32_t index);
 uint32_t ff_get_result_score(uint32_t index);
 uint32_t ff_get_result_start(uint32_t index);
 uint32_t ff_get_result_end(uint32_t index);
-void ff_reset(void);
+uint32_t ff_get_result_rank(uint32_t index);
+uint32_t ff_get_result_relative(uint32_t index);
+uint32_t ff_get_result_density(uint32_t index);
+uint32_t ff_get_result_confidence(uint32_t index);
+uint32_t ff_results_common_prefix(void);
+uint32_t ff_get_result_char_start(uint32_t index);
+uint32_t ff_get_result_char_end(uint32_t index);
+void ff_clear_data(void);
 uint32_t ff_compact(void);
 uint32_t ff_get_string_pool_used(void);
 uint32_t ff_get_available_memory(void);
+uint32_t ff_get_pool_reclaimable(void);
+uint32_t ff_get_record_high_water(void);
+uint32_t ff_get_record_capacity(void);
+uint32_t ff_get_records_remaining(void);
+uint32_t ff_get_pool_capacity(void);
 */
 import "C"
 import (
@@ -118,16 +183,44 @@ func (ff *FlashFuzzy) AddBatch(records map[uint32]string) int {
 	return added
 }
 
+// AddDelimited adds each delimiter-separated segment of data as a record,
+// with IDs counting up from baseID (one per segment, empty ones included).
+// Returns the number added, or a negative error code.
+func (ff *FlashFuzzy) AddDelimited(data []byte, delimiter byte, baseID uint32) int {
+	if len(data) == 0 {
+		return 0
+	}
+	return int(C.ff_add_delimited((*C.uint8_t)(unsafe.Pointer(&data[0])), C.uint32_t(len(data)), C.uint8_t(delimiter), C.uint32_t(baseID)))
+}
+
 // Search performs a fuzzy search and returns matching results
 func (ff *FlashFuzzy) Search(query string) []SearchResult {
+	return ff.search(query, func() uint32 { return uint32(C.ff_search()) })
+}
+
+// SearchTopK returns the k best matches for query whatever their score.
+// The threshold is ignored, but matches must still be within MaxErrors.
+func (ff *FlashFuzzy) SearchTopK(query string, k uint32) []SearchResult {
+	return ff.search(query, func() uint32 { return uint32(C.ff_search_topk(C.uint32_t(k))) })
+}
+
+// SearchWindow searches for query within the first maxOffset bytes of each record.
+// A maxOffset of 0 searches whole records.
+func (ff *FlashFuzzy) SearchWindow(query string, maxOffset uint32) []SearchResult {
+	return ff.search(query, func() uint32 { return uint32(C.ff_search_window(C.uint32_t(maxOffset))) })
+}
+
+// prepare sets query as the pattern, returning false if it is empty or
+// doesn't fit the write buffer.
+func prepare(query string) bool {
 	if len(query) == 0 {
-		return nil
+		return false
 	}
 
 	queryBytes := []byte(query)
 	ptr := C.ff_get_write_buffer(C.uint32_t(len(queryBytes)))
 	if ptr == nil {
-		return nil
+		return false
 	}
 
 	// Copy query to write buffer
@@ -136,8 +229,26 @@ func (ff *FlashFuzzy) Search(query string) []SearchResult {
 
 	C.ff_commit_write(C.uint32_t(len(queryBytes)))
 	C.ff_prepare_pattern()
+	return true
+}
+
+// FindInRecord returns the start of the first match of query at or after
+// from in record id, or -1 if there is none. Pass the previous start + 1
+// to step to the next occurrence.
+func (ff *FlashFuzzy) FindInRecord(query string, id uint32, from uint32) int {
+	if !prepare(query) {
+		return -1
+	}
+	return int(C.ff_find_in_record(C.uint32_t(id), C.uint32_t(from)))
+}
 
-	count := uint32(C.ff_search())
+// search prepares query as the pattern and collects the results of run.
+func (ff *FlashFuzzy) search(query string, run func() uint32) []SearchResult {
+	if !prepare(query) {
+		return nil
+	}
+
+	count := run()
 	if count == 0 {
 		return nil
 	}
@@ -155,14 +266,119 @@ func (ff *FlashFuzzy) Search(query string) []SearchResult {
 	return results
 }
 
+// SearchMulti runs up to 8 queries in a single pass over the records.
+// The result at index i holds query i's matches (nil if it was empty);
+// queries past the eighth are ignored.
+func (ff *FlashFuzzy) SearchMulti(queries []string) [][]SearchResult {
+	C.ff_clear_multi_patterns()
+	patterns := make([]int32, len(queries))
+	for i, query := range queries {
+		patterns[i] = -1
+		if len(query) == 0 {
+			continue
+		}
+		queryBytes := []byte(query)
+		ptr := C.ff_get_write_buffer(C.uint32_t(len(queryBytes)))
+		if ptr == nil {
+			continue
+		}
+		cBytes := (*[1 << 30]byte)(unsafe.Pointer(ptr))[:len(queryBytes):len(queryBytes)]
+		copy(cBytes, queryBytes)
+		C.ff_commit_write(C.uint32_t(len(queryBytes)))
+		patterns[i] = int32(C.ff_add_multi_pattern())
+	}
+
+	C.ff_search_multi()
+
+	results := make([][]SearchResult, len(queries))
+	for i, p := range patterns {
+		if p < 0 {
+			continue
+		}
+		pattern := C.uint32_t(p)
+		count := uint32(C.ff_get_multi_result_count(pattern))
+		for j := uint32(0); j < count; j++ {
+			results[i] = append(results[i], SearchResult{
+				ID:    uint32(C.ff_get_multi_result_id(pattern, C.uint32_t(j))),
+				Score: float32(C.ff_get_multi_result_score(pattern, C.uint32_t(j))) / 1000.0,
+			})
+		}
+	}
+	return results
+}
+
+// Update replaces the text of the record with this ID
+// Unchanged text is a no-op; changed text re-adds the record at the end.
+func (ff *FlashFuzzy) Update(id uint32, text string) bool {
+	if len(text) == 0 {
+		return false
+	}
+
+	textBytes := []byte(text)
+	ptr := C.ff_get_write_buffer(C.uint32_t(len(textBytes)))
+	if ptr == nil {
+		return false
+	}
+	cBytes := (*[1 << 30]byte)(unsafe.Pointer(ptr))[:len(textBytes):len(textBytes)]
+	copy(cBytes, textBytes)
+	C.ff_commit_write(C.uint32_t(len(textBytes)))
+
+	return C.ff_update_record(C.uint32_t(id)) == 1
+}
+
+// CountMatches returns how many records query matches, without storing results
+func (ff *FlashFuzzy) CountMatches(query string) uint32 {
+	if len(query) == 0 {
+		return 0
+	}
+	queryBytes := []byte(query)
+	return uint32(C.ff_count_matches((*C.uint8_t)(unsafe.Pointer(&queryBytes[0])), C.uint32_t(len(queryBytes))))
+}
+
 // Remove removes a record by ID
 func (ff *FlashFuzzy) Remove(id uint32) bool {
 	return C.ff_remove_record(C.uint32_t(id)) == 1
 }
 
-// Reset clears all records
+// Reset clears all records, keeping the configuration
 func (ff *FlashFuzzy) Reset() {
-	C.ff_reset()
+	C.ff_clear_data()
+}
+
+// Close tears down the engine and frees its buffers.
+// The instance must not be used afterwards; New starts a fresh engine.
+func (ff *FlashFuzzy) Close() {
+	C.ff_destroy_all()
+}
+
+// Warmup faults in the engine's fixed arrays so the first search doesn't
+// pay for page faults. Returns the number of pages touched.
+func (ff *FlashFuzzy) Warmup() uint32 {
+	return uint32(C.ff_warmup())
+}
+
+// Limits are the engine's fixed capacities
+type Limits struct {
+	MaxRecords     uint32
+	MaxResults     uint32
+	StringPoolSize uint32
+	ScratchpadSize uint32
+	MaxPatternLen  uint32
+	MaxErrors      uint32
+}
+
+// Limits reads the engine's fixed capacities
+func (ff *FlashFuzzy) Limits() Limits {
+	var l C.ff_limits
+	C.ff_get_limits(&l)
+	return Limits{
+		MaxRecords:     uint32(l.max_records),
+		MaxResults:     uint32(l.max_results),
+		StringPoolSize: uint32(l.string_pool_size),
+		ScratchpadSize: uint32(l.scratchpad_size),
+		MaxPatternLen:  uint32(l.max_pattern_len),
+		MaxErrors:      uint32(l.max_errors),
+	}
 }
 
 // Count returns the number of records
@@ -170,18 +386,96 @@ func (ff *FlashFuzzy) Count() uint32 {
 	return uint32(C.ff_get_record_count())
 }
 
+// IDs returns the IDs of all active records, in insertion order.
+func (ff *FlashFuzzy) IDs() []uint32 {
+	n := -int32(C.ff_get_record_ids(nil, 0))
+	if n <= 0 {
+		return nil
+	}
+	ids := make([]uint32, n)
+	C.ff_get_record_ids((*C.uint32_t)(unsafe.Pointer(&ids[0])), C.uint32_t(n))
+	return ids
+}
+
+// Digest returns an order-independent hash of the records' IDs and texts.
+// Two indexes holding the same records return the same digest.
+func (ff *FlashFuzzy) Digest() uint64 {
+	return uint64(C.ff_index_digest())
+}
+
 // SetThreshold sets the minimum score threshold
 func (ff *FlashFuzzy) SetThreshold(threshold float32) {
 	ff.threshold = threshold
 	C.ff_set_threshold(C.uint32_t(threshold * 1000))
 }
 
+// SetAutoThreshold derives the threshold from the query length instead.
+// SetThreshold turns it off again.
+func (ff *FlashFuzzy) SetAutoThreshold(enabled bool) {
+	var flag C.uint32_t
+	if enabled {
+		flag = 1
+	}
+	C.ff_set_auto_threshold(flag)
+}
+
+// SetFoldCase sets whether matching ignores ASCII case (the default).
+func (ff *FlashFuzzy) SetFoldCase(enabled bool) {
+	var flag C.uint32_t
+	if enabled {
+		flag = 1
+	}
+	C.ff_set_fold_case(flag)
+}
+
+// SetFoldDiacritics sets whether matching ignores accents on Latin
+// letters, so "naive" matches "naïve". Independent of SetFoldCase.
+func (ff *FlashFuzzy) SetFoldDiacritics(enabled bool) {
+	var flag C.uint32_t
+	if enabled {
+		flag = 1
+	}
+	C.ff_set_fold_diacritics(flag)
+}
+
+// SetMinScoreGap drops a result whose matched text equals that of a kept
+// result scoring within gap (0-1000) of it. 0, the default, keeps all.
+func (ff *FlashFuzzy) SetMinScoreGap(gap uint32) {
+	C.ff_set_min_score_gap(C.uint32_t(gap))
+}
+
+// SetMinPatternBits refuses queries that would match nearly every record:
+// those whose error budget is at least half their length and that leave
+// fewer than bits pattern bloom bits past it. 0, the default, turns the
+// guard off.
+func (ff *FlashFuzzy) SetMinPatternBits(bits uint32) {
+	C.ff_set_min_pattern_bits(C.uint32_t(bits))
+}
+
+// SetMinMatchRatio rejects matches spanning fewer than ratio times the
+// query's length, which deletions allow under a high error budget. 0, the
+// default, accepts any span.
+func (ff *FlashFuzzy) SetMinMatchRatio(ratio float32) {
+	C.ff_set_min_match_ratio(C.float(ratio))
+}
+
+// TooBroad reports whether the last search was refused by SetMinPatternBits
+func (ff *FlashFuzzy) TooBroad() bool {
+	return C.ff_search_too_broad() != 0
+}
+
 // SetMaxErrors sets the maximum number of errors allowed
 func (ff *FlashFuzzy) SetMaxErrors(maxErrors uint32) {
 	ff.maxErrors = maxErrors
 	C.ff_set_max_errors(C.uint32_t(maxErrors))
 }
 
+// MaxErrors returns the maximum errors in effect, after the engine
+// clamped the SetMaxErrors value to its ceiling of 8
+func (ff *FlashFuzzy) MaxErrors() uint32 {
+	return uint32(C.ff_get_max_errors())
+}
+
 // SetMaxResults sets the maximum number of results to return
 func (ff *FlashFuzzy) SetMaxResults(maxResults uint32) {
 	ff.maxResults = maxResults
@@ -190,16 +484,26 @@ func (ff *FlashFuzzy) SetMaxResults(maxResults uint32) {
 
 // Stats returns statistics about the index
 type Stats struct {
-	RecordCount     uint32
-	StringPoolUsed  uint32
-	AvailableMemory uint32
+	RecordCount      uint32
+	StringPoolUsed   uint32
+	AvailableMemory  uint32
+	RecordCapacity   uint32
+	RecordsRemaining uint32
+	PoolCapacity     uint32
+	PoolReclaimable  uint32
+	RecordHighWater  uint32
 }
 
 // GetStats returns current index statistics
 func (ff *FlashFuzzy) GetStats() Stats {
 	return Stats{
-		RecordCount:     uint32(C.ff_get_record_count()),
-		StringPoolUsed:  uint32(C.ff_get_string_pool_used()),
-		AvailableMemory: uint32(C.ff_get_available_memory()),
+		RecordCount:      uint32(C.ff_get_record_count()),
+		StringPoolUsed:   uint32(C.ff_get_string_pool_used()),
+		AvailableMemory:  uint32(C.ff_get_available_memory()),
+		RecordCapacity:   uint32(C.ff_get_record_capacity()),
+		RecordsRemaining: uint32(C.ff_get_records_remaining()),
+		PoolCapacity:     uint32(C.ff_get_pool_capacity()),
+		PoolReclaimable:  uint32(C.ff_get_pool_reclaimable()),
+		RecordHighWater:  uint32(C.ff_get_record_high_water()),
 	}
 }
@@ -1,11 +1,12 @@
 //! Flash-Fuzzy JNI Bindings for Java/Kotlin/Android
 
-use jni::objects::{JClass, JObject, JString, JValue};
+use jni::objects::{JByteArray, JClass, JFloatArray, JIntArray, JObject, JObjectArray, JString, JValue};
 use jni::sys::{jboolean, jfloat, jint, jobjectArray, JNI_TRUE};
 use jni::JNIEnv;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
-use flash_fuzzy_core::{bitap, bloom::BloomFilter, BitapSearcher, ScoredResult, SearchConfig};
+use flash_fuzzy_core::{bitap, bloom::BloomFilter, insert_ranked, BitapSearcher, ScoredResult, SearchConfig, MAX_ERRORS_CEIL};
 
 // Global state wrapped in Mutex for thread safety
 struct FlashFuzzyState {
@@ -15,17 +16,100 @@ struct FlashFuzzyState {
 
 struct Record {
     id: i32,
-    text: String,
+    // Raw bytes: `nativeAddBytes` accepts text that isn't valid UTF-8
+    text: Vec<u8>,
     bloom: BloomFilter,
 }
 
 static STATE: Mutex<Option<FlashFuzzyState>> = Mutex::new(None);
 
+// Set by nativeCancel without taking the lock; cleared when a search starts
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+/// Records a search visits between checks of `CANCEL`
+const CANCEL_POLL: usize = 256;
+
+/// Java class of the objects returned by `nativeSearch`
+const RESULT_CLASS: &str = "com/flashfuzzy/SearchResult";
+
 fn get_state() -> std::sync::MutexGuard<'static, Option<FlashFuzzyState>> {
-    STATE.lock().unwrap()
+    // A panic while holding the lock leaves the data intact; keep serving it
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Convert a 0.0-1.0 threshold from Java to core's 0-1000 scale
+/// Out-of-range values clamp (NaN becomes 0). Rounding rather than
+/// truncating keeps e.g. 0.29 at 290 instead of 289, so values survive a
+/// round trip through `to_float_score` at 1/1000 granularity.
+fn to_core_score(value: jfloat) -> u16 {
+    (value.clamp(0.0, 1.0) * 1000.0).round() as u16
+}
+
+/// Convert a core 0-1000 score to the 0.0-1.0 scale used by Java
+fn to_float_score(score: u16) -> jfloat {
+    score as jfloat / 1000.0
+}
+
+/// Clamp a Java max errors value to 0..=`MAX_ERRORS_CEIL`, as the C API does
+fn clamp_max_errors(value: jint) -> u32 {
+    (value.max(0) as u32).min(MAX_ERRORS_CEIL)
+}
+
+/// Throw IllegalStateException for calls made before nativeInit
+fn throw_not_initialized(env: &mut JNIEnv) {
+    let _ = env.throw_new(
+        "java/lang/IllegalStateException",
+        "FlashFuzzy not initialized: nativeInit was never called",
+    );
+}
+
+/// Build the `SearchResult[]` returned to Java
+fn new_result_array<'local>(
+    env: &mut JNIEnv<'local>,
+    results: &[ScoredResult],
+) -> jni::errors::Result<JObjectArray<'local>> {
+    let result_class = env.find_class(RESULT_CLASS)?;
+    let array = env.new_object_array(results.len() as i32, &result_class, JObject::null())?;
+
+    for (i, r) in results.iter().enumerate() {
+        let obj = env.new_object(
+            &result_class,
+            "(IFII)V",
+            &[
+                JValue::Int(r.id as i32),
+                JValue::Float(to_float_score(r.score)),
+                JValue::Int(r.start as i32),
+                JValue::Int(r.end as i32),
+            ],
+        )?;
+        env.set_object_array_element(&array, i as i32, &obj)?;
+        // Large result sets would otherwise exhaust the local reference table
+        env.delete_local_ref(obj)?;
+    }
+    Ok(array)
+}
+
+/// Turn a failure to build results into a catchable Java exception
+///
+/// The failed JNI call may have left its own exception (e.g.
+/// `NoClassDefFoundError` when the class was renamed by ProGuard/R8)
+/// pending; it is replaced by one that names the class to keep.
+fn throw_result_error(env: &mut JNIEnv, err: jni::errors::Error) {
+    if env.exception_check().unwrap_or(false) {
+        let _ = env.exception_clear();
+    }
+    eprintln!("flash-fuzzy: cannot create {RESULT_CLASS} results: {err}");
+    let _ = env.throw_new(
+        "java/lang/IllegalStateException",
+        format!("FlashFuzzy cannot create {RESULT_CLASS} results ({err}); is the class kept by ProGuard/R8?"),
+    );
 }
 
 /// Initialize the engine
+///
+/// Safe to call more than once: an existing state is reset (records
+/// cleared, config replaced) rather than raced over.
+/// Returns true if the state was created, false if it was reset.
 #[no_mangle]
 pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeInit(
     _env: JNIEnv,
@@ -33,16 +117,29 @@ pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeInit(
     threshold: jfloat,
     max_errors: jint,
     max_results: jint,
-) {
+) -> jboolean {
+    let config = SearchConfig {
+        threshold: to_core_score(threshold),
+        max_errors: clamp_max_errors(max_errors),
+        max_results: max_results as usize,
+        ..SearchConfig::default()
+    };
+
     let mut state = get_state();
-    *state = Some(FlashFuzzyState {
-        records: Vec::with_capacity(1000),
-        config: SearchConfig {
-            threshold: (threshold * 1000.0) as u16,
-            max_errors: max_errors as u32,
-            max_results: max_results as usize,
-        },
-    });
+    match *state {
+        Some(ref mut s) => {
+            s.records.clear();
+            s.config = config;
+            0
+        }
+        None => {
+            *state = Some(FlashFuzzyState {
+                records: Vec::with_capacity(1000),
+                config,
+            });
+            JNI_TRUE as jboolean
+        }
+    }
 }
 
 /// Add a record
@@ -57,21 +154,88 @@ pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeAdd<'local>(
         Ok(s) => s.into(),
         Err(_) => return 0,
     };
+    add_record(&mut env, id, text.into_bytes())
+}
+
+/// Add a record from raw bytes
+///
+/// Unlike `nativeAdd` the text needn't be valid (modified) UTF-8, so NULs
+/// and corrupt imports are indexed as-is rather than dropped.
+#[no_mangle]
+pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeAddBytes<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    id: jint,
+    text: JByteArray<'local>,
+) -> jboolean {
+    let text = match env.convert_byte_array(&text) {
+        Ok(bytes) => bytes,
+        Err(_) => return 0,
+    };
+    add_record(&mut env, id, text)
+}
 
+/// Store a record, throwing if the engine isn't initialized
+fn add_record(env: &mut JNIEnv, id: jint, text: Vec<u8>) -> jboolean {
     if text.is_empty() {
         return 0;
     }
 
-    let bloom = BloomFilter::from_text(text.as_bytes());
+    let bloom = BloomFilter::from_text(&text);
 
     let mut state = get_state();
     if let Some(ref mut s) = *state {
         s.records.push(Record { id, text, bloom });
         return JNI_TRUE as jboolean;
     }
+    drop(state);
+
+    throw_not_initialized(env);
     0
 }
 
+/// Rank the records matching `query`, or None before nativeInit
+/// The state lock is released on return, before any JNI calls.
+fn search_records(query: &[u8]) -> Option<Vec<ScoredResult>> {
+    let state = get_state();
+    let s = state.as_ref()?;
+    let searcher = BitapSearcher::new(query);
+    let pattern_bloom = searcher.bloom();
+    let pattern_len = searcher.pattern_len();
+
+    let mut results = vec![ScoredResult::default(); s.config.max_results];
+    let mut count = 0;
+
+    CANCEL.store(false, Ordering::Relaxed);
+    for (index, record) in s.records.iter().enumerate() {
+        if index % CANCEL_POLL == 0 && CANCEL.load(Ordering::Relaxed) {
+            break;
+        }
+        if !record.bloom.might_contain_within(pattern_bloom, s.config.max_errors) {
+            continue;
+        }
+
+        if let Some(m) = searcher.search(&record.text, s.config.max_errors) {
+            let score = bitap::compute_score(m.errors, pattern_len as u32, m.end_pos);
+
+            if score >= s.config.threshold {
+                let mut result = ScoredResult::new(
+                    record.id as u32,
+                    score,
+                    m.start_pos,
+                    m.end_pos,
+                );
+                result.index = index as u32;
+
+                // Same ranking as the FFI/WASM builds
+                count = insert_ranked(&mut results, count, s.config.max_results, result);
+            }
+        }
+    }
+    results.truncate(count);
+    Some(results)
+}
+
 /// Search for matches
 #[no_mangle]
 pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSearch<'local>(
@@ -83,78 +247,127 @@ pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSearch<'local>(
         Ok(s) => s.into(),
         Err(_) => return std::ptr::null_mut(),
     };
+    search_to_array(&mut env, query.as_bytes())
+}
 
-    if query.is_empty() {
-        // Return empty array
-        let result_class = env.find_class("com/flashfuzzy/SearchResult").unwrap();
-        return env.new_object_array(0, result_class, JObject::null()).unwrap().into_raw();
-    }
-
-    let state = get_state();
-    let results = if let Some(ref s) = *state {
-        let query_bytes = query.as_bytes();
-        let searcher = BitapSearcher::new(query_bytes);
-        let pattern_bloom = searcher.bloom();
-        let pattern_len = searcher.pattern_len();
-
-        let mut results: Vec<ScoredResult> = Vec::new();
-
-        for record in &s.records {
-            if !record.bloom.might_contain(pattern_bloom) {
-                continue;
-            }
+/// Search for matches with a raw byte query
+///
+/// Unlike `nativeSearch` the query needn't convert from a Java string, so
+/// it can hold any bytes, matching records added by `nativeAddBytes`.
+/// Result offsets are byte offsets into the record text.
+#[no_mangle]
+pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSearchBytes<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    query: JByteArray<'local>,
+) -> jobjectArray {
+    let query = match env.convert_byte_array(&query) {
+        Ok(bytes) => bytes,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    search_to_array(&mut env, &query)
+}
 
-            let text_bytes = record.text.as_bytes();
-            if let Some(m) = searcher.search(text_bytes, s.config.max_errors) {
-                let score = bitap::compute_score(m.errors, pattern_len as u32, m.end_pos);
-
-                if score >= s.config.threshold {
-                    let start_pos = m.end_pos.saturating_sub(pattern_len);
-                    let result = ScoredResult::new(
-                        record.id as u32,
-                        score,
-                        start_pos as u16,
-                        m.end_pos as u16,
-                    );
-
-                    // Insert sorted
-                    let pos = results.iter().position(|r| r.score < result.score).unwrap_or(results.len());
-                    if results.len() < s.config.max_results {
-                        results.insert(pos, result);
-                    } else if pos < results.len() {
-                        results.pop();
-                        results.insert(pos, result);
-                    }
-                }
+/// Run a search and build its `SearchResult[]`, throwing on failure
+fn search_to_array(env: &mut JNIEnv, query: &[u8]) -> jobjectArray {
+    let results = if query.is_empty() {
+        Vec::new()
+    } else {
+        match search_records(query) {
+            Some(results) => results,
+            None => {
+                throw_not_initialized(env);
+                return std::ptr::null_mut();
             }
         }
-        results
-    } else {
-        Vec::new()
     };
 
-    // Drop the lock before JNI calls
-    drop(state);
+    match new_result_array(env, &results) {
+        Ok(array) => array.into_raw(),
+        Err(err) => {
+            throw_result_error(env, err);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Search for matches, writing them into caller-provided arrays
+///
+/// Fills result `i` into index `i` of each array, allocating no Java
+/// objects. Results past the shortest array are dropped. Returns the
+/// number written; throws IllegalArgumentException if an array is null.
+#[no_mangle]
+pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSearchInto<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    query: JString<'local>,
+    ids_out: JIntArray<'local>,
+    scores_out: JFloatArray<'local>,
+    starts_out: JIntArray<'local>,
+    ends_out: JIntArray<'local>,
+) -> jint {
+    let query: String = match env.get_string(&query) {
+        Ok(s) => s.into(),
+        Err(_) => return 0,
+    };
+    if query.is_empty() {
+        return 0;
+    }
 
-    // Create Java array
-    let result_class = env.find_class("com/flashfuzzy/SearchResult").unwrap();
-    let array = env.new_object_array(results.len() as i32, &result_class, JObject::null()).unwrap();
+    let Some(results) = search_records(query.as_bytes()) else {
+        throw_not_initialized(&mut env);
+        return 0;
+    };
 
-    for (i, r) in results.iter().enumerate() {
-        let obj = env.new_object(
-            &result_class,
-            "(IFII)V",
-            &[
-                JValue::Int(r.id as i32),
-                JValue::Float(r.score as f32 / 1000.0),
-                JValue::Int(r.start as i32),
-                JValue::Int(r.end as i32),
-            ],
-        ).unwrap();
-        env.set_object_array_element(&array, i as i32, obj).unwrap();
+    match fill_result_arrays(&mut env, &results, &ids_out, &scores_out, &starts_out, &ends_out) {
+        Ok(count) => count,
+        Err(err) => {
+            if env.exception_check().unwrap_or(false) {
+                let _ = env.exception_clear();
+            }
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("cannot fill result arrays: {err}"));
+            0
+        }
     }
+}
 
-    array.into_raw()
+/// Copy as many `results` as fit into the output arrays, returning how many
+fn fill_result_arrays(
+    env: &mut JNIEnv,
+    results: &[ScoredResult],
+    ids_out: &JIntArray,
+    scores_out: &JFloatArray,
+    starts_out: &JIntArray,
+    ends_out: &JIntArray,
+) -> jni::errors::Result<jint> {
+    let cap = [
+        env.get_array_length(ids_out)?,
+        env.get_array_length(scores_out)?,
+        env.get_array_length(starts_out)?,
+        env.get_array_length(ends_out)?,
+    ]
+    .into_iter()
+    .min()
+    .unwrap_or(0)
+    .max(0) as usize;
+    let results = &results[..results.len().min(cap)];
+
+    let ids: Vec<i32> = results.iter().map(|r| r.id as i32).collect();
+    let scores: Vec<f32> = results.iter().map(|r| to_float_score(r.score)).collect();
+    let starts: Vec<i32> = results.iter().map(|r| r.start as i32).collect();
+    let ends: Vec<i32> = results.iter().map(|r| r.end as i32).collect();
+    env.set_int_array_region(ids_out, 0, &ids)?;
+    env.set_float_array_region(scores_out, 0, &scores)?;
+    env.set_int_array_region(starts_out, 0, &starts)?;
+    env.set_int_array_region(ends_out, 0, &ends)?;
+    Ok(results.len() as jint)
+}
+
+/// Stop the search in progress, which returns the results found so far
+/// Callable from any thread while another is inside `nativeSearch`.
+#[no_mangle]
+pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeCancel(_env: JNIEnv, _class: JClass) {
+    CANCEL.store(true, Ordering::Relaxed);
 }
 
 /// Remove a record by ID
@@ -208,11 +421,11 @@ pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSetThreshold(
 ) {
     let mut state = get_state();
     if let Some(ref mut s) = *state {
-        s.config.threshold = (threshold * 1000.0) as u16;
+        s.config.threshold = to_core_score(threshold);
     }
 }
 
-/// Set max errors
+/// Set max errors, clamped to 0..=`MAX_ERRORS_CEIL` as `ff_set_max_errors` does
 #[no_mangle]
 pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSetMaxErrors(
     _env: JNIEnv,
@@ -221,10 +434,16 @@ pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSetMaxErrors(
 ) {
     let mut state = get_state();
     if let Some(ref mut s) = *state {
-        s.config.max_errors = max_errors as u32;
+        s.config.max_errors = clamp_max_errors(max_errors);
     }
 }
 
+/// Get the max errors in effect, after clamping
+#[no_mangle]
+pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeGetMaxErrors(_env: JNIEnv, _class: JClass) -> jint {
+    get_state().as_ref().map_or(0, |s| s.config.max_errors as jint)
+}
+
 /// Set max results
 #[no_mangle]
 pub extern "system" fn Java_com_flashfuzzy_FlashFuzzy_nativeSetMaxResults(
@@ -77,14 +77,19 @@ public class FlashFuzzy implements AutoCloseable {
     }
 
     // Native methods
-    private static native void nativeInit(float threshold, int maxErrors, int maxResults);
+    private static native boolean nativeInit(float threshold, int maxErrors, int maxResults);
     private static native boolean nativeAdd(int id, String text);
+    private static native boolean nativeAddBytes(int id, byte[] text);
     private static native SearchResult[] nativeSearch(String query);
+    private static native SearchResult[] nativeSearchBytes(byte[] query);
+    private static native int nativeSearchInto(String query, int[] idsOut, float[] scoresOut, int[] startsOut, int[] endsOut);
+    private static native void nativeCancel();
     private static native boolean nativeRemove(int id);
     private static native void nativeReset();
     private static native int nativeGetCount();
     private static native void nativeSetThreshold(float threshold);
     private static native void nativeSetMaxErrors(int maxErrors);
+    private static native int nativeGetMaxErrors();
     private static native void nativeSetMaxResults(int maxResults);
 
     private float threshold;
@@ -129,6 +134,7 @@ public class FlashFuzzy implements AutoCloseable {
      * @param id Unique record ID
      * @param text Text to index
      * @return true if added successfully
+     * @throws IllegalStateException if the native engine is not initialized
      */
     public boolean add(int id, String text) {
         if (text == null || text.isEmpty()) {
@@ -137,6 +143,23 @@ public class FlashFuzzy implements AutoCloseable {
         return nativeAdd(id, text);
     }
 
+    /**
+     * Add a record from raw bytes.
+     * Unlike {@link #add(int, String)} the bytes are indexed as-is, so text
+     * holding NULs or invalid UTF-8 (e.g. from a corrupt import) isn't lost.
+     *
+     * @param id Unique record ID
+     * @param text Bytes to index
+     * @return true if added successfully
+     * @throws IllegalStateException if the native engine is not initialized
+     */
+    public boolean addBytes(int id, byte[] text) {
+        if (text == null || text.length == 0) {
+            return false;
+        }
+        return nativeAddBytes(id, text);
+    }
+
     /**
      * Add multiple records efficiently.
      *
@@ -158,6 +181,9 @@ public class FlashFuzzy implements AutoCloseable {
      *
      * @param query Search query
      * @return Array of search results sorted by score (descending)
+     * @throws IllegalStateException if the native engine is not initialized,
+     *         or if {@link SearchResult} cannot be instantiated (e.g. it was
+     *         renamed or stripped by ProGuard/R8)
      */
     public SearchResult[] search(String query) {
         if (query == null || query.isEmpty()) {
@@ -167,6 +193,57 @@ public class FlashFuzzy implements AutoCloseable {
         return results != null ? results : new SearchResult[0];
     }
 
+    /**
+     * Search for matching records with a raw byte query.
+     * Unlike {@link #search(String)} the query needn't be valid text, so it
+     * can match bytes indexed by {@link #addBytes(int, byte[])}. Result
+     * start and end are byte offsets into the record.
+     *
+     * @param query Query bytes
+     * @return Array of search results sorted by score (descending)
+     * @throws IllegalStateException if the native engine is not initialized
+     */
+    public SearchResult[] searchBytes(byte[] query) {
+        if (query == null || query.length == 0) {
+            return new SearchResult[0];
+        }
+        SearchResult[] results = nativeSearchBytes(query);
+        return results != null ? results : new SearchResult[0];
+    }
+
+    /**
+     * Search for matching records, writing them into caller-provided arrays.
+     *
+     * <p>Allocates no {@link SearchResult} objects, so the same arrays can
+     * be reused across searches. Result {@code i} (best first) goes to index
+     * {@code i} of each array; results past the shortest array are dropped.
+     *
+     * @param query Search query
+     * @param idsOut Receives record IDs
+     * @param scoresOut Receives scores (0.0-1.0)
+     * @param startsOut Receives match start offsets
+     * @param endsOut Receives match end offsets
+     * @return Number of results written
+     * @throws IllegalArgumentException if an array is null
+     * @throws IllegalStateException if the native engine is not initialized
+     */
+    public int searchInto(String query, int[] idsOut, float[] scoresOut, int[] startsOut, int[] endsOut) {
+        if (query == null || query.isEmpty()) {
+            return 0;
+        }
+        return nativeSearchInto(query, idsOut, scoresOut, startsOut, endsOut);
+    }
+
+    /**
+     * Stop the search in progress on another thread.
+     *
+     * <p>That {@link #search} call returns early with the matches found so
+     * far. Has no effect if no search is running.
+     */
+    public void cancel() {
+        nativeCancel();
+    }
+
     /**
      * Remove a record by ID.
      *
@@ -228,6 +305,12 @@ public class FlashFuzzy implements AutoCloseable {
     public int getMaxErrors() { return maxErrors; }
     public int getMaxResults() { return maxResults; }
 
+    /**
+     * Max errors the native engine is using, after clamping to its ceiling
+     * (MAX_ERRORS_CEIL, 8), the same value ff_get_max_errors reports.
+     */
+    public int getEffectiveMaxErrors() { return nativeGetMaxErrors(); }
+
     @Override
     public void close() {
         reset();
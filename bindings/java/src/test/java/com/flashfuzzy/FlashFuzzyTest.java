@@ -32,6 +32,16 @@ class FlashFuzzyTest {
         custom.close();
     }
 
+    @Test
+    void testReinitResetsState() {
+        ff.add(1, "Hello World");
+        FlashFuzzy again = new FlashFuzzy();
+        assertEquals(0, again.getCount());
+        assertTrue(again.add(2, "Hello Again"));
+        assertEquals(1, again.search("again").length);
+        again.close();
+    }
+
     @Test
     void testAdd() {
         assertTrue(ff.add(1, "Hello World"));
@@ -44,6 +54,31 @@ class FlashFuzzyTest {
         assertFalse(ff.add(1, null));
     }
 
+    @Test
+    void testAddBytesWithNul() {
+        byte[] text = {'k', 'e', 'y', 0, (byte) 0xFF, 'b', 'o', 'a', 'r', 'd'};
+        assertTrue(ff.addBytes(1, text));
+        assertFalse(ff.addBytes(2, new byte[0]));
+
+        SearchResult[] results = ff.search("board");
+        assertEquals(1, results.length);
+        assertEquals(1, results[0].getId());
+    }
+
+    @Test
+    void testSearchBytes() {
+        byte[] text = {'k', 'e', 'y', (byte) 0xFF, 'b', 'o', 'a', 'r', 'd'};
+        assertTrue(ff.addBytes(1, text));
+        ff.add(2, "Wireless Mouse");
+
+        SearchResult[] results = ff.searchBytes(new byte[] {'y', (byte) 0xFF, 'b', 'o'});
+        assertEquals(1, results.length);
+        assertEquals(1, results[0].getId());
+        assertEquals(2, results[0].getStart());
+        assertEquals(6, results[0].getEnd());
+        assertEquals(0, ff.searchBytes(new byte[0]).length);
+    }
+
     @Test
     void testSearchExact() {
         ff.add(1, "Wireless Headphones");
@@ -93,6 +128,22 @@ class FlashFuzzyTest {
         assertEquals(0, results.length);
     }
 
+    @Test
+    void testEqualScoresKeepInsertionOrder() {
+        // Identical texts score identically; IDs deliberately out of order
+        ff.add(30, "Apple Pie");
+        ff.add(10, "Apple Pie");
+        ff.add(20, "Apple Pie");
+
+        SearchResult[] results = ff.search("apple");
+
+        assertEquals(3, results.length);
+        assertEquals(results[0].getScore(), results[2].getScore(), 0.0);
+        assertEquals(30, results[0].getId());
+        assertEquals(10, results[1].getId());
+        assertEquals(20, results[2].getId());
+    }
+
     @Test
     void testRemove() {
         ff.add(1, "First");
@@ -130,6 +181,13 @@ class FlashFuzzyTest {
         assertThrows(IllegalArgumentException.class, () -> new FlashFuzzy(0.25f, 5, 50));
     }
 
+    @Test
+    void testEffectiveMaxErrors() {
+        assertEquals(2, ff.getEffectiveMaxErrors());
+        ff.setMaxErrors(3);
+        assertEquals(3, ff.getEffectiveMaxErrors());
+    }
+
     @Test
     void testInvalidMaxResults() {
         assertThrows(IllegalArgumentException.class, () -> new FlashFuzzy(0.25f, 2, 1000));
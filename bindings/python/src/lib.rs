@@ -3,7 +3,7 @@
 
 use pyo3::prelude::*;
 use pyo3::exceptions::PyValueError;
-use flash_fuzzy_core::{bitap, bloom::BloomFilter, BitapSearcher, ScoredResult, SearchConfig};
+use flash_fuzzy_core::{bitap, bloom::BloomFilter, insert_ranked, BitapSearcher, ScoredResult, SearchConfig};
 
 const MAX_RECORDS: usize = 100_000;
 const MAX_RESULTS: usize = 100;
@@ -71,6 +71,7 @@ impl FlashFuzzy {
                 threshold: (threshold * 1000.0) as u16,
                 max_errors,
                 max_results,
+                ..SearchConfig::default()
             },
         })
     }
@@ -113,11 +114,12 @@ impl FlashFuzzy {
         let pattern_bloom = searcher.bloom();
         let pattern_len = searcher.pattern_len();
 
-        let mut results: Vec<ScoredResult> = Vec::with_capacity(self.config.max_results);
+        let mut results = vec![ScoredResult::default(); self.config.max_results];
+        let mut count = 0;
 
-        for record in &self.records {
+        for (index, record) in self.records.iter().enumerate() {
             // Bloom filter pre-check
-            if !record.bloom.might_contain(pattern_bloom) {
+            if !record.bloom.might_contain_within(pattern_bloom, self.config.max_errors) {
                 continue;
             }
 
@@ -126,27 +128,28 @@ impl FlashFuzzy {
                 let score = bitap::compute_score(m.errors, pattern_len as u32, m.end_pos);
 
                 if score >= self.config.threshold {
-                    let start_pos = m.end_pos.saturating_sub(pattern_len);
-
-                    let result = ScoredResult::new(
+                    let mut result = ScoredResult::new(
                         record.id,
                         score,
-                        start_pos as u16,
-                        m.end_pos as u16,
+                        m.start_pos,
+                        m.end_pos,
                     );
+                    result.index = index as u32;
 
-                    // Insert sorted (descending by score)
-                    Self::insert_sorted(&mut results, result, self.config.max_results);
+                    // Same ranking as the FFI/WASM builds
+                    count = insert_ranked(&mut results, count, self.config.max_results, result);
                 }
             }
         }
 
+        results.truncate(count);
+
         // Convert to Python objects
         results.into_iter().map(|r| SearchResult {
             id: r.id,
             score: r.score as f32 / 1000.0,
-            start: r.start as u32,
-            end: r.end as u32,
+            start: r.start,
+            end: r.end,
         }).collect()
     }
 
@@ -249,18 +252,6 @@ impl FlashFuzzy {
         self.records.push(Record { id, text, bloom });
         Ok(1)
     }
-
-    fn insert_sorted(results: &mut Vec<ScoredResult>, result: ScoredResult, max_results: usize) {
-        if results.len() >= max_results {
-            if result.score <= results.last().unwrap().score {
-                return;
-            }
-            results.pop();
-        }
-
-        let pos = results.iter().position(|r| r.score < result.score).unwrap_or(results.len());
-        results.insert(pos, result);
-    }
 }
 
 /// Flash-Fuzzy: High-performance fuzzy search engine
@@ -6,24 +6,69 @@
 interface WasmExports {
   memory: WebAssembly.Memory;
   init(): void;
-  reset(): void;
+  clearData(): void;
   getWriteBuffer(len: number): number;
   commitWrite(len: number): void;
   addRecord(id: number): number;
+  addRecordWithBloom(id: number, bloom: bigint): number;
+  updateRecord(id: number): number;
   removeRecord(id: number): number;
   compact(): number;
+  setAutoCompactRatio(ratio: number): void;
   getRecordCount(): number;
+  getRecordIds(outPtr: number, cap: number): number;
+  indexDigest(): bigint;
   setThreshold(threshold: number): void;
+  setAutoThreshold(enabled: number): void;
   setMaxErrors(maxErrors: number): void;
+  getMaxErrors(): number;
   setMaxResults(maxResults: number): void;
-  preparePattern(): void;
+  setBloomHash(scheme: number): void;
+  setFoldCase(enabled: number): void;
+  setFoldDiacritics(enabled: number): void;
+  setMinScoreGap(gap: number): void;
+  setMinPatternBits(bits: number): void;
+  setMinMatchRatio(ratio: number): void;
+  setCollapseWhitespace(enabled: number): void;
+  originalOffset(ptr: number, len: number, pos: number): number;
+  preparePattern(): number;
   search(): number;
+  searchLimited(maxRecords: number): number;
+  searchTopK(k: number): number;
+  searchWindow(maxOffset: number): number;
+  searchTooBroad(): number;
+  suggest(outPtr: number, cap: number): number;
+  setCandidateCache(enabled: number): void;
+  rethreshold(threshold: number): number;
+  resultDiff(outAdded: number, outRemoved: number, cap: number): number;
+  addMultiPattern(): number;
+  clearMultiPatterns(): void;
+  searchMulti(): number;
+  getMultiResultCount(pattern: number): number;
+  getMultiResultId(pattern: number, i: number): number;
+  getMultiResultScore(pattern: number, i: number): number;
+  getMultiMatchMask(id: number): number;
+  explainResult(i: number, outPtr: number): number;
+  findInRecord(id: number, fromOffset: number): number;
+  getLimits(outPtr: number): number;
+  getResultEditProfile(i: number, outPtr: number): number;
+  getCharMask(byte: number): number;
   getResultId(i: number): number;
   getResultScore(i: number): number;
   getResultStart(i: number): number;
   getResultEnd(i: number): number;
+  getResultRank(i: number): number;
+  resultsToJson(outPtr: number, cap: number): number;
+  getResultRelative(i: number): number;
+  getResultDensity(i: number): number;
+  getResultConfidence(i: number): number;
+  resultsCommonPrefix(): number;
+  getResultCharStart(i: number): number;
+  getResultCharEnd(i: number): number;
   getStringPoolUsed(): number;
   getAvailableMemory(): number;
+  getPoolReclaimable(): number;
+  getRecordHighWater(): number;
 }
 
 export interface FlashFuzzyOptions {
@@ -53,6 +98,8 @@ export interface IndexStats {
   stringPoolUsed: number;
   availableMemory: number;
   usedMemory: number; // Alias for stringPoolUsed
+  poolReclaimable: number; // Bytes freed by removals, recovered by compaction
+  recordHighWater: number;
 }
 
 export class FlashFuzzy {
@@ -225,7 +272,7 @@ export class FlashFuzzy {
         id: this.wasm.getResultId(i),
         score: this.wasm.getResultScore(i) / 1000,
         matches: {
-          _default: [[this.wasm.getResultStart(i), this.wasm.getResultEnd(i)]],
+          _default: [[this.wasm.getResultCharStart(i), this.wasm.getResultCharEnd(i)]],
         },
       };
     }
@@ -242,7 +289,7 @@ export class FlashFuzzy {
   }
 
   reset(): void {
-    this.wasm?.reset();
+    this.wasm?.clearData();
   }
 
   setThreshold(threshold: number): void {
@@ -259,7 +306,7 @@ export class FlashFuzzy {
 
   stats(): IndexStats {
     if (!this.wasm) {
-      return { recordCount: 0, stringPoolUsed: 0, availableMemory: 0, usedMemory: 0 };
+      return { recordCount: 0, stringPoolUsed: 0, availableMemory: 0, usedMemory: 0, poolReclaimable: 0, recordHighWater: 0 };
     }
     const stringPoolUsed = this.wasm.getStringPoolUsed();
     return {
@@ -267,6 +314,8 @@ export class FlashFuzzy {
       stringPoolUsed,
       availableMemory: this.wasm.getAvailableMemory(),
       usedMemory: stringPoolUsed, // Alias
+      poolReclaimable: this.wasm.getPoolReclaimable(),
+      recordHighWater: this.wasm.getRecordHighWater(),
     };
   }
 
@@ -0,0 +1,93 @@
+//! Hot-path benchmarks: bitap search, bloom construction and a full
+//! add-then-search workload over 1k records.
+//!
+//! Run with `cargo bench -p flash-fuzzy-core`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flash_fuzzy_core::{search_records, BitapSearcher, BloomFilter, CharSet, RecordView, ScoredResult, SearchConfig};
+
+const WORDS: &[&str] = &[
+    "wireless", "mechanical", "keyboard", "mouse", "monitor", "laptop", "stand", "cable", "adapter", "charger",
+    "headphones", "speaker", "webcam", "microphone", "ergonomic", "portable", "gaming", "office", "pro", "mini",
+];
+
+/// Deterministic product-like text of roughly `len` bytes
+fn text(seed: usize, len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 16);
+    let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+    while out.len() < len {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        if !out.is_empty() {
+            out.push(b' ');
+        }
+        out.extend_from_slice(WORDS[(state >> 33) % WORDS.len()].as_bytes());
+    }
+    out.truncate(len);
+    out
+}
+
+fn bench_bitap(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bitap_search");
+    for &text_len in &[16, 64, 256] {
+        let haystack = text(7, text_len);
+        for &pattern in &[&b"pro"[..], b"keybord", b"ergonomic keyboard", b"wireless mechanical keyboard pro"] {
+            let searcher = BitapSearcher::new(pattern);
+            group.throughput(Throughput::Bytes(text_len as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("pattern_{}", pattern.len()), text_len),
+                &haystack,
+                |b, haystack| b.iter(|| searcher.search(black_box(haystack), black_box(2))),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_bloom(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bloom_from_text");
+    for &text_len in &[16, 64, 256] {
+        let haystack = text(11, text_len);
+        group.throughput(Throughput::Bytes(text_len as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(text_len), &haystack, |b, haystack| {
+            b.iter(|| BloomFilter::from_text(black_box(haystack)))
+        });
+    }
+    group.finish();
+}
+
+/// Build the pool and record table as the bindings do on `add`
+fn add_records(count: usize) -> (Vec<u8>, Vec<RecordView>) {
+    let mut pool = Vec::new();
+    let mut records = Vec::with_capacity(count);
+    for i in 0..count {
+        let t = text(i, 20 + i % 40);
+        records.push(RecordView {
+            id: i as u32,
+            text_start: pool.len() as u32,
+            text_len: t.len() as u32,
+            bloom: BloomFilter::from_text(&t).bits(),
+            chars: CharSet::from_text(&t),
+            added_seq: i as u32,
+            active: true,
+            ..RecordView::default()
+        });
+        pool.extend_from_slice(&t);
+    }
+    (pool, records)
+}
+
+fn bench_workload(c: &mut Criterion) {
+    let config = SearchConfig::default();
+    let searcher = BitapSearcher::new(b"mechanical keybord");
+    let mut out = vec![ScoredResult::default(); config.max_results];
+
+    c.bench_function("add_1k", |b| b.iter(|| add_records(black_box(1000))));
+
+    let (pool, records) = add_records(1000);
+    c.bench_function("search_1k", |b| {
+        b.iter(|| search_records(black_box(&records), &pool, &searcher, &config, &mut out))
+    });
+}
+
+criterion_group!(benches, bench_bitap, bench_bloom, bench_workload);
+criterion_main!(benches);
@@ -1,49 +1,258 @@
 //! Bitap (Shift-Or) algorithm for fuzzy string matching
 //!
 //! Implements Wu-Manber extension for approximate matching with errors.
+//! Each error level keeps one state row with a bit per pattern char. The
+//! row width is picked from the pattern length: a `u32` for up to 32
+//! chars, a `u64` for up to 64, and chained `u64` words beyond that. All
+//! widths run the same automaton, so matches are identical.
+//!
+//! A match only starts where the text holds the pattern's first char:
+//! the first state bit is seeded solely by an exact (case-folded) match,
+//! and the error transitions only carry bits that are already set. So a
+//! mistyped first letter never matches ("xeyboard" misses "keyboard"),
+//! while the error budget applies from the second char on.
+//!
+//! Exact hits skip the automaton: with no error budget they are the only
+//! possible matches, and a best-match search takes a zero-error hit over
+//! any fuzzy one. `find_exact` scans for the pattern's first char and
+//! compares the rest in place; bitap only runs when that finds nothing
+//! and errors are allowed.
+//!
+//! The automaton only reports where a match ends. Under errors the
+//! matched region isn't `pattern_len` bytes long, so each reported match
+//! gets its true start from a small edit-distance table over the bytes
+//! before its end (see `match_start`).
+
+use crate::bloom::{BloomFilter, BloomHash};
+use crate::charset::CharSet;
+use crate::fold::{self, Folding};
+use crate::types::{EditProfile, ScoreBreakdown, SearchMatch};
+use crate::{fold_byte, MAX_PATTERN_LEN};
+
+/// `u64` words per state row for patterns longer than 64 chars
+const MULTI_WORDS: usize = (MAX_PATTERN_LEN + 63) / 64;
+
+/// Most error levels tracked; higher budgets are clamped
+const MAX_SCAN_ERRORS: u32 = 32;
+
+/// One bitap state row: bit `i` set means pattern[..=i] matched so far
+trait Row: Copy {
+    const ZERO: Self;
+
+    /// Shift every bit one position up, carrying across words
+    fn shl1(self) -> Self;
+    fn or(self, other: Self) -> Self;
+    fn and(self, other: Self) -> Self;
+    fn set(&mut self, bit: usize);
+    /// True if any bit is set in both rows
+    fn meets(self, other: Self) -> bool;
+}
+
+impl Row for u32 {
+    const ZERO: Self = 0;
+
+    #[inline(always)]
+    fn shl1(self) -> Self {
+        self << 1
+    }
+    #[inline(always)]
+    fn or(self, other: Self) -> Self {
+        self | other
+    }
+    #[inline(always)]
+    fn and(self, other: Self) -> Self {
+        self & other
+    }
+    #[inline(always)]
+    fn set(&mut self, bit: usize) {
+        *self |= 1 << bit;
+    }
+    #[inline(always)]
+    fn meets(self, other: Self) -> bool {
+        self & other != 0
+    }
+}
+
+impl Row for u64 {
+    const ZERO: Self = 0;
+
+    #[inline(always)]
+    fn shl1(self) -> Self {
+        self << 1
+    }
+    #[inline(always)]
+    fn or(self, other: Self) -> Self {
+        self | other
+    }
+    #[inline(always)]
+    fn and(self, other: Self) -> Self {
+        self & other
+    }
+    #[inline(always)]
+    fn set(&mut self, bit: usize) {
+        *self |= 1 << bit;
+    }
+    #[inline(always)]
+    fn meets(self, other: Self) -> bool {
+        self & other != 0
+    }
+}
+
+impl<const N: usize> Row for [u64; N] {
+    const ZERO: Self = [0; N];
+
+    #[inline(always)]
+    fn shl1(self) -> Self {
+        let mut out = [0; N];
+        let mut carry = 0;
+        for (dst, word) in out.iter_mut().zip(self) {
+            *dst = (word << 1) | carry;
+            carry = word >> 63;
+        }
+        out
+    }
+    #[inline(always)]
+    fn or(self, other: Self) -> Self {
+        core::array::from_fn(|i| self[i] | other[i])
+    }
+    #[inline(always)]
+    fn and(self, other: Self) -> Self {
+        core::array::from_fn(|i| self[i] & other[i])
+    }
+    #[inline(always)]
+    fn set(&mut self, bit: usize) {
+        self[bit / 64] |= 1 << (bit % 64);
+    }
+    #[inline(always)]
+    fn meets(self, other: Self) -> bool {
+        self.iter().zip(other).any(|(a, b)| a & b != 0)
+    }
+}
+
+/// Per-char pattern masks at the width chosen for the pattern
+// Boxing the wide variants would need an allocator, which no_std lacks
+#[allow(clippy::large_enum_variant)]
+enum Masks {
+    U32([u32; 256]),
+    U64([u64; 256]),
+    Multi([[u64; MULTI_WORDS]; 256]),
+}
+
+impl Masks {
+    /// Masks for `pattern` at the narrowest width that holds it
+    fn for_pattern(pattern: &[u8], case: bool) -> Self {
+        match pattern.len() {
+            0..=32 => Masks::U32(char_masks(pattern, case)),
+            33..=64 => Masks::U64(char_masks(pattern, case)),
+            _ => Masks::Multi(char_masks(pattern, case)),
+        }
+    }
+
+    /// Replace the masks of `old` with those of `pattern`
+    /// Reuses the storage when both need the same width, touching only the
+    /// entries `old` set rather than all 256.
+    fn reset(&mut self, old: &[u8], pattern: &[u8], case: bool) {
+        match (self, pattern.len()) {
+            (Masks::U32(masks), 0..=32) => reset_masks(masks, old, pattern, case),
+            (Masks::U64(masks), 33..=64) => reset_masks(masks, old, pattern, case),
+            (Masks::Multi(masks), 65..) => reset_masks(masks, old, pattern, case),
+            (masks, _) => *masks = Masks::for_pattern(pattern, case),
+        }
+    }
+}
+
+/// Build char masks for `pattern`, case-insensitive if `case`
+fn char_masks<R: Row>(pattern: &[u8], case: bool) -> [R; 256] {
+    let mut masks = [R::ZERO; 256];
+    set_masks(&mut masks, pattern, case);
+    masks
+}
 
-use crate::bloom::{to_lower, BloomFilter};
-use crate::types::SearchMatch;
-use crate::MAX_PATTERN_LEN;
+/// Set the mask bits for `pattern`, case-insensitive if `case`
+fn set_masks<R: Row>(masks: &mut [R; 256], pattern: &[u8], case: bool) {
+    for (i, &ch) in pattern.iter().enumerate() {
+        // Set bit for the original case, and for lowercase if folding
+        masks[ch as usize].set(i);
+        if case {
+            masks[fold_byte(ch) as usize].set(i);
+        }
+    }
+}
+
+/// Clear the masks `old` set, then set those of `pattern`
+fn reset_masks<R: Row>(masks: &mut [R; 256], old: &[u8], pattern: &[u8], case: bool) {
+    for &ch in old {
+        masks[fold_byte(ch) as usize] = R::ZERO;
+        masks[ch as usize] = R::ZERO;
+    }
+    set_masks(masks, pattern, case);
+}
 
 /// Bitap searcher with pre-computed pattern masks
 pub struct BitapSearcher {
+    /// Pattern bytes as given, diacritics stripped if folding them
+    pattern: [u8; MAX_PATTERN_LEN],
     /// Character bitmasks (256 ASCII chars)
-    char_masks: [u32; 256],
+    char_masks: Masks,
     /// Pattern length
     pattern_len: usize,
     /// Bloom filter for the pattern
     pattern_bloom: BloomFilter,
+    /// Exact set of the pattern's chars
+    pattern_chars: CharSet,
+    /// Scheme `pattern_bloom` was built with
+    bloom_hash: BloomHash,
+    /// What matching ignores
+    folding: Folding,
 }
 
 impl BitapSearcher {
     /// Create a new Bitap searcher from a pattern
+    /// Patterns longer than `MAX_PATTERN_LEN` are truncated.
     pub fn new(pattern: &[u8]) -> Self {
-        let len = pattern.len().min(MAX_PATTERN_LEN);
-        let mut char_masks = [0u32; 256];
-        let mut bloom_bits = 0u64;
-
-        for (i, &ch) in pattern.iter().take(len).enumerate() {
-            let lower = to_lower(ch);
-            let bit = 1u32 << i;
+        Self::with_folding(pattern, Folding::CASE)
+    }
 
-            // Set bit for lowercase
-            char_masks[lower as usize] |= bit;
+    /// Create a searcher that ignores what `folding` says
+    /// With diacritic folding the pattern's accented letters are stripped
+    /// to their base before anything else, so "naïve" searches as "naive".
+    pub fn with_folding(pattern: &[u8], folding: Folding) -> Self {
+        let mut stripped = [0u8; MAX_PATTERN_LEN];
+        let len = folding.strip(pattern, &mut stripped);
+        let pattern = &stripped[..len];
+        Self { folding, ..Self::with_masks(pattern, Masks::for_pattern(pattern, folding.case)) }
+    }
 
-            // Also set for original case if different
-            if lower != ch {
-                char_masks[ch as usize] |= bit;
-            }
+    /// Rebuild in place for a new pattern, keeping the bloom hash and folding
+    ///
+    /// Gives the same searcher as `new` but is cheaper: when the row width
+    /// doesn't change only the mask entries of the old and new pattern
+    /// chars are written, where `new` fills up to 4KB of masks and moves
+    /// the result. Lets one searcher serve a stream of queries without
+    /// rebuilding from scratch.
+    pub fn reset(&mut self, pattern: &[u8]) {
+        let mut stripped = [0u8; MAX_PATTERN_LEN];
+        let len = self.folding.strip(pattern, &mut stripped);
+        let pattern = &stripped[..len];
+        self.char_masks.reset(&self.pattern[..self.pattern_len], pattern, self.folding.case);
+        self.pattern[..len].copy_from_slice(pattern);
+        self.pattern_len = len;
+        self.pattern_bloom = BloomFilter::with_hash(pattern, self.bloom_hash);
+        self.pattern_chars = CharSet::from_text(pattern);
+    }
 
-            // Build bloom filter
-            let bloom_idx = (lower & 0x3F) as u64;
-            bloom_bits |= 1u64 << bloom_idx;
-        }
+    fn with_masks(pattern: &[u8], char_masks: Masks) -> Self {
+        let mut stored = [0u8; MAX_PATTERN_LEN];
+        stored[..pattern.len()].copy_from_slice(pattern);
 
         Self {
+            pattern: stored,
             char_masks,
-            pattern_len: len,
-            pattern_bloom: BloomFilter(bloom_bits),
+            pattern_len: pattern.len(),
+            pattern_bloom: BloomFilter::from_text(pattern),
+            pattern_chars: CharSet::from_text(pattern),
+            bloom_hash: BloomHash::default(),
+            folding: Folding::CASE,
         }
     }
 
@@ -53,92 +262,412 @@ impl BitapSearcher {
         self.pattern_len
     }
 
+    /// Get the pattern bytes
+    #[inline]
+    pub fn pattern(&self) -> &[u8] {
+        &self.pattern[..self.pattern_len]
+    }
+
     /// Get the pattern's bloom filter
     #[inline]
     pub fn bloom(&self) -> BloomFilter {
         self.pattern_bloom
     }
 
+    /// Get what matching ignores
+    #[inline]
+    pub fn folding(&self) -> Folding {
+        self.folding
+    }
+
+    /// Mask of pattern positions holding byte `c`, for the first 64 chars
+    /// Bit `i` is set if `pattern[i]` equals `c` after case folding (if
+    /// on), so 'a' and 'A' get the same mask. For debugging pattern preparation.
+    pub fn char_mask(&self, c: u8) -> u64 {
+        let c = self.folding.byte(c) as usize;
+        match &self.char_masks {
+            Masks::U32(masks) => masks[c] as u64,
+            Masks::U64(masks) => masks[c],
+            Masks::Multi(masks) => masks[c][0],
+        }
+    }
+
+    /// Rebuild the pattern's bloom with `hash`, to match records built with it
+    pub fn with_bloom_hash(mut self, hash: BloomHash) -> Self {
+        self.pattern_bloom = BloomFilter::with_hash(self.pattern(), hash);
+        self.bloom_hash = hash;
+        self
+    }
+
+    /// Get the scheme the pattern's bloom was built with
+    #[inline]
+    pub fn bloom_hash(&self) -> BloomHash {
+        self.bloom_hash
+    }
+
+    /// Get the exact set of the pattern's chars
+    #[inline]
+    pub fn chars(&self) -> CharSet {
+        self.pattern_chars
+    }
+
+    /// Bits per state row: 32, 64, or a multiple of 64 for chained words
+    /// Exposed to check which implementation a pattern length selects.
+    pub fn row_bits(&self) -> usize {
+        match self.char_masks {
+            Masks::U32(_) => 32,
+            Masks::U64(_) => 64,
+            Masks::Multi(_) => MULTI_WORDS * 64,
+        }
+    }
+
     /// Search for pattern in text with up to max_errors
     /// Returns the best match found (lowest error count)
     pub fn search(&self, text: &[u8], max_errors: u32) -> Option<SearchMatch> {
+        self.scan(text, max_errors, Scan::Best)
+    }
+
+    /// Find every occurrence of the pattern, left to right
+    ///
+    /// Each occurrence is the earliest match end after the previous one's
+    /// start (`overlap`) or end (non-overlapping). Overlapping mode reports
+    /// every alignment, e.g. "aa" in "aaaa" ends at 2, 3 and 4; without
+    /// overlap it ends at 2 and 4. Returns the number written to `out`.
+    pub fn search_all(&self, text: &[u8], max_errors: u32, overlap: bool, out: &mut [SearchMatch]) -> usize {
+        let mut count = 0;
+        self.each_match(text, max_errors, overlap, |m| {
+            if count == out.len() {
+                return false;
+            }
+            out[count] = m;
+            count += 1;
+            true
+        });
+        count
+    }
+
+    /// First occurrence `search_all` would report in `text[from..]`
+    /// Positions are in `text`. Resume from the previous match's start + 1
+    /// to step through every alignment, or from its end to skip overlaps.
+    pub fn next_match(&self, text: &[u8], from: usize, max_errors: u32) -> Option<SearchMatch> {
+        let m = self.scan(text.get(from..)?, max_errors, Scan::First)?;
+        Some(SearchMatch { start_pos: from + m.start_pos, end_pos: from + m.end_pos, ..m })
+    }
+
+    /// Count the non-overlapping occurrences `search_all` would report
+    pub fn count_matches(&self, text: &[u8], max_errors: u32) -> usize {
+        let mut count = 0;
+        self.each_match(text, max_errors, false, |_| {
+            count += 1;
+            true
+        });
+        count
+    }
+
+    /// Pass each occurrence to `f` until it returns false
+    fn each_match(&self, text: &[u8], max_errors: u32, overlap: bool, mut f: impl FnMut(SearchMatch) -> bool) {
+        let mut from = 0;
+        while from < text.len() {
+            let Some(m) = self.scan(&text[from..], max_errors, Scan::First) else {
+                break;
+            };
+            let end_pos = from + m.end_pos;
+            if !f(SearchMatch { start_pos: from + m.start_pos, end_pos, ..m }) {
+                break;
+            }
+
+            from = if overlap {
+                from + m.start_pos + 1
+            } else {
+                end_pos
+            };
+        }
+    }
+
+    /// Run the automaton over text, reporting the match `scan` selects
+    fn scan(&self, text: &[u8], max_errors: u32, scan: Scan) -> Option<SearchMatch> {
         if self.pattern_len == 0 || text.is_empty() {
             return None;
         }
 
-        let pattern_len = self.pattern_len as u32;
+        let effective_max_errors = effective_errors(self.pattern_len, max_errors);
+
+        // Accented letters vary in width, so the in-place compare can't
+        // see them; the automaton handles them
+        let folding = self.folding;
+        if (effective_max_errors == 0 || scan == Scan::Best) && !folding.diacritics {
+            let exact = self.find_exact(text, scan);
+            if exact.is_some() || effective_max_errors == 0 {
+                return exact;
+            }
+        }
 
-        // Adaptive max_errors based on pattern length
-        let effective_max_errors = if pattern_len <= 3 {
-            0 // Exact match only for very short patterns
-        } else if pattern_len <= 5 {
-            max_errors.min(1) // Max 1 error for short patterns
+        let found = if folding.diacritics {
+            self.scan_units(fold::units(text, folding), text.len(), effective_max_errors, scan)
         } else {
-            max_errors
-        };
+            let units = text.iter().enumerate().map(|(i, &c)| (i + 1, folding.byte(c)));
+            self.scan_units(units, text.len(), effective_max_errors, scan)
+        }?;
+        Some(SearchMatch { start_pos: self.match_start(text, found), ..found })
+    }
 
-        // Initialize R array (1 = matched position)
-        let mut r = [0u32; MAX_PATTERN_LEN + 1];
+    /// Run `scan_rows` with the masks' row width
+    #[inline]
+    fn scan_units(
+        &self,
+        units: impl Iterator<Item = (usize, u8)>,
+        text_len: usize,
+        max_errors: u32,
+        scan: Scan,
+    ) -> Option<SearchMatch> {
+        match &self.char_masks {
+            Masks::U32(masks) => scan_rows(masks, self.pattern_len, units, text_len, max_errors, scan),
+            Masks::U64(masks) => scan_rows(masks, self.pattern_len, units, text_len, max_errors, scan),
+            Masks::Multi(masks) => scan_rows(masks, self.pattern_len, units, text_len, max_errors, scan),
+        }
+    }
 
-        let pattern_mask = (1u32 << self.pattern_len) - 1;
-        let match_bit = 1u32 << (self.pattern_len - 1);
+    /// Start of the pattern's best alignment ending at `m.end_pos`
+    ///
+    /// A match with `k` errors spans at most `pattern_len + k` bytes, so
+    /// an edit-distance table over that window, filled right to left,
+    /// gives the cost of the pattern starting at each byte. As in the
+    /// automaton the first char must match exactly; on a tie the later
+    /// (tighter) start wins. Only runs for matches found, so texts that
+    /// don't match never pay for it. Windows and costs count chars, so an
+    /// accented letter is one when folding diacritics.
+    fn match_start(&self, text: &[u8], m: SearchMatch) -> usize {
+        let pattern = self.pattern();
+        let len = pattern.len();
+        let end = m.end_pos;
+        let folding = self.folding;
+        let first = folding.byte(pattern[0]);
 
-        let mut best_errors = effective_max_errors + 1;
-        let mut best_pos = 0usize;
+        // col[j]: edit distance from pattern[j..] to text[i..end]
+        let mut col = [0u32; MAX_PATTERN_LEN + 1];
+        for (j, cost) in col[..=len].iter_mut().enumerate() {
+            *cost = (len - j) as u32;
+        }
 
-        for (pos, &ch) in text.iter().enumerate() {
-            let lower = to_lower(ch);
-            let char_mask = self.char_masks[lower as usize];
+        let mut best = (u32::MAX, end.saturating_sub(len));
+        let mut i = end;
+        for seen in 1..=len + m.errors as usize {
+            if i == 0 {
+                break;
+            }
+            let c;
+            (i, c) = fold::unit_before(text, i, folding);
+            // Starting at i spends text[i] on the first char
+            if c == first && col[1] < best.0 {
+                best = (col[1], i);
+            }
 
-            // Save old values for error propagation
-            let mut old_r = r[0];
+            let mut diag = col[len];
+            col[len] = seen as u32;
+            for j in (0..len).rev() {
+                let skip_text = col[j] + 1;
+                let substitute = diag + (c != folding.byte(pattern[j])) as u32;
+                diag = col[j];
+                col[j] = substitute.min(skip_text).min(col[j + 1] + 1);
+            }
+        }
+        best.1
+    }
 
-            // Exact match: shift left, seed new match at pos 0, filter by char
-            r[0] = ((r[0] << 1) | 1) & char_mask;
+    /// Earliest exact (case-folded) occurrence, or the one ending the text
+    /// for `Scan::End`; the same match bitap reports with 0 errors
+    fn find_exact(&self, text: &[u8], scan: Scan) -> Option<SearchMatch> {
+        let pattern = self.pattern();
+        let last_start = text.len().checked_sub(pattern.len())?;
+        let byte = |c| self.folding.byte(c);
+        let first = byte(pattern[0]);
 
-            // Error levels
-            for k in 1..=(effective_max_errors as usize) {
-                let new_r = r[k];
+        let mut from = if scan == Scan::End { last_start } else { 0 };
+        while let Some(i) = text[from..=last_start].iter().position(|&c| byte(c) == first) {
+            let start = from + i;
+            let end_pos = start + pattern.len();
+            let mut rest = text[start + 1..end_pos].iter().zip(&pattern[1..]);
+            if rest.all(|(&c, &p)| byte(c) == byte(p)) {
+                return Some(SearchMatch { errors: 0, start_pos: start, end_pos });
+            }
+            from = start + 1;
+        }
+        None
+    }
 
-                // Error transitions:
-                // - Exact match at this level
-                // - Substitution: was at pos i with k-1 errors, now at i+1 with k
-                // - Deletion: was at pos i with k-1 errors, stay at i with k
-                // - Insertion: was at pos i with k errors, now at i+1 with k
-                r[k] = (((r[k] << 1) | 1) & char_mask) |  // exact match
-                       (old_r << 1) |                      // substitution
-                       old_r |                              // deletion
-                       (r[k - 1] << 1);                     // insertion
+    /// Split the errors of match `m` in `text` into insertions, deletions
+    /// and substitutions
+    ///
+    /// Traces the cheapest alignment of the pattern with the region
+    /// `m.start_pos..m.end_pos`, keeping per-kind counts alongside each
+    /// cost instead of a full table. On equal cost a substitution is
+    /// preferred over an insertion, and that over a deletion.
+    pub fn edit_profile(&self, text: &[u8], m: SearchMatch) -> EditProfile {
+        let pattern = self.pattern();
+        let len = pattern.len();
 
-                old_r = new_r;
-            }
+        // col[j]: cheapest edits turning pattern[..j] into the region so far
+        let mut col = [(0u32, EditProfile::default()); MAX_PATTERN_LEN + 1];
+        for (j, cell) in col[..=len].iter_mut().enumerate() {
+            *cell = (j as u32, EditProfile { deletions: j as u32, ..EditProfile::default() });
+        }
 
-            // Mask to pattern length
-            for i in 0..=(effective_max_errors as usize) {
-                r[i] &= pattern_mask;
+        let folding = self.folding;
+        for (_, c) in fold::units(&text[m.start_pos..m.end_pos], folding) {
+            let mut diag = col[0];
+            col[0].0 += 1;
+            col[0].1.insertions += 1;
+            for j in 1..=len {
+                let up = col[j];
+                let mut best = diag;
+                if c != folding.byte(pattern[j - 1]) {
+                    best.0 += 1;
+                    best.1.substitutions += 1;
+                }
+                if up.0 + 1 < best.0 {
+                    best = (up.0 + 1, EditProfile { insertions: up.1.insertions + 1, ..up.1 });
+                }
+                let left = col[j - 1];
+                if left.0 + 1 < best.0 {
+                    best = (left.0 + 1, EditProfile { deletions: left.1.deletions + 1, ..left.1 });
+                }
+                diag = up;
+                col[j] = best;
             }
+        }
+        col[len].1
+    }
 
-            // Check for matches
-            for k in 0..=effective_max_errors {
-                if (r[k as usize] & match_bit) != 0 {
-                    if k < best_errors {
-                        best_errors = k;
-                        best_pos = pos + 1;
-                    }
-                    break;
+    /// Most bytes a match with `max_errors` errors can span
+    /// Twice the chars when folding diacritics, as accented letters take two.
+    #[inline]
+    fn window(&self, max_errors: u32) -> usize {
+        let chars = self.pattern_len + max_errors as usize;
+        if self.folding.diacritics {
+            chars * 2
+        } else {
+            chars
+        }
+    }
+
+    /// Search for pattern at the start of text (prefix match)
+    ///
+    /// Only the first `pattern_len + max_errors` chars are scanned, so a
+    /// match must end within that window. With 0 errors this is an exact
+    /// case-insensitive prefix test.
+    pub fn search_anchored(&self, text: &[u8], max_errors: u32) -> Option<SearchMatch> {
+        let window = self.window(max_errors).min(text.len());
+        self.search(&text[..window], max_errors)
+    }
+
+    /// Search for pattern at the end of text (suffix match)
+    ///
+    /// Only matches ending at the last text byte count, so "txt" matches
+    /// "notes.txt" but not "notes.txt.bak". A match with `k` errors spans
+    /// at most `pattern_len + k` bytes, so only that tail is scanned.
+    pub fn search_anchored_end(&self, text: &[u8], max_errors: u32) -> Option<SearchMatch> {
+        let from = text.len().saturating_sub(self.window(max_errors));
+        self.scan(&text[from..], max_errors, Scan::End).map(|m| SearchMatch {
+            start_pos: from + m.start_pos,
+            end_pos: from + m.end_pos,
+            ..m
+        })
+    }
+}
+
+/// Which match `scan_rows` reports
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    /// Lowest-error match anywhere (earliest on ties)
+    Best,
+    /// Earliest match end
+    First,
+    /// Lowest-error match ending at the last byte
+    End,
+}
+
+/// The automaton itself, generic over the state row width
+///
+/// Steps once per `(end, byte)` char of `units`, already folded; `text_len`
+/// is where the last one ends.
+fn scan_rows<R: Row>(
+    char_masks: &[R; 256],
+    pattern_len: usize,
+    units: impl Iterator<Item = (usize, u8)>,
+    text_len: usize,
+    max_errors: u32,
+    scan: Scan,
+) -> Option<SearchMatch> {
+    // Initialize R array (1 = matched position)
+    let mut r = [R::ZERO; MAX_SCAN_ERRORS as usize + 1];
+
+    // Bits only ever move up, so bits past the pattern never reach
+    // `match_bit` and rows need no masking to the pattern length
+    let mut match_bit = R::ZERO;
+    match_bit.set(pattern_len - 1);
+    let mut seed = R::ZERO;
+    seed.set(0);
+
+    // Exclusive ranges: `RangeInclusive` may not inline at opt-level "z"
+    let rows = max_errors as usize + 1;
+    let mut best_errors = max_errors + 1;
+    let mut best_pos = 0usize;
+
+    for (end, ch) in units {
+        let char_mask = char_masks[ch as usize];
+
+        // Save old values for error propagation
+        let mut old_r = r[0];
+
+        // Exact match: shift left, seed new match at pos 0, filter by char
+        r[0] = r[0].shl1().or(seed).and(char_mask);
+
+        // Error levels
+        for k in 1..rows {
+            let new_r = r[k];
+
+            // Error transitions:
+            // - Exact match at this level
+            // - Substitution: was at pos i with k-1 errors, now at i+1 with k
+            // - Deletion: was at pos i with k-1 errors, stay at i with k
+            // - Insertion: was at pos i with k errors, now at i+1 with k
+            r[k] = r[k].shl1().or(seed).and(char_mask)     // exact match
+                .or(old_r.shl1())                          // substitution
+                .or(old_r)                                 // deletion
+                .or(r[k - 1].shl1());                      // insertion
+
+            old_r = new_r;
+        }
+
+        // Check for matches
+        if scan == Scan::End && end < text_len {
+            continue;
+        }
+        for (k, row) in r[..rows].iter().enumerate() {
+            if row.meets(match_bit) {
+                if (k as u32) < best_errors {
+                    best_errors = k as u32;
+                    best_pos = end;
                 }
+                break;
             }
         }
 
-        if best_errors <= effective_max_errors {
-            Some(SearchMatch {
-                errors: best_errors,
-                end_pos: best_pos,
-            })
-        } else {
-            None
+        if scan == Scan::First && best_errors <= max_errors {
+            break;
         }
     }
+
+    if best_errors <= max_errors {
+        // `scan` fills in the start
+        Some(SearchMatch {
+            errors: best_errors,
+            start_pos: 0,
+            end_pos: best_pos,
+        })
+    } else {
+        None
+    }
 }
 
 /// Compute score from match result
@@ -147,20 +676,75 @@ impl BitapSearcher {
 /// - Base: 1000 - (errors * 250)
 /// - Position bonus: +50 for start, +25 for near start
 pub fn compute_score(errors: u32, pattern_len: u32, end_pos: usize) -> u16 {
-    let base = 1000u32.saturating_sub(errors * 250);
+    let base = base_score(errors);
+    let pos_bonus = position_bonus(end_pos.saturating_sub(pattern_len as usize));
 
-    let start_pos = end_pos.saturating_sub(pattern_len as usize);
+    let score = base + pos_bonus;
+    if score > 1000 { 1000 } else { score as u16 }
+}
+
+/// Components of `compute_score`
+pub fn score_breakdown(errors: u32, pattern_len: u32, end_pos: usize) -> ScoreBreakdown {
+    ScoreBreakdown::new(base_score(errors), position_bonus(end_pos.saturating_sub(pattern_len as usize)), 0)
+}
 
-    let pos_bonus = if start_pos == 0 {
+/// Score before bonuses: 1000 - (errors * 250), floored at 0
+#[inline]
+pub fn base_score(errors: u32) -> u32 {
+    1000u32.saturating_sub(errors * 250)
+}
+
+/// Bonus for where a match starts: +50 at the start, +25 within 10 bytes
+#[inline]
+pub fn position_bonus(start_pos: usize) -> u32 {
+    if start_pos == 0 {
         50
     } else if start_pos < 10 {
         25
     } else {
         0
-    };
+    }
+}
 
-    let score = base + pos_bonus;
-    if score > 1000 { 1000 } else { score as u16 }
+/// Error budget bitap actually uses for a `pattern_len`-char pattern
+/// Adapts `max_errors` to the pattern length: very short patterns only
+/// match exactly (up to 3 chars) or with one error (up to 5 chars).
+#[inline]
+pub fn effective_errors(pattern_len: usize, max_errors: u32) -> u32 {
+    if pattern_len <= 3 {
+        0
+    } else if pattern_len <= 5 {
+        max_errors.min(1)
+    } else {
+        max_errors.min(MAX_SCAN_ERRORS)
+    }
+}
+
+/// Pattern chars per error the automatic threshold tolerates
+pub const AUTO_THRESHOLD_CHARS_PER_ERROR: usize = 4;
+
+/// Threshold derived from the pattern length, for `SearchConfig::auto_threshold`
+///
+/// Tolerates one error per `AUTO_THRESHOLD_CHARS_PER_ERROR` pattern chars,
+/// capped at `max_errors`: `base_score(min(max_errors, pattern_len / 4))`.
+/// So a 3-char query needs an exact match, a 4-char one (750) allows one
+/// error and a 20-char one allows `max_errors`.
+pub fn auto_threshold(pattern_len: usize, max_errors: u32) -> u16 {
+    let tolerated = (pattern_len / AUTO_THRESHOLD_CHARS_PER_ERROR).min(max_errors as usize) as u32;
+    base_score(tolerated) as u16
+}
+
+/// Calibrated confidence (0-1000) that a match is the intended one
+///
+/// The share of the longer of pattern and match that isn't an error:
+/// `1000 * (1 - errors / max(pattern_len, match_len))`. Unlike the score,
+/// it accounts for length, so one error costs a 4-char query 250 but a
+/// 20-char query 50, and a single cutoff works across queries. It falls
+/// as errors rise and rises with length; 0 for an empty pattern and match.
+pub fn confidence(errors: u32, pattern_len: usize, match_len: usize) -> u16 {
+    let len = pattern_len.max(match_len) as u64;
+    let errors = (errors as u64).min(len);
+    ((len - errors) * 1000).checked_div(len).unwrap_or(0) as u16
 }
 
 #[cfg(test)]
@@ -205,6 +789,28 @@ mod tests {
         assert_eq!(result.unwrap().errors, 0);
     }
 
+    #[test]
+    fn test_anchored() {
+        let searcher = BitapSearcher::new(b"world");
+
+        assert!(searcher.search_anchored(b"hello world", 0).is_none());
+        assert!(searcher.search_anchored(b"World peace", 0).is_some());
+        // One stray byte before the pattern fits in a 1-error window
+        assert!(searcher.search_anchored(b"_world", 1).is_some());
+    }
+
+    #[test]
+    fn test_anchored_end() {
+        let searcher = BitapSearcher::new(b".json");
+        assert_eq!(searcher.search_anchored_end(b"config.json", 0).map(|m| m.end_pos), Some(11));
+        assert!(searcher.search_anchored_end(b"config.json.bak", 0).is_none());
+        assert!(searcher.search(b"config.json.bak", 0).is_some());
+
+        // ".jsn" ends the text with one deletion; the exact ".json" before it doesn't count
+        let m = searcher.search_anchored_end(b"a.json b.jsn", 1).unwrap();
+        assert_eq!((m.errors, m.end_pos), (1, 12));
+    }
+
     #[test]
     fn test_score_computation() {
         // Exact match at start
@@ -216,4 +822,232 @@ mod tests {
         // Match not at start
         assert_eq!(compute_score(0, 5, 15), 1000); // 1000 + 0
     }
+
+    #[test]
+    fn test_confidence() {
+        assert_eq!(confidence(0, 4, 4), 1000);
+        assert_eq!(confidence(1, 4, 4), 750);
+        assert_eq!(confidence(1, 20, 19), 950);
+        // A match longer than the pattern counts its extra bytes
+        assert_eq!(confidence(2, 8, 10), 800);
+        assert_eq!(confidence(9, 8, 8), 0);
+        assert_eq!(confidence(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_auto_threshold() {
+        assert_eq!(auto_threshold(3, 2), 1000);
+        assert_eq!(auto_threshold(4, 2), 750);
+        assert_eq!(auto_threshold(20, 2), 500);
+        assert_eq!(auto_threshold(20, 0), 1000);
+        // A 1-error match clears it on a long query but not a short one
+        assert!(compute_score(1, 8, 20) >= auto_threshold(8, 2));
+        assert!(compute_score(1, 3, 20) < auto_threshold(3, 2));
+    }
+
+    #[test]
+    fn test_row_width_by_pattern_len() {
+        assert_eq!(BitapSearcher::new(&[b'a'; 20]).row_bits(), 32);
+        assert_eq!(BitapSearcher::new(&[b'a'; 32]).row_bits(), 32);
+        assert_eq!(BitapSearcher::new(&[b'a'; 50]).row_bits(), 64);
+        assert_eq!(BitapSearcher::new(&[b'a'; 100]).row_bits(), 128);
+    }
+
+    #[test]
+    fn test_long_pattern() {
+        let pattern = b"wireless mechanical keyboard with backlit keys!!";
+        let pattern = &[&pattern[..], b"xx"].concat()[..50];
+        let searcher = BitapSearcher::new(pattern);
+        assert_eq!(searcher.pattern_len(), 50);
+
+        let mut text = [b'_'; 80];
+        text[10..60].copy_from_slice(pattern);
+        let m = searcher.search(&text, 2).unwrap();
+        assert_eq!((m.errors, m.end_pos), (0, 60));
+
+        // Substitutions past the first 32 chars still count
+        text[45] = b'#';
+        text[58] = b'#';
+        let m = searcher.search(&text, 2).unwrap();
+        assert_eq!((m.errors, m.end_pos), (2, 60));
+        assert!(searcher.search(&text, 1).is_none());
+    }
+
+    #[test]
+    fn test_widths_agree() {
+        let pattern = b"keyboard";
+        let narrow = BitapSearcher::new(pattern);
+        let wide = BitapSearcher::with_masks(pattern, Masks::U64(char_masks(pattern, true)));
+        let multi = BitapSearcher::with_masks(pattern, Masks::Multi(char_masks(pattern, true)));
+
+        for text in [&b"mechanical keybaord"[..], b"KEYBOARD", b"kyboard pro", b"no match here"] {
+            for max_errors in 0..=3 {
+                let expected = narrow.search(text, max_errors).map(|m| (m.errors, m.end_pos));
+                assert_eq!(wide.search(text, max_errors).map(|m| (m.errors, m.end_pos)), expected);
+                assert_eq!(multi.search(text, max_errors).map(|m| (m.errors, m.end_pos)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_char_exact() {
+        let searcher = BitapSearcher::new(b"keyboard");
+        // First char substituted or missing
+        for text in [&b"xeyboard"[..], b"eyboard"] {
+            assert!(searcher.search(text, 2).is_none());
+        }
+        // Errors after it still count against the budget
+        assert_eq!(searcher.search(b"kxyboard", 1).map(|m| m.errors), Some(1));
+        assert_eq!(searcher.search(b"Kyboard", 1).map(|m| m.errors), Some(1));
+    }
+
+    #[test]
+    fn test_fold_diacritics_keeps_case() {
+        let folding = Folding { case: false, diacritics: true };
+        let searcher = BitapSearcher::with_folding(b"naive", folding);
+        assert_eq!(searcher.search("naïve".as_bytes(), 0).map(|m| m.errors), Some(0));
+        let m = searcher.search("une naïve idée".as_bytes(), 0).unwrap();
+        assert_eq!((m.start_pos, m.end_pos), (4, 10));
+        assert_eq!(searcher.edit_profile("une naïve idée".as_bytes(), m), EditProfile::default());
+
+        // Case still counts
+        assert_eq!(BitapSearcher::with_folding(b"NAIVE", folding).search("naïve".as_bytes(), 0), None);
+        assert!(BitapSearcher::with_folding(b"NAIVE", folding).search("NAÏVE".as_bytes(), 0).is_some());
+
+        // Either side may carry the accent
+        let accented = BitapSearcher::with_folding("naïve".as_bytes(), folding);
+        assert_eq!(accented.pattern(), b"naive");
+        assert_eq!(accented.search(b"a naive idea", 0).map(|m| (m.start_pos, m.end_pos)), Some((2, 7)));
+
+        // The defaults fold case only
+        assert_eq!(BitapSearcher::new(b"naive").search("naïve".as_bytes(), 0), None);
+        assert!(BitapSearcher::new(b"NAIVE").search(b"naive", 0).is_some());
+    }
+
+    #[test]
+    fn test_next_match() {
+        let searcher = BitapSearcher::new(b"word");
+        let text = b"a word, a wrd, a sword";
+        let starts: [Option<usize>; 4] = core::array::from_fn(|i| {
+            let from = [0, 3, 11, 19][i];
+            searcher.next_match(text, from, 1).map(|m| m.start_pos)
+        });
+        assert_eq!(starts, [Some(2), Some(10), Some(18), None]);
+        assert_eq!(searcher.next_match(text, 99, 1), None);
+    }
+
+    #[test]
+    fn test_exact_fast_path() {
+        let searcher = BitapSearcher::new(b"Keyboard");
+        // A later exact hit beats an earlier fuzzy one
+        assert_eq!(searcher.search(b"keybaord KEYBOARD", 2), Some(SearchMatch { errors: 0, start_pos: 9, end_pos: 17 }));
+        assert_eq!(searcher.search(b"kkeyboard", 0), Some(SearchMatch { errors: 0, start_pos: 1, end_pos: 9 }));
+        assert_eq!(searcher.search(b"keybaord", 0), None);
+        assert_eq!(searcher.search_anchored_end(b"keyboard keyboard", 0).map(|m| m.end_pos), Some(17));
+        assert_eq!(searcher.search_anchored_end(b"keyboards", 0), None);
+
+        // Same answers as the automaton alone
+        let Masks::U32(masks) = &searcher.char_masks else { unreachable!() };
+        for text in [&b"a keyboard"[..], b"keyboar", b"xkeyboardkeyboard", b"KEYBOARD"] {
+            for scan in [Scan::Best, Scan::First, Scan::End] {
+                let ends = |m: Option<SearchMatch>| m.map(|m| (m.errors, m.end_pos));
+                assert_eq!(ends(searcher.find_exact(text, scan)), ends(scan_rows(masks, 8, fold::units(text, Folding::CASE), text.len(), 0, scan)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_start_under_errors() {
+        let searcher = BitapSearcher::new(b"keyboard");
+        let span = |text: &[u8]| searcher.search(text, 2).map(|m| (m.errors, m.start_pos, m.end_pos));
+        // Missing char: the region is shorter than the pattern
+        assert_eq!(span(b"the keybard here"), Some((1, 4, 11)));
+        // Extra char: longer
+        assert_eq!(span(b"keyyboard"), Some((1, 0, 9)));
+        assert_eq!(span(b"a keybxard"), Some((1, 2, 10)));
+        assert_eq!(searcher.search_anchored_end(b"my keybord", 1).map(|m| m.start_pos), Some(3));
+
+        let mut out = [SearchMatch::default(); 2];
+        assert_eq!(searcher.search_all(b"keybard, keyboard", 1, false, &mut out), 2);
+        assert_eq!([out[0].start_pos, out[1].start_pos], [0, 9]);
+    }
+
+    #[test]
+    fn test_edit_profile() {
+        let searcher = BitapSearcher::new(b"keyboard");
+        let profile = |text: &[u8]| {
+            let m = searcher.search(text, 2).unwrap();
+            let p = searcher.edit_profile(text, m);
+            (p.insertions, p.deletions, p.substitutions)
+        };
+        assert_eq!(profile(b"a Keyboard"), (0, 0, 0));
+        assert_eq!(profile(b"keyyboard"), (1, 0, 0));
+        assert_eq!(profile(b"the keybard"), (0, 1, 0));
+        assert_eq!(profile(b"keybxard"), (0, 0, 1));
+        assert_eq!(profile(b"kyeboard"), (0, 0, 2));
+    }
+
+    #[test]
+    fn test_char_mask() {
+        let searcher = BitapSearcher::new(b"Banana");
+        assert_eq!(searcher.char_mask(b'a'), 0b101010);
+        assert_eq!(searcher.char_mask(b'A'), 0b101010);
+        assert_eq!(searcher.char_mask(b'b'), 0b000001);
+        assert_eq!(searcher.char_mask(b'z'), 0);
+
+        let mut long = [b'x'; 70];
+        long[40] = b'y';
+        long[66] = b'y';
+        assert_eq!(BitapSearcher::new(&long[..50]).char_mask(b'y'), 1 << 40);
+        assert_eq!(BitapSearcher::new(&long).char_mask(b'y'), 1 << 40);
+    }
+
+    #[test]
+    fn test_reset_matches_new() {
+        let long = [b'k'; 100];
+        let mut searcher = BitapSearcher::new(b"Keyboard").with_bloom_hash(BloomHash::NibbleFold);
+        // Same width, then wider and narrower again
+        for pattern in [&b"mouse"[..], &long[..50], &long[..], b"KEYBOARD", b""] {
+            searcher.reset(pattern);
+            let fresh = BitapSearcher::new(pattern).with_bloom_hash(BloomHash::NibbleFold);
+            assert_eq!(searcher.pattern(), fresh.pattern());
+            assert_eq!(searcher.bloom().bits(), fresh.bloom().bits());
+            // No mask bits left over from the previous pattern
+            let same_masks = match (&searcher.char_masks, &fresh.char_masks) {
+                (Masks::U32(a), Masks::U32(b)) => a == b,
+                (Masks::U64(a), Masks::U64(b)) => a == b,
+                (Masks::Multi(a), Masks::Multi(b)) => a == b,
+                _ => false,
+            };
+            assert!(same_masks);
+            assert_eq!(searcher.chars(), fresh.chars());
+            for text in [&b"mechanical keybaord"[..], b"Mouse pad", &long[..60], b""] {
+                let found = |s: &BitapSearcher| s.search(text, 2).map(|m| (m.errors, m.end_pos));
+                assert_eq!(found(&searcher), found(&fresh));
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_all_overlap() {
+        let searcher = BitapSearcher::new(b"aa");
+        let mut out = [SearchMatch::default(); 8];
+
+        let n = searcher.search_all(b"aaaa", 0, true, &mut out);
+        assert_eq!(n, 3);
+        assert_eq!([out[0].end_pos, out[1].end_pos, out[2].end_pos], [2, 3, 4]);
+
+        let n = searcher.search_all(b"aaaa", 0, false, &mut out);
+        assert_eq!(n, 2);
+        assert_eq!([out[0].end_pos, out[1].end_pos], [2, 4]);
+    }
+
+    #[test]
+    fn test_search_all_out_capacity() {
+        let searcher = BitapSearcher::new(b"ab");
+        let mut out = [SearchMatch::default(); 2];
+        assert_eq!(searcher.search_all(b"ab ab ab", 0, false, &mut out), 2);
+        assert_eq!(searcher.count_matches(b"ab ab ab", 0), 3);
+        assert_eq!(out[1].end_pos, 5);
+    }
 }
@@ -1,10 +1,54 @@
 //! Bloom filter for O(1) candidate rejection
 
+use crate::fold::base_at;
+use crate::fold_byte;
+
+/// How a (case-folded) byte picks its bit among the 64
+///
+/// Record and pattern blooms must be built with the same scheme.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BloomHash {
+    /// Low 6 bits of the byte. Lowercase letters get distinct bits, but
+    /// digits share bits with `p`-`y` and punctuation with `a`-`z`
+    #[default]
+    LowBits,
+    /// Fibonacci hashing: top 6 bits of the byte times 2^32 / phi
+    Multiplicative,
+    /// Low 6 bits XOR the high nibble shifted up 3, which keeps letters,
+    /// digits and punctuation apart
+    NibbleFold,
+}
+
+impl BloomHash {
+    /// Convert from the numeric scheme used by the FFI/WASM exports
+    /// Unknown values fall back to `LowBits`
+    pub fn from_u32(scheme: u32) -> Self {
+        match scheme {
+            1 => BloomHash::Multiplicative,
+            2 => BloomHash::NibbleFold,
+            _ => BloomHash::LowBits,
+        }
+    }
+
+    /// Bit index (0-63) for byte `c`
+    #[inline]
+    pub const fn bit(self, c: u8) -> u32 {
+        match self {
+            BloomHash::LowBits => (c & 0x3F) as u32,
+            BloomHash::Multiplicative => (c as u32).wrapping_mul(0x9E37_79B1) >> 26,
+            BloomHash::NibbleFold => ((c ^ ((c >> 4) << 3)) & 0x3F) as u32,
+        }
+    }
+}
+
 /// 64-bit bloom filter using character positions
 #[derive(Clone, Copy, Debug, Default)]
 pub struct BloomFilter(pub u64);
 
 impl BloomFilter {
+    /// Filter that might contain anything (stands in when none was computed)
+    pub const FULL: Self = Self(u64::MAX);
+
     /// Create a new empty bloom filter
     #[inline]
     pub const fn new() -> Self {
@@ -12,13 +56,24 @@ impl BloomFilter {
     }
 
     /// Create bloom filter from text
+    /// An accented Latin letter also sets its base letter's bit, so a
+    /// diacritic-folding search for the base still passes.
     #[inline]
     pub fn from_text(text: &[u8]) -> Self {
+        Self::with_hash(text, BloomHash::LowBits)
+    }
+
+    /// Create bloom filter from text, placing bytes with `hash`
+    #[inline]
+    pub fn with_hash(text: &[u8], hash: BloomHash) -> Self {
         let mut bits = 0u64;
-        for &c in text {
-            let lower = to_lower(c);
-            let idx = (lower & 0x3F) as u64;
-            bits |= 1u64 << idx;
+        for (i, &c) in text.iter().enumerate() {
+            bits |= 1u64 << hash.bit(fold_byte(c));
+            if c >= 0xC3 {
+                if let Some(base) = base_at(text, i) {
+                    bits |= 1u64 << hash.bit(fold_byte(base));
+                }
+            }
         }
         Self(bits)
     }
@@ -31,6 +86,16 @@ impl BloomFilter {
         (self.0 & pattern_bloom.0) == pattern_bloom.0
     }
 
+    /// Check containment while tolerating up to `max_missing` absent pattern bits
+    ///
+    /// A match with `k` edits leaves at most `k` pattern chars unmatched, and
+    /// each absent char clears at most one bit, so passing the error budget
+    /// here never rejects a record an error-tolerant search would accept.
+    #[inline]
+    pub fn might_contain_within(&self, pattern_bloom: BloomFilter, max_missing: u32) -> bool {
+        (pattern_bloom.0 & !self.0).count_ones() <= max_missing
+    }
+
     /// Get raw bits
     #[inline]
     pub fn bits(&self) -> u64 {
@@ -39,9 +104,10 @@ impl BloomFilter {
 }
 
 /// Convert ASCII uppercase to lowercase (branch-free)
+/// Same as `fold_byte`, which the rest of the crate uses.
 #[inline]
 pub fn to_lower(c: u8) -> u8 {
-    c | (0x20 * ((c >= b'A' && c <= b'Z') as u8))
+    fold_byte(c)
 }
 
 #[cfg(test)]
@@ -65,11 +131,59 @@ mod tests {
         assert!(!text_bloom.might_contain(pattern_bloom));
     }
 
+    #[test]
+    fn test_bloom_tolerates_missing() {
+        let text_bloom = BloomFilter::from_text(b"abcdefgh");
+        let pattern_bloom = BloomFilter::from_text(b"abxdefgy");
+
+        assert!(!text_bloom.might_contain(pattern_bloom));
+        assert!(!text_bloom.might_contain_within(pattern_bloom, 1));
+        assert!(text_bloom.might_contain_within(pattern_bloom, 2));
+    }
+
     #[test]
     fn test_to_lower() {
         assert_eq!(to_lower(b'A'), b'a');
         assert_eq!(to_lower(b'Z'), b'z');
         assert_eq!(to_lower(b'a'), b'a');
         assert_eq!(to_lower(b'1'), b'1');
+        // The FFI and WASM layers fold through these too; pin them to ASCII folding
+        for c in 0..=u8::MAX {
+            assert_eq!(to_lower(c), c.to_ascii_lowercase());
+            assert_eq!(crate::fold_byte(c), c.to_ascii_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_hash_collisions() {
+        // Chars beyond the first to land on an already-set bit
+        let collisions = |chars: &[u8], hash: BloomHash| {
+            chars.len() as u32 - BloomFilter::with_hash(chars, hash).bits().count_ones()
+        };
+
+        // Every scheme gives the lowercase alphabet 26 distinct bits...
+        let lower = b"abcdefghijklmnopqrstuvwxyz";
+        for hash in [BloomHash::LowBits, BloomHash::Multiplicative, BloomHash::NibbleFold] {
+            assert_eq!(collisions(lower, hash), 0);
+        }
+
+        // ...but add digits and the default crams 10 of them onto p-y
+        let alnum = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        assert_eq!(collisions(alnum, BloomHash::LowBits), 10);
+        assert_eq!(collisions(alnum, BloomHash::Multiplicative), 5);
+        assert_eq!(collisions(alnum, BloomHash::NibbleFold), 0);
+        assert_eq!(BloomFilter::with_hash(alnum, BloomHash::LowBits).bits(), BloomFilter::from_text(alnum).bits());
+    }
+
+    #[test]
+    fn test_case_never_bloom_rejected() {
+        use crate::BitapSearcher;
+
+        // Pattern blooms come from the searcher, record blooms from the text
+        for c in 0..=u8::MAX {
+            let (lower, upper) = ([c.to_ascii_lowercase()], [c.to_ascii_uppercase()]);
+            assert!(BloomFilter::from_text(&upper).might_contain(BitapSearcher::new(&lower).bloom()));
+            assert!(BloomFilter::from_text(&lower).might_contain(BitapSearcher::new(&upper).bloom()));
+        }
     }
 }
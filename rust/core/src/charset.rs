@@ -0,0 +1,90 @@
+//! Exact character-set filter
+//!
+//! The bloom filter folds bytes into 64 buckets, so distinct chars can
+//! share a bit and a record may pass it without holding every pattern
+//! char. `CharSet` keeps one bit per (lowercased) byte value instead: a
+//! record whose set lacks any pattern char can't hold a zero-error match.
+
+use crate::fold::base_at;
+use crate::fold_byte;
+
+/// 256-bit presence set of lowercased bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CharSet(pub [u64; 4]);
+
+impl CharSet {
+    /// Set holding no bytes
+    pub const EMPTY: Self = Self([0; 4]);
+
+    /// Set holding every byte (stands in when none was computed)
+    pub const FULL: Self = Self([u64::MAX; 4]);
+
+    /// Create the set of bytes in `text`, ASCII case folded
+    /// Accented Latin letters add their base letter too (see `fold`).
+    #[inline]
+    pub fn from_text(text: &[u8]) -> Self {
+        let mut words = [0u64; 4];
+        for (i, &c) in text.iter().enumerate() {
+            let lower = fold_byte(c);
+            words[(lower >> 6) as usize] |= 1u64 << (lower & 0x3F);
+            if c >= 0xC3 {
+                if let Some(base) = base_at(text, i) {
+                    let base = fold_byte(base);
+                    words[(base >> 6) as usize] |= 1u64 << (base & 0x3F);
+                }
+            }
+        }
+        Self(words)
+    }
+
+    /// Create the set of exactly the bytes given, without case folding
+    pub const fn from_bytes(bytes: &[u8]) -> Self {
+        let mut words = [0u64; 4];
+        let mut i = 0;
+        while i < bytes.len() {
+            let c = bytes[i];
+            words[(c >> 6) as usize] |= 1u64 << (c & 0x3F);
+            i += 1;
+        }
+        Self(words)
+    }
+
+    /// True if byte `c` is in the set (no case folding)
+    #[inline]
+    pub const fn contains(&self, c: u8) -> bool {
+        self.0[(c >> 6) as usize] & (1u64 << (c & 0x3F)) != 0
+    }
+
+    /// Bytes in either set
+    #[inline]
+    pub fn union(self, other: CharSet) -> Self {
+        Self(core::array::from_fn(|i| self.0[i] | other.0[i]))
+    }
+
+    /// True if every byte of `pattern` is in this set
+    #[inline]
+    pub fn contains_all(&self, pattern: CharSet) -> bool {
+        self.0.iter().zip(pattern.0).all(|(&have, need)| need & !have == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_all() {
+        let text = CharSet::from_text(b"Hello World");
+        assert!(text.contains_all(CharSet::from_text(b"WORLD")));
+        assert!(!text.contains_all(CharSet::from_text(b"word!")));
+        assert!(CharSet::FULL.contains_all(text));
+    }
+
+    #[test]
+    fn test_exact_where_bloom_collides() {
+        // 'a' (0x61) and '!' (0x21) share bloom bit 33
+        let text = CharSet::from_text(b"a");
+        assert!(!text.contains_all(CharSet::from_text(b"!")));
+        assert!(text.union(CharSet::from_text(b"!")).contains_all(CharSet::from_text(b"a!")));
+    }
+}
@@ -0,0 +1,54 @@
+//! Order-independent digest of index contents
+//!
+//! Each record hashes to a 64-bit FNV-1a value over its ID and text; the
+//! index digest is the wrapping sum of those values. Summing makes the
+//! digest independent of insertion order and lets a removal subtract its
+//! record's hash again, and unlike XOR two identical records don't cancel.
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Digest of an empty index
+pub const EMPTY_DIGEST: u64 = 0;
+
+/// FNV-1a hash of a record's ID (little-endian) followed by its text
+pub fn record_hash(id: u32, text: &[u8]) -> u64 {
+    id.to_le_bytes()
+        .iter()
+        .chain(text)
+        .fold(FNV_OFFSET, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Digest after adding a record
+#[inline]
+pub fn add_record(digest: u64, id: u32, text: &[u8]) -> u64 {
+    digest.wrapping_add(record_hash(id, text))
+}
+
+/// Digest after removing a record previously added with the same ID and text
+#[inline]
+pub fn remove_record(digest: u64, id: u32, text: &[u8]) -> u64 {
+    digest.wrapping_sub(record_hash(id, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_independent() {
+        let a = add_record(add_record(EMPTY_DIGEST, 1, b"apple"), 2, b"banana");
+        let b = add_record(add_record(EMPTY_DIGEST, 2, b"banana"), 1, b"apple");
+        assert_eq!(a, b);
+        assert_ne!(a, add_record(add_record(EMPTY_DIGEST, 1, b"banana"), 2, b"apple"));
+    }
+
+    #[test]
+    fn test_remove_restores() {
+        let one = add_record(EMPTY_DIGEST, 1, b"apple");
+        let twice = add_record(one, 1, b"apple");
+        assert_ne!(twice, EMPTY_DIGEST);
+        assert_eq!(remove_record(twice, 1, b"apple"), one);
+        assert_eq!(remove_record(one, 1, b"apple"), EMPTY_DIGEST);
+    }
+}
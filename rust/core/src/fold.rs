@@ -0,0 +1,165 @@
+//! Case and diacritic folding
+//!
+//! Bitap matching folds ASCII case by default. Diacritic folding also
+//! reads each two-byte UTF-8 Latin letter in U+00C0..U+017F as its
+//! unaccented ASCII base, so "naïve" matches "naive". Matching stays
+//! byte-based: the accented letter counts as one char, and match
+//! positions still refer to the original bytes. The two settings are
+//! independent, so a case-sensitive search can still ignore accents.
+//!
+//! Blooms and char sets fold case and record each accented letter's base
+//! next to its bytes, so they stay valid prefilters under any folding.
+
+use crate::fold_byte;
+
+/// ASCII base of U+00C0 + i (lead byte 0xC3), or 0 if it has none
+/// Ligatures and letters without a one-letter base (Æ, Þ, ß) keep theirs.
+const LATIN_1: [u8; 64] = *b"AAAAAA\0CEEEEIIIIDNOOOOO\0OUUUUY\0\0aaaaaa\0ceeeeiiiidnooooo\0ouuuuy\0y";
+
+/// ASCII base of U+0100 + i (lead bytes 0xC4 and 0xC5), or 0 if it has none
+const LATIN_EXTENDED_A: [u8; 128] = *b"AaAaAaCcCcCcCcDdDdEeEeEeEeEeGgGgGgGgHhHhIiIiIiIiIi\0\0JjKk\0LlLlLlLlLlNnNnNn\0NnOoOoOo\0\0RrRrRrSsSsSsSsTtTtTtUuUuUuUuUuUuWwYyYZzZzZzs";
+
+/// Which differences bitap matching ignores
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Folding {
+    /// ASCII case
+    pub case: bool,
+    /// Accents on Latin letters
+    pub diacritics: bool,
+}
+
+impl Folding {
+    /// Case folded, diacritics kept: the default
+    pub const CASE: Self = Self { case: true, diacritics: false };
+
+    /// Fold one byte under the case setting
+    #[inline]
+    pub const fn byte(self, c: u8) -> u8 {
+        if self.case {
+            fold_byte(c)
+        } else {
+            c
+        }
+    }
+
+    /// Fold `text` into `out`, returning the folded length
+    /// Accented letters become their base when folding diacritics; case is
+    /// left alone, since masks and comparisons fold it themselves.
+    pub fn strip(self, text: &[u8], out: &mut [u8]) -> usize {
+        let mut len = 0;
+        for (_, c) in units(text, Folding { case: false, ..self }) {
+            if len == out.len() {
+                break;
+            }
+            out[len] = c;
+            len += 1;
+        }
+        len
+    }
+}
+
+impl Default for Folding {
+    fn default() -> Self {
+        Self::CASE
+    }
+}
+
+/// Unaccented ASCII base of the two-byte UTF-8 sequence `lead`, `cont`
+#[inline]
+pub const fn strip_diacritic(lead: u8, cont: u8) -> Option<u8> {
+    if cont & 0xC0 != 0x80 {
+        return None;
+    }
+    let i = (cont & 0x3F) as usize;
+    let base = match lead {
+        0xC3 => LATIN_1[i],
+        0xC4 => LATIN_EXTENDED_A[i],
+        0xC5 => LATIN_EXTENDED_A[64 + i],
+        _ => 0,
+    };
+    if base == 0 {
+        None
+    } else {
+        Some(base)
+    }
+}
+
+/// Base letter of an accented letter starting at `text[i]`, if any
+#[inline]
+pub fn base_at(text: &[u8], i: usize) -> Option<u8> {
+    match text.get(i..i + 2) {
+        Some(&[lead @ 0xC3..=0xC5, cont]) => strip_diacritic(lead, cont),
+        _ => None,
+    }
+}
+
+/// Iterate the chars of `text` as bitap sees them under `folding`
+pub fn units(text: &[u8], folding: Folding) -> Units<'_> {
+    Units { text, pos: 0, folding }
+}
+
+/// Iterator of `(end, byte)`: the offset just past each char's bytes and
+/// its folded byte. An accented letter is one char when folding diacritics.
+#[derive(Clone, Debug)]
+pub struct Units<'a> {
+    text: &'a [u8],
+    pos: usize,
+    folding: Folding,
+}
+
+impl Iterator for Units<'_> {
+    type Item = (usize, u8);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, u8)> {
+        let c = *self.text.get(self.pos)?;
+        if self.folding.diacritics {
+            if let Some(base) = base_at(self.text, self.pos) {
+                self.pos += 2;
+                return Some((self.pos, self.folding.byte(base)));
+            }
+        }
+        self.pos += 1;
+        Some((self.pos, self.folding.byte(c)))
+    }
+}
+
+/// The char ending at `end` (> 0): its start offset and folded byte
+#[inline]
+pub fn unit_before(text: &[u8], end: usize, folding: Folding) -> (usize, u8) {
+    if folding.diacritics && end >= 2 {
+        if let Some(base) = base_at(text, end - 2) {
+            return (end - 2, folding.byte(base));
+        }
+    }
+    (end - 1, folding.byte(text[end - 1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_diacritic() {
+        let base = |s: &str| strip_diacritic(s.as_bytes()[0], s.as_bytes()[1]);
+        assert_eq!(base("ï"), Some(b'i'));
+        assert_eq!(base("É"), Some(b'E'));
+        assert_eq!(base("ç"), Some(b'c'));
+        assert_eq!(base("ł"), Some(b'l'));
+        assert_eq!(base("Ž"), Some(b'Z'));
+        assert_eq!(base("ß"), None);
+        assert_eq!(base("×"), None);
+    }
+
+    #[test]
+    fn test_units() {
+        let text = "Naïve".as_bytes();
+        let folding = Folding { case: false, diacritics: true };
+        let mut out = [0u8; 8];
+        let len = folding.strip(text, &mut out);
+        assert_eq!(&out[..len], b"Naive");
+        assert_eq!(units(text, folding).nth(2), Some((4, b'i')));
+        assert_eq!(unit_before(text, 4, folding), (2, b'i'));
+        assert_eq!(units(text, Folding::CASE).nth(2), Some((3, 0xC3)));
+    }
+}
@@ -7,17 +7,149 @@
 
 pub mod bitap;
 pub mod bloom;
+pub mod charset;
+pub mod digest;
+pub mod fold;
+pub mod phonetic;
+pub mod pool;
+pub mod prefix;
+pub mod query;
+pub mod search;
+pub mod subsequence;
 pub mod types;
+pub mod whitespace;
+pub mod word_prefix;
 
 pub use bitap::BitapSearcher;
-pub use bloom::BloomFilter;
+pub use bloom::{BloomFilter, BloomHash};
+pub use charset::CharSet;
+pub use fold::Folding;
+pub use pool::PoolAllocator;
+pub use prefix::PrefixIndex;
+pub use search::{closest_record, common_prefix_len, compact_records, diff_result_ids, insert_ranked, rank_order, search_records, RecordView, RecordsView};
+pub use subsequence::SubsequenceSearcher;
+pub use word_prefix::WordPrefixSearcher;
 pub use types::*;
 
-/// Maximum pattern length supported (32 characters)
-pub const MAX_PATTERN_LEN: usize = 32;
+/// Fold a byte for case-insensitive matching: ASCII uppercase to lowercase
+///
+/// Bloom filters, char sets, prefix keys and the bitap and subsequence
+/// matchers all fold through this, so a pattern and a record differing
+/// only in case always agree. Non-ASCII bytes pass through unchanged.
+#[inline]
+pub const fn fold_byte(c: u8) -> u8 {
+    c | (0x20 * (c.is_ascii_uppercase() as u8))
+}
+
+/// Maximum pattern length supported (128 characters)
+/// Patterns up to 32 characters take the single-`u32` bitap fast path.
+pub const MAX_PATTERN_LEN: usize = 128;
 
 /// Default threshold score (0-1000 scale)
 pub const DEFAULT_THRESHOLD: u16 = 250;
 
 /// Default max errors allowed
 pub const DEFAULT_MAX_ERRORS: u32 = 2;
+
+/// Highest max_errors accepted by the bindings (8)
+///
+/// Bitap keeps one state row per error level for up to 32 levels, so the
+/// algorithm itself is not the limit.
+/// The ceiling is a quarter of a 32-char pattern; past that nearly every
+/// record matches.
+///
+/// Performance: each error level adds one row update per text byte, so a
+/// scan costs O(text_len * (max_errors + 1)) — 5 errors is roughly twice
+/// the work of 2. Scoring subtracts 250 per error, so matches with 4+
+/// errors score at most 50 and need a lowered threshold to be returned.
+pub const MAX_ERRORS_CEIL: u32 = 8;
+
+/// Fixed capacities shared by the FFI and WASM engines
+///
+/// Both bindings size their static arrays from these consts, so they
+/// can't drift apart. An instance, `Limits::CURRENT`, is what
+/// `ff_get_limits` writes for hosts to read at runtime instead of
+/// hardcoding the values.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_records: u32,
+    pub max_results: u32,
+    pub string_pool_size: u32,
+    pub scratchpad_size: u32,
+    pub max_pattern_len: u32,
+    pub max_errors: u32,
+}
+
+impl Limits {
+    /// Record slots, including removed records not yet compacted
+    pub const MAX_RECORDS: usize = 100_000;
+    /// Results kept per search (the `std` FFI build can raise it)
+    pub const MAX_RESULTS: usize = 100;
+    /// Bytes of record text and aliases
+    pub const STRING_POOL_SIZE: usize = 4 * 1024 * 1024;
+    /// Bytes of the write buffer (the `std` FFI build can grow it)
+    pub const SCRATCHPAD_SIZE: usize = 64 * 1024;
+    /// Pattern bytes kept; see `MAX_PATTERN_LEN`
+    pub const MAX_PATTERN_LEN: usize = MAX_PATTERN_LEN;
+    /// Highest accepted max errors; see `MAX_ERRORS_CEIL`
+    pub const MAX_ERRORS: u32 = MAX_ERRORS_CEIL;
+
+    /// The consts above as a value
+    pub const CURRENT: Self = Self {
+        max_records: Self::MAX_RECORDS as u32,
+        max_results: Self::MAX_RESULTS as u32,
+        string_pool_size: Self::STRING_POOL_SIZE as u32,
+        scratchpad_size: Self::SCRATCHPAD_SIZE as u32,
+        max_pattern_len: Self::MAX_PATTERN_LEN as u32,
+        max_errors: Self::MAX_ERRORS,
+    };
+}
+
+/// Engine version packed as `major << 16 | minor << 8 | patch`
+pub const VERSION: u32 = (parse_u32(env!("CARGO_PKG_VERSION_MAJOR")) << 16)
+    | (parse_u32(env!("CARGO_PKG_VERSION_MINOR")) << 8)
+    | parse_u32(env!("CARGO_PKG_VERSION_PATCH"));
+
+/// Feature flag: built with `std` (growable buffers, hash-set ID checks)
+pub const FEATURE_STD: u32 = 1 << 0;
+/// Feature flag: SIMD-accelerated scanning (not yet implemented)
+pub const FEATURE_SIMD: u32 = 1 << 1;
+/// Feature flag: subsequence match mode available
+pub const FEATURE_SUBSEQUENCE: u32 = 1 << 2;
+/// Feature flag: phonetic matching available
+pub const FEATURE_PHONETIC: u32 = 1 << 3;
+/// Bits 16-23 of the feature word hold the bloom filter width in bits
+pub const FEATURE_BLOOM_WIDTH_SHIFT: u32 = 16;
+/// Bits 24-31 of the feature word hold `MAX_PATTERN_LEN`
+pub const FEATURE_MAX_PATTERN_SHIFT: u32 = 24;
+
+/// Feature word for this core build; bindings OR in `FEATURE_STD`
+pub const FEATURES: u32 = FEATURE_SUBSEQUENCE
+    | FEATURE_PHONETIC
+    | ((u64::BITS) << FEATURE_BLOOM_WIDTH_SHIFT)
+    | ((MAX_PATTERN_LEN as u32) << FEATURE_MAX_PATTERN_SHIFT);
+
+const fn parse_u32(s: &str) -> u32 {
+    let bytes = s.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        value = value * 10 + (bytes[i] - b'0') as u32;
+        i += 1;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_matches_package() {
+        assert_eq!(VERSION >> 16, parse_u32(env!("CARGO_PKG_VERSION_MAJOR")));
+        assert_eq!((VERSION >> 8) & 0xFF, parse_u32(env!("CARGO_PKG_VERSION_MINOR")));
+        assert_eq!((FEATURES >> FEATURE_MAX_PATTERN_SHIFT) as usize, MAX_PATTERN_LEN);
+        assert_eq!((FEATURES >> FEATURE_BLOOM_WIDTH_SHIFT) & 0xFF, 64);
+    }
+}
@@ -0,0 +1,93 @@
+//! Phonetic codes for name search
+//!
+//! A Soundex variant over the first word of a text. Classic Soundex keeps
+//! the first letter verbatim, so "Katherine" (K365) and "Catherine" (C365)
+//! differ; here the first letter is replaced by its digit class as well,
+//! giving both the code 2365.
+
+/// Code for texts without letters
+pub const NO_CODE: u16 = 0;
+
+/// Score given to records admitted only by a phonetic match
+/// Sits between a 1-error (750) and a 2-error (500) bitap match.
+pub const PHONETIC_SCORE: u16 = 600;
+
+/// Soundex digit class of a lowercase letter (0 for vowels and h/w/y)
+#[inline]
+fn class(c: u8) -> u8 {
+    match c {
+        b'b' | b'f' | b'p' | b'v' => 1,
+        b'c' | b'g' | b'j' | b'k' | b'q' | b's' | b'x' | b'z' => 2,
+        b'd' | b't' => 3,
+        b'l' => 4,
+        b'm' | b'n' => 5,
+        b'r' => 6,
+        _ => 0,
+    }
+}
+
+/// Byte range of the first run of ASCII letters
+pub fn first_word(text: &[u8]) -> Option<(usize, usize)> {
+    let start = text.iter().position(|c| c.is_ascii_alphabetic())?;
+    let len = text[start..].iter().take_while(|c| c.is_ascii_alphabetic()).count();
+    Some((start, start + len))
+}
+
+/// Phonetic code of the first word of `text`, or `NO_CODE`
+///
+/// The four Soundex digits are packed one per nibble, each offset by one
+/// so a real code is never `NO_CODE`.
+pub fn encode(text: &[u8]) -> u16 {
+    let Some((start, end)) = first_word(text) else {
+        return NO_CODE;
+    };
+    let word = &text[start..end];
+
+    let mut digits = [0u8; 4];
+    let mut len = 1;
+    let mut prev = class(word[0].to_ascii_lowercase());
+    digits[0] = prev;
+
+    for &c in &word[1..] {
+        if len == digits.len() {
+            break;
+        }
+        let c = c.to_ascii_lowercase();
+        let d = class(c);
+        if d != 0 && d != prev {
+            digits[len] = d;
+            len += 1;
+        }
+        // h and w don't separate letters of the same class; vowels do
+        if c != b'h' && c != b'w' {
+            prev = d;
+        }
+    }
+
+    digits.iter().fold(0u16, |code, &d| (code << 4) | (d as u16 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_letter_folded() {
+        assert_eq!(encode(b"Katherine"), encode(b"Catherine"));
+        assert_ne!(encode(b"Katherine"), encode(b"Kathleen"));
+    }
+
+    #[test]
+    fn test_classic_rules() {
+        // Robert / Rupert -> R163; Ashcraft -> A261 (h doesn't split s/c)
+        assert_eq!(encode(b"Robert"), encode(b"Rupert"));
+        assert_eq!(encode(b"Ashcraft"), encode(b"Ashcroft"));
+        assert_eq!(encode(b"Tymczak"), encode(b"Tymczak Jr"));
+    }
+
+    #[test]
+    fn test_no_letters() {
+        assert_eq!(encode(b"1234"), NO_CODE);
+        assert_eq!(first_word(b"  Smith, John"), Some((2, 7)));
+    }
+}
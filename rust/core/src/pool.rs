@@ -0,0 +1,238 @@
+//! Free-list allocator for the string pool
+//!
+//! Hands out byte spans from a fixed-size pool. Spans released by removed
+//! records go onto a sorted free list and are reused by later allocations,
+//! so add/remove churn doesn't exhaust the pool.
+
+/// Maximum number of free spans tracked at once
+pub const MAX_FREE_SPANS: usize = 1024;
+
+/// A contiguous byte range inside the pool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    /// Offset of the first byte
+    pub start: u32,
+    /// Length in bytes
+    pub len: u32,
+}
+
+impl Span {
+    #[inline]
+    fn end(&self) -> u32 {
+        self.start + self.len
+    }
+}
+
+/// Allocator over a fixed-capacity byte pool
+///
+/// New spans are bump-allocated from the top of the pool unless a free
+/// span of sufficient size exists, in which case the smallest such span
+/// is reused (best fit). Adjacent free spans are coalesced.
+pub struct PoolAllocator {
+    /// Total pool size in bytes
+    capacity: usize,
+    /// Bump pointer (high-water mark)
+    used: usize,
+    /// Free spans sorted by start offset
+    free: [Span; MAX_FREE_SPANS],
+    /// Number of entries in `free`
+    free_count: usize,
+    /// Total bytes held by the free list
+    free_bytes: usize,
+}
+
+impl PoolAllocator {
+    /// Create an empty allocator for a pool of `capacity` bytes
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            used: 0,
+            free: [Span { start: 0, len: 0 }; MAX_FREE_SPANS],
+            free_count: 0,
+            free_bytes: 0,
+        }
+    }
+
+    /// Release every span
+    pub fn reset(&mut self) {
+        self.used = 0;
+        self.free_count = 0;
+        self.free_bytes = 0;
+    }
+
+    /// Allocate `len` bytes, returning the start offset
+    /// Returns None if neither a free span nor the pool tail can hold it
+    pub fn alloc(&mut self, len: usize) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, span) in self.free[..self.free_count].iter().enumerate() {
+            if span.len as usize >= len && best.map_or(true, |b| span.len < self.free[b].len) {
+                best = Some(i);
+            }
+        }
+
+        if let Some(i) = best {
+            let start = self.free[i].start as usize;
+            if self.free[i].len as usize == len {
+                self.remove_free(i);
+            } else {
+                self.free[i].start += len as u32;
+                self.free[i].len -= len as u32;
+            }
+            self.free_bytes -= len;
+            return Some(start);
+        }
+
+        if self.used + len > self.capacity {
+            return None;
+        }
+        let start = self.used;
+        self.used += len;
+        Some(start)
+    }
+
+    /// Return a previously allocated span to the pool
+    ///
+    /// If the free list is full and the span can't be merged with a
+    /// neighbour, the bytes stay unusable until the next `reset`.
+    pub fn free(&mut self, start: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        // Freeing the tail just lowers the bump pointer
+        if start + len == self.used {
+            self.used = start;
+            self.trim_tail();
+            return;
+        }
+
+        let span = Span { start: start as u32, len: len as u32 };
+        let pos = self.free[..self.free_count].partition_point(|s| s.start < span.start);
+
+        let merge_prev = pos > 0 && self.free[pos - 1].end() == span.start;
+        let merge_next = pos < self.free_count && span.end() == self.free[pos].start;
+
+        match (merge_prev, merge_next) {
+            (true, true) => {
+                self.free[pos - 1].len += span.len + self.free[pos].len;
+                self.remove_free(pos);
+            }
+            (true, false) => self.free[pos - 1].len += span.len,
+            (false, true) => {
+                self.free[pos].start = span.start;
+                self.free[pos].len += span.len;
+            }
+            (false, false) => {
+                if self.free_count >= MAX_FREE_SPANS {
+                    return;
+                }
+                self.free.copy_within(pos..self.free_count, pos + 1);
+                self.free[pos] = span;
+                self.free_count += 1;
+            }
+        }
+        self.free_bytes += len;
+    }
+
+    /// Bytes below the high-water mark (live data plus free spans)
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Bytes held by the free list
+    #[inline]
+    pub fn free_bytes(&self) -> usize {
+        self.free_bytes
+    }
+
+    /// Bytes still allocatable (tail plus free spans)
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.capacity - self.used + self.free_bytes
+    }
+
+    /// Total pool size in bytes
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn remove_free(&mut self, i: usize) {
+        self.free.copy_within(i + 1..self.free_count, i);
+        self.free_count -= 1;
+    }
+
+    /// Pull the bump pointer down past any free span touching it
+    fn trim_tail(&mut self) {
+        while self.free_count > 0 {
+            let last = self.free[self.free_count - 1];
+            if last.end() as usize != self.used {
+                break;
+            }
+            self.used = last.start as usize;
+            self.free_bytes -= last.len as usize;
+            self.free_count -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_alloc() {
+        let mut pool = PoolAllocator::new(100);
+        assert_eq!(pool.alloc(40), Some(0));
+        assert_eq!(pool.alloc(40), Some(40));
+        assert_eq!(pool.alloc(40), None);
+        assert_eq!(pool.used(), 80);
+    }
+
+    #[test]
+    fn test_reuse_freed_span() {
+        let mut pool = PoolAllocator::new(100);
+        pool.alloc(30);
+        pool.alloc(30);
+        pool.alloc(30);
+
+        // Free the middle span; pool tail only has 10 bytes left
+        pool.free(30, 30);
+        assert_eq!(pool.free_bytes(), 30);
+        assert_eq!(pool.alloc(20), Some(30));
+        assert_eq!(pool.alloc(10), Some(50));
+        assert_eq!(pool.free_bytes(), 0);
+        assert_eq!(pool.used(), 90);
+    }
+
+    #[test]
+    fn test_best_fit() {
+        let mut pool = PoolAllocator::new(100);
+        for _ in 0..5 {
+            pool.alloc(20);
+        }
+        pool.free(0, 20);
+        pool.free(40, 20);
+        // Coalesce 40..60 with 60..80 -> 40 bytes
+        pool.free(60, 20);
+
+        // 15 bytes should land in the 20-byte hole, not the 40-byte one
+        assert_eq!(pool.alloc(15), Some(0));
+        assert_eq!(pool.alloc(40), Some(40));
+    }
+
+    #[test]
+    fn test_free_tail_lowers_high_water() {
+        let mut pool = PoolAllocator::new(100);
+        pool.alloc(10);
+        pool.alloc(10);
+        pool.alloc(10);
+
+        pool.free(10, 10);
+        pool.free(20, 10);
+        assert_eq!(pool.used(), 10);
+        assert_eq!(pool.free_bytes(), 0);
+        assert_eq!(pool.available(), 90);
+    }
+}
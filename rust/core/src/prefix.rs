@@ -0,0 +1,247 @@
+//! Prefix index for anchored (autocomplete) queries
+//!
+//! Buckets record slots by their first two case-folded bytes so an
+//! exact prefix query only visits records that can possibly match.
+
+use crate::fold_byte;
+
+/// Number of buckets (256 first bytes x 16 second-byte nibbles)
+pub const PREFIX_BUCKETS: usize = 256 * 16;
+
+/// End-of-chain marker
+const NIL: u32 = u32::MAX;
+
+/// Bucket for a text starting with `b0`, `b1`
+#[inline]
+fn bucket(b0: u8, b1: u8) -> usize {
+    ((fold_byte(b0) as usize) << 4) | (fold_byte(b1) & 0x0F) as usize
+}
+
+/// Slot chains keyed by the first two folded bytes of each record
+///
+/// Chains keep insertion order, so candidates come out in the same order
+/// a linear scan would visit them. Removed records stay chained; callers
+/// skip inactive slots as they do in the linear scan.
+pub struct PrefixIndex<const N: usize> {
+    heads: [u32; PREFIX_BUCKETS],
+    tails: [u32; PREFIX_BUCKETS],
+    next: [u32; N],
+}
+
+impl<const N: usize> PrefixIndex<N> {
+    /// Create an empty index
+    pub const fn new() -> Self {
+        Self {
+            heads: [NIL; PREFIX_BUCKETS],
+            tails: [NIL; PREFIX_BUCKETS],
+            next: [NIL; N],
+        }
+    }
+
+    /// Drop every chain
+    pub fn reset(&mut self) {
+        self.heads = [NIL; PREFIX_BUCKETS];
+        self.tails = [NIL; PREFIX_BUCKETS];
+    }
+
+    /// Chain a record slot under its text's prefix, in slot order
+    ///
+    /// Slots added in increasing order append in O(1); a lower slot (from
+    /// `relink`) walks its chain to its place.
+    pub fn insert(&mut self, slot: u32, text: &[u8]) {
+        if text.is_empty() || slot as usize >= N {
+            return;
+        }
+
+        let b = bucket(text[0], text.get(1).copied().unwrap_or(0));
+        if self.tails[b] == NIL || self.tails[b] < slot {
+            self.next[slot as usize] = NIL;
+            if self.tails[b] == NIL {
+                self.heads[b] = slot;
+            } else {
+                self.next[self.tails[b] as usize] = slot;
+            }
+            self.tails[b] = slot;
+            return;
+        }
+
+        let mut prev = NIL;
+        let mut cur = self.heads[b];
+        while cur != NIL && cur < slot {
+            prev = cur;
+            cur = self.next[cur as usize];
+        }
+        if cur == slot {
+            return;
+        }
+        self.next[slot as usize] = cur;
+        if prev == NIL {
+            self.heads[b] = slot;
+        } else {
+            self.next[prev as usize] = slot;
+        }
+    }
+
+    /// Move a slot whose text changed from `old` to `new` to its new chain
+    pub fn relink(&mut self, slot: u32, old: &[u8], new: &[u8]) {
+        self.unlink(slot, old);
+        self.insert(slot, new);
+    }
+
+    /// Take a slot out of the chain for `text`
+    fn unlink(&mut self, slot: u32, text: &[u8]) {
+        if text.is_empty() || slot as usize >= N {
+            return;
+        }
+
+        let b = bucket(text[0], text.get(1).copied().unwrap_or(0));
+        let mut prev = NIL;
+        let mut cur = self.heads[b];
+        while cur != NIL && cur != slot {
+            prev = cur;
+            cur = self.next[cur as usize];
+        }
+        if cur == NIL {
+            return;
+        }
+
+        let after = self.next[slot as usize];
+        if prev == NIL {
+            self.heads[b] = after;
+        } else {
+            self.next[prev as usize] = after;
+        }
+        if self.tails[b] == slot {
+            self.tails[b] = prev;
+        }
+        self.next[slot as usize] = NIL;
+    }
+
+    /// Iterate slots whose text may start with `pattern`
+    ///
+    /// A one-byte pattern visits all 16 buckets sharing its first byte.
+    pub fn candidates(&self, pattern: &[u8]) -> Candidates<'_, N> {
+        let (first, last) = match pattern.len() {
+            0 => (0, 0),
+            1 => {
+                let b = bucket(pattern[0], 0);
+                (b, b + 16)
+            }
+            _ => {
+                let b = bucket(pattern[0], pattern[1]);
+                (b, b + 1)
+            }
+        };
+
+        Candidates {
+            index: self,
+            bucket: first,
+            last,
+            cursor: if first < last { self.heads[first] } else { NIL },
+        }
+    }
+}
+
+impl<const N: usize> Default for PrefixIndex<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over candidate slots from `PrefixIndex::candidates`
+pub struct Candidates<'a, const N: usize> {
+    index: &'a PrefixIndex<N>,
+    bucket: usize,
+    last: usize,
+    cursor: u32,
+}
+
+impl<const N: usize> Iterator for Candidates<'_, N> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        while self.cursor == NIL {
+            self.bucket += 1;
+            if self.bucket >= self.last {
+                return None;
+            }
+            self.cursor = self.index.heads[self.bucket];
+        }
+
+        let slot = self.cursor;
+        self.cursor = self.index.next[slot as usize];
+        Some(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect<const N: usize>(index: &PrefixIndex<N>, pattern: &[u8]) -> ([u32; 8], usize) {
+        let mut out = [0u32; 8];
+        let mut n = 0;
+        for slot in index.candidates(pattern) {
+            out[n] = slot;
+            n += 1;
+        }
+        (out, n)
+    }
+
+    #[test]
+    fn test_two_byte_bucket() {
+        let mut index: PrefixIndex<8> = PrefixIndex::new();
+        index.insert(0, b"apple");
+        index.insert(1, b"banana");
+        index.insert(2, b"Avocado");
+        index.insert(3, b"APPLE pie");
+
+        let (slots, n) = collect(&index, b"app");
+        assert_eq!(&slots[..n], &[0, 3]);
+    }
+
+    #[test]
+    fn test_one_byte_pattern() {
+        let mut index: PrefixIndex<8> = PrefixIndex::new();
+        index.insert(0, b"apple");
+        index.insert(1, b"banana");
+        index.insert(2, b"a");
+        index.insert(3, b"avocado");
+
+        let (slots, n) = collect(&index, b"a");
+        assert_eq!(n, 3);
+        assert!(slots[..n].contains(&0));
+        assert!(slots[..n].contains(&2));
+        assert!(slots[..n].contains(&3));
+    }
+
+    #[test]
+    fn test_relink_keeps_slot_order() {
+        let mut index: PrefixIndex<8> = PrefixIndex::new();
+        index.insert(0, b"apple");
+        index.insert(1, b"banana");
+        index.insert(2, b"apricot");
+        index.insert(3, b"blueberry");
+
+        index.relink(1, b"banana", b"apex");
+        let (slots, n) = collect(&index, b"ap");
+        assert_eq!(&slots[..n], &[0, 1, 2]);
+        let (slots, n) = collect(&index, b"bl");
+        assert_eq!(&slots[..n], &[3]);
+
+        index.relink(2, b"apricot", b"bramble");
+        let (slots, n) = collect(&index, b"ap");
+        assert_eq!(&slots[..n], &[0, 1]);
+        index.insert(4, b"apple pie");
+        let (slots, n) = collect(&index, b"ap");
+        assert_eq!(&slots[..n], &[0, 1, 4]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut index: PrefixIndex<4> = PrefixIndex::new();
+        index.insert(0, b"apple");
+        index.reset();
+        assert_eq!(index.candidates(b"ap").count(), 0);
+    }
+}
@@ -0,0 +1,82 @@
+//! Inline query syntax
+//!
+//! With query syntax enabled, a query may end in `~N` to set the error
+//! budget for that search: `keybord~1` searches "keybord" with at most one
+//! error. A tilde not followed only by digits is literal. To search for a
+//! literal `~N` suffix, escape the tilde with a backslash: `v\~2` searches
+//! "v~2".
+
+/// Split an inline error budget off `query`, writing the pattern to `out`
+///
+/// Returns the pattern length (truncated to `out.len()`) and the budget,
+/// if the query ended in `~N`. Budgets too large for a `u32` saturate.
+pub fn parse_error_budget(query: &[u8], out: &mut [u8]) -> (usize, Option<u32>) {
+    let digits = query.iter().rev().take_while(|c| c.is_ascii_digit()).count();
+    let tilde = query.len() - digits;
+
+    let (pattern, budget, escape) = if digits == 0 || tilde == 0 || query[tilde - 1] != b'~' {
+        (query, None, None)
+    } else if tilde >= 2 && query[tilde - 2] == b'\\' {
+        // Escaped: keep "~N" but drop the backslash
+        (query, None, Some(tilde - 2))
+    } else {
+        let budget = query[tilde..]
+            .iter()
+            .fold(0u32, |n, &c| n.saturating_mul(10).saturating_add((c - b'0') as u32));
+        (&query[..tilde - 1], Some(budget), None)
+    };
+
+    let mut len = 0;
+    for (i, &c) in pattern.iter().enumerate() {
+        if Some(i) == escape {
+            continue;
+        }
+        if len == out.len() {
+            break;
+        }
+        out[len] = c;
+        len += 1;
+    }
+    (len, budget)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &[u8]) -> ([u8; 32], usize, Option<u32>) {
+        let mut out = [0u8; 32];
+        let (len, budget) = parse_error_budget(query, &mut out);
+        (out, len, budget)
+    }
+
+    #[test]
+    fn test_budget_suffix() {
+        let (out, len, budget) = parse(b"keybord~2");
+        assert_eq!(&out[..len], b"keybord");
+        assert_eq!(budget, Some(2));
+
+        let (_, _, budget) = parse(b"x~99999999999");
+        assert_eq!(budget, Some(u32::MAX));
+    }
+
+    #[test]
+    fn test_literal_tilde() {
+        for query in [&b"a~b"[..], b"~", b"a~", b"12"] {
+            let (out, len, budget) = parse(query);
+            assert_eq!(&out[..len], query);
+            assert_eq!(budget, None);
+        }
+
+        let (out, len, budget) = parse(b"v\\~2");
+        assert_eq!(&out[..len], b"v~2");
+        assert_eq!(budget, None);
+    }
+
+    #[test]
+    fn test_truncates_to_out() {
+        let mut out = [0u8; 4];
+        assert_eq!(parse_error_budget(b"keyboard~1", &mut out), (4, Some(1)));
+        assert_eq!(&out, b"keyb");
+    }
+}
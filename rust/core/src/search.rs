@@ -0,0 +1,1219 @@
+//! Record scanning independent of any global state
+//!
+//! The FFI and WASM layers keep records and their text in static buffers;
+//! this module holds the matching and ranking logic over plain slices so
+//! it can be reused (and tested) from ordinary Rust code.
+
+use core::cmp::Ordering;
+
+use crate::bitap::{self, BitapSearcher};
+use crate::bloom::BloomFilter;
+use crate::charset::CharSet;
+use crate::phonetic::{self, NO_CODE, PHONETIC_SCORE};
+use crate::subsequence::{self, SubsequenceSearcher};
+use crate::types::{
+    Anchor, EditProfile, MatchMode, ScoreBreakdown, ScoredResult, SearchConfig, SearchMatch, SortOrder, SubsequenceMatch,
+    WordPrefixMatch,
+};
+use crate::word_prefix::{self, WordPrefixSearcher};
+
+/// A record as stored by the bindings: text spans into a shared byte pool
+#[derive(Clone, Copy, Debug)]
+pub struct RecordView {
+    /// Caller-supplied record ID
+    pub id: u32,
+    /// Offset of the record text in the pool
+    pub text_start: u32,
+    /// Length of the record text
+    pub text_len: u32,
+    /// Offset of the alias text in the pool
+    pub alias_start: u32,
+    /// Length of the alias text (0 if none)
+    pub alias_len: u32,
+    /// Bloom filter over text and alias
+    pub bloom: u64,
+    /// Exact set of chars in text and alias (`CharSet::FULL` if unknown)
+    pub chars: CharSet,
+    /// Insertion counter, used for recency scoring
+    pub added_seq: u32,
+    /// Phonetic code of the text's first word (`phonetic::encode`)
+    pub phonetic: u16,
+    /// False once removed
+    pub active: bool,
+}
+
+impl Default for RecordView {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            text_start: 0,
+            text_len: 0,
+            alias_start: 0,
+            alias_len: 0,
+            bloom: 0,
+            chars: CharSet::FULL,
+            added_seq: 0,
+            phonetic: 0,
+            active: false,
+        }
+    }
+}
+
+impl RecordView {
+    /// Record text within `pool`
+    #[inline]
+    pub fn text<'p>(&self, pool: &'p [u8]) -> &'p [u8] {
+        let start = self.text_start as usize;
+        &pool[start..start + self.text_len as usize]
+    }
+
+    /// Alias text within `pool` (empty if none)
+    #[inline]
+    pub fn alias<'p>(&self, pool: &'p [u8]) -> &'p [u8] {
+        let start = self.alias_start as usize;
+        &pool[start..start + self.alias_len as usize]
+    }
+
+    /// Text `result` was matched in: the alias or the record text
+    #[inline]
+    pub fn matched_text<'p>(&self, result: &ScoredResult, pool: &'p [u8]) -> &'p [u8] {
+        if result.alias {
+            self.alias(pool)
+        } else {
+            self.text(pool)
+        }
+    }
+}
+
+/// Borrowed record table and string pool, seen through its active records
+#[derive(Clone, Copy, Debug)]
+pub struct RecordsView<'a> {
+    records: &'a [RecordView],
+    pool: &'a [u8],
+}
+
+impl<'a> RecordsView<'a> {
+    /// View `records`, whose text spans index into `pool`
+    pub fn new(records: &'a [RecordView], pool: &'a [u8]) -> Self {
+        Self { records, pool }
+    }
+
+    /// Iterate `(id, text)` of active records in slot order
+    pub fn iter(&self) -> ActiveRecords<'a> {
+        ActiveRecords { slots: self.slots(), pool: self.pool }
+    }
+
+    /// Iterate `(slot, record)` of active records in slot order
+    pub fn slots(&self) -> ActiveSlots<'a> {
+        ActiveSlots { inner: self.records.iter().enumerate() }
+    }
+
+    /// Pool bytes held by active records, text and alias
+    pub fn live_bytes(&self) -> usize {
+        self.slots().map(|(_, record)| (record.text_len + record.alias_len) as usize).sum()
+    }
+}
+
+impl<'a> IntoIterator for RecordsView<'a> {
+    type Item = (u32, &'a [u8]);
+    type IntoIter = ActiveRecords<'a>;
+
+    fn into_iter(self) -> ActiveRecords<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over active `(slot, record)` pairs from `RecordsView::slots`
+#[derive(Clone, Debug)]
+pub struct ActiveSlots<'a> {
+    inner: core::iter::Enumerate<core::slice::Iter<'a, RecordView>>,
+}
+
+impl<'a> Iterator for ActiveSlots<'a> {
+    type Item = (usize, &'a RecordView);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.find(|(_, record)| record.active)
+    }
+}
+
+/// Iterator over active `(id, text)` pairs from `RecordsView::iter`
+#[derive(Clone, Debug)]
+pub struct ActiveRecords<'a> {
+    slots: ActiveSlots<'a>,
+    pool: &'a [u8],
+}
+
+impl<'a> Iterator for ActiveRecords<'a> {
+    type Item = (u32, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|(_, record)| (record.id, record.text(self.pool)))
+    }
+}
+
+/// Raw match found by a `Matcher`, before scoring
+enum Found {
+    Bitap(SearchMatch),
+    Subsequence(SubsequenceMatch),
+    WordPrefix(WordPrefixMatch),
+}
+
+/// Matches one prepared pattern against records under a config
+pub struct Matcher<'a> {
+    searcher: &'a BitapSearcher,
+    subsequence: Option<SubsequenceSearcher>,
+    words: Option<WordPrefixSearcher>,
+    config: &'a SearchConfig,
+}
+
+impl<'a> Matcher<'a> {
+    /// Create a matcher for `searcher`'s pattern
+    pub fn new(searcher: &'a BitapSearcher, config: &'a SearchConfig) -> Self {
+        let subsequence = match config.match_mode {
+            MatchMode::Subsequence => Some(SubsequenceSearcher::new(searcher.pattern()).with_separators(config.separators)),
+            MatchMode::Bitap | MatchMode::WordPrefix => None,
+        };
+        let words = match config.match_mode {
+            MatchMode::WordPrefix => Some(
+                WordPrefixSearcher::new(searcher.pattern())
+                    .with_separators(config.separators)
+                    .with_bloom_hash(searcher.bloom_hash()),
+            ),
+            MatchMode::Bitap | MatchMode::Subsequence => None,
+        };
+        Self { searcher, subsequence, words, config }
+    }
+
+    /// Bloom and exact char set the record must pass
+    /// Word prefix matching drops the pattern's separators, so the record
+    /// needn't contain those.
+    #[inline]
+    fn pattern_filters(&self) -> (BloomFilter, CharSet) {
+        match &self.words {
+            Some(words) => (words.bloom(), words.chars()),
+            None => (self.searcher.bloom(), self.searcher.chars()),
+        }
+    }
+
+    /// Whether every pattern char must be in the record
+    #[inline]
+    fn needs_all_chars(&self) -> bool {
+        self.subsequence.is_some() || self.words.is_some()
+    }
+
+    /// True if the pattern is too broad to be worth searching
+    /// A bitap pattern spending its whole error budget on chars the text
+    /// lacks matches any text holding the rest of its bloom bits. When the
+    /// budget bitap would use is at least half the pattern and fewer than
+    /// `min_pattern_bits` bits remain, nearly every record would match.
+    /// Subsequence and word prefix patterns need every char, so they are
+    /// never too broad; neither is anything with `min_pattern_bits` at 0.
+    pub fn too_broad(&self) -> bool {
+        if self.config.min_pattern_bits == 0 || self.needs_all_chars() {
+            return false;
+        }
+        let pattern_len = self.searcher.pattern_len();
+        let errors = bitap::effective_errors(pattern_len, self.config.errors_for(pattern_len, usize::MAX));
+        let bits = self.pattern_filters().0.bits().count_ones();
+        errors * 2 >= pattern_len as u32 && bits.saturating_sub(errors) < self.config.min_pattern_bits
+    }
+
+    /// Bloom filter pre-check (bloom part skipped with `config.bloom` off)
+    /// Bitap tolerates as many pattern chars absent from the record as its
+    /// error budget; subsequence and word prefix matching need every char.
+    /// When none may be missing, the record's exact char set must also hold
+    /// them all.
+    #[inline]
+    pub fn bloom_passes(&self, record: &RecordView) -> bool {
+        let max_missing = if self.needs_all_chars() {
+            0
+        } else {
+            self.max_errors(record.text_len.max(record.alias_len) as usize)
+        };
+        let (bloom, chars) = self.pattern_filters();
+        if max_missing == 0 && !record.chars.contains_all(chars) {
+            return false;
+        }
+        !self.config.bloom || BloomFilter(record.bloom).might_contain_within(bloom, max_missing)
+    }
+
+    /// As `bloom_passes`, for the OR of several records' blooms
+    /// Allows the largest error budget any record in the block could get.
+    #[inline]
+    pub fn block_passes(&self, bloom: u64) -> bool {
+        let max_missing = if self.needs_all_chars() { 0 } else { self.config.max_errors };
+        !self.config.bloom || BloomFilter(bloom).might_contain_within(self.pattern_filters().0, max_missing)
+    }
+
+    /// Bitap error budget against a text of `text_len` bytes
+    #[inline]
+    fn max_errors(&self, text_len: usize) -> u32 {
+        self.config.errors_for(self.searcher.pattern_len(), text_len)
+    }
+
+    /// Run the configured match against one text (or its window)
+    /// Word prefix matching ignores the anchor.
+    #[inline]
+    fn find(&self, text: &[u8]) -> Option<Found> {
+        let text = self.config.windowed(text);
+        if let Some(words) = &self.words {
+            return words.search(text).map(Found::WordPrefix);
+        }
+        match &self.subsequence {
+            None => {
+                let max_errors = self.max_errors(text.len());
+                let m = match self.config.anchor {
+                    Anchor::None => self.searcher.search(text, max_errors),
+                    Anchor::Start => self.searcher.search_anchored(text, max_errors),
+                    Anchor::End => self.searcher.search_anchored_end(text, max_errors),
+                };
+                m.filter(|m| self.config.long_enough(m.end_pos - m.start_pos, self.searcher.pattern_len()))
+                    .map(Found::Bitap)
+            }
+            Some(subsequence) => {
+                let m = match self.config.anchor {
+                    Anchor::None => subsequence.search(text),
+                    Anchor::Start => subsequence.search_anchored(text),
+                    Anchor::End => subsequence.search_anchored_end(text),
+                };
+                m.map(Found::Subsequence)
+            }
+        }
+    }
+
+    /// Match one text, returning score, span and errors (ID left at 0)
+    pub fn match_text(&self, text: &[u8]) -> Option<ScoredResult> {
+        let result = match self.find(text)? {
+            Found::Bitap(m) => {
+                let pattern_len = self.searcher.pattern_len();
+                let score = bitap::compute_score(m.errors, pattern_len as u32, m.end_pos);
+                ScoredResult::new(0, score, m.start_pos, m.end_pos).with_errors(m.errors)
+            }
+            Found::Subsequence(m) => {
+                let score = subsequence::compute_score(&m, self.config.boundary_bonus);
+                // Each gap byte is one insertion relative to the pattern
+                ScoredResult::new(0, score, m.start, m.end).with_errors(m.gaps)
+            }
+            Found::WordPrefix(m) => ScoredResult::new(0, word_prefix::compute_score(&m), m.start, m.end),
+        };
+        Some(ScoredResult { text_len: text.len() as u32, ..result })
+    }
+
+    /// Count the pattern's non-overlapping occurrences in `text`
+    /// Subsequence and word prefix matches count once.
+    pub fn count_hits(&self, text: &[u8]) -> u32 {
+        if self.subsequence.is_some() || self.words.is_some() {
+            return self.find(text).is_some() as u32;
+        }
+        let text = self.config.windowed(text);
+        self.searcher.count_matches(text, self.max_errors(text.len())) as u32
+    }
+
+    /// Break down the score `match_text` gives one text
+    pub fn explain_text(&self, text: &[u8]) -> Option<ScoreBreakdown> {
+        Some(match self.find(text)? {
+            Found::Bitap(m) => bitap::score_breakdown(m.errors, self.searcher.pattern_len() as u32, m.end_pos),
+            Found::Subsequence(m) => subsequence::score_breakdown(&m, self.config.boundary_bonus),
+            Found::WordPrefix(m) => word_prefix::score_breakdown(&m),
+        })
+    }
+
+    /// Count the kinds of edits in the match `match_text` finds in one text
+    /// A subsequence match's gap bytes all count as insertions; word
+    /// prefix matches have no edits.
+    pub fn edit_profile(&self, text: &[u8]) -> Option<EditProfile> {
+        let text = self.config.windowed(text);
+        Some(match self.find(text)? {
+            Found::Bitap(m) => self.searcher.edit_profile(text, m),
+            Found::Subsequence(m) => EditProfile { insertions: m.gaps, ..EditProfile::default() },
+            Found::WordPrefix(_) => EditProfile::default(),
+        })
+    }
+
+    /// Match a record's text and alias, keeping the better score
+    pub fn match_record(&self, record: &RecordView, pool: &[u8]) -> Option<ScoredResult> {
+        let mut best = self.match_text(record.text(pool));
+        if record.alias_len > 0 {
+            if let Some(alias) = self.match_text(record.alias(pool)) {
+                if best.map_or(true, |b| alias.score > b.score) {
+                    best = Some(ScoredResult { alias: true, ..alias });
+                }
+            }
+        }
+        best.map(|mut result| {
+            result.id = record.id;
+            result
+        })
+    }
+}
+
+/// Lower a score by how long ago its record was added
+///
+/// The newest record (`added_seq == next_seq - 1`) keeps its score; older
+/// ones lose up to `weight` in proportion to their age, so ties among
+/// perfect matches still break. A weight of 0 returns `score` unchanged.
+pub fn apply_recency(score: u16, added_seq: u32, next_seq: u32, weight: u16) -> u16 {
+    if weight == 0 || next_seq == 0 {
+        return score;
+    }
+    let age = next_seq.saturating_sub(added_seq.wrapping_add(1)) as u64;
+    let penalty = weight as u64 * age / next_seq as u64;
+    score.saturating_sub(penalty as u16)
+}
+
+/// Ranking order: higher score first, then lower record `index`
+///
+/// Indices are unique within one search, so this is a total order and
+/// every binding ranks equal-score records identically.
+#[inline]
+pub fn rank_order(a: &ScoredResult, b: &ScoredResult) -> Ordering {
+    b.score.cmp(&a.score).then(a.index.cmp(&b.index))
+}
+
+/// Insert `result` into the ranked prefix `out[..count]`, keeping at most `limit`
+///
+/// When full, the lowest-ranked entry is dropped if `result` outranks it.
+/// Returns the new count.
+pub fn insert_ranked(out: &mut [ScoredResult], count: usize, limit: usize, result: ScoredResult) -> usize {
+    let limit = limit.min(out.len());
+    let count = count.min(limit);
+    let pos = out[..count].partition_point(|r| rank_order(r, &result) == Ordering::Less);
+    if pos >= limit {
+        return count;
+    }
+
+    let kept = if count == limit { limit - 1 } else { count };
+    out.copy_within(pos..kept, pos + 1);
+    out[pos] = result;
+    kept + 1
+}
+
+/// Results kept in `rank_order`, capped at `max_results`
+///
+/// With `dedup`, only the best result per distinct record text is kept;
+/// with `min_score_gap`, only the best of results whose matched text is
+/// identical and whose scores are within the gap. `finish` applies the
+/// config's `SortOrder` to the final set.
+pub struct ResultSet<'a> {
+    out: &'a mut [ScoredResult],
+    count: usize,
+    limit: usize,
+    dedup: bool,
+    min_score_gap: u16,
+    sort: SortOrder,
+}
+
+impl<'a> ResultSet<'a> {
+    /// Collect into `out`, keeping at most `config.max_results` entries
+    pub fn new(out: &'a mut [ScoredResult], config: &SearchConfig) -> Self {
+        let limit = config.max_results.min(out.len());
+        Self { out, count: 0, limit, dedup: config.dedup, min_score_gap: config.min_score_gap, sort: config.sort }
+    }
+
+    /// Insert a result whose `index` points into `records`
+    pub fn insert(&mut self, result: ScoredResult, records: &[RecordView], pool: &[u8]) {
+        if self.dedup {
+            let text = records[result.index as usize].text(pool);
+            let existing = self.out[..self.count]
+                .iter()
+                .position(|r| records[r.index as usize].text(pool) == text);
+            if !self.displace(existing, &result) {
+                return;
+            }
+        }
+        if self.min_score_gap > 0 {
+            let span = matched_span(&result, records, pool);
+            let near = self.out[..self.count].iter().position(|r| {
+                r.score.abs_diff(result.score) <= self.min_score_gap && matched_span(r, records, pool) == span
+            });
+            if !self.displace(near, &result) {
+                return;
+            }
+        }
+
+        self.count = insert_ranked(self.out, self.count, self.limit, result);
+    }
+
+    /// Resolve `result` against the kept result at `existing`, if any
+    /// Removes that one if `result` scores higher; returns false if it
+    /// should be dropped instead.
+    fn displace(&mut self, existing: Option<usize>, result: &ScoredResult) -> bool {
+        let Some(i) = existing else {
+            return true;
+        };
+        if self.out[i].score >= result.score {
+            return false;
+        }
+        self.out.copy_within(i + 1..self.count, i);
+        self.count -= 1;
+        true
+    }
+
+    /// Number of results collected
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// True if nothing has been collected
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Arrange the results in the configured order, returning their count
+    pub fn finish(self) -> usize {
+        let results = &mut self.out[..self.count];
+        let top = results.first().map_or(0, |r| r.score as u32);
+        for (rank, r) in results.iter_mut().enumerate() {
+            r.rank = rank as u32;
+            r.relative = (r.score as u32 * 1000).checked_div(top).unwrap_or(0) as u16;
+        }
+        match self.sort {
+            // Already in rank order
+            SortOrder::ByScore => {}
+            SortOrder::ByPosition => results.sort_unstable_by_key(|r| (r.index, r.start, r.end)),
+            SortOrder::ById => results.sort_unstable_by_key(|r| (r.id, r.index)),
+            SortOrder::ByDensity => {
+                results.sort_unstable_by(|a, b| b.score.cmp(&a.score).then(b.density().cmp(&a.density())).then(a.index.cmp(&b.index)))
+            }
+        }
+        self.count
+    }
+}
+
+/// The bytes `result` spans in the text it was matched in
+#[inline]
+fn matched_span<'p>(result: &ScoredResult, records: &[RecordView], pool: &'p [u8]) -> &'p [u8] {
+    let text = records[result.index as usize].matched_text(result, pool);
+    text.get(result.start as usize..result.end as usize).unwrap_or(&[])
+}
+
+/// Incremental search over records the caller chooses to visit
+///
+/// `search_records` visits every record; the bindings use this directly
+/// to visit prefix-index candidates or to veto matches.
+pub struct RecordSearch<'a> {
+    records: &'a [RecordView],
+    pool: &'a [u8],
+    config: &'a SearchConfig,
+    matcher: Matcher<'a>,
+    results: ResultSet<'a>,
+    next_seq: u32,
+    phonetic: u16,
+    threshold: u16,
+}
+
+impl<'a> RecordSearch<'a> {
+    /// Start a search writing results into `out`
+    pub fn new(
+        records: &'a [RecordView],
+        pool: &'a [u8],
+        searcher: &'a BitapSearcher,
+        config: &'a SearchConfig,
+        out: &'a mut [ScoredResult],
+    ) -> Self {
+        let next_seq = if config.recency_weight > 0 {
+            records.iter().map(|r| r.added_seq.wrapping_add(1)).max().unwrap_or(0)
+        } else {
+            0
+        };
+
+        Self {
+            records,
+            pool,
+            config,
+            matcher: Matcher::new(searcher, config),
+            results: ResultSet::new(out, config),
+            next_seq,
+            phonetic: if config.phonetic { phonetic::encode(searcher.pattern()) } else { NO_CODE },
+            threshold: config.threshold_for(searcher.pattern_len()),
+        }
+    }
+
+    /// True if a block of records whose blooms OR to `bloom` may hold a match
+    /// Callers can skip blocks that fail without visiting their records.
+    /// Phonetic matches bypass blooms, so every block passes with them on.
+    #[inline]
+    pub fn may_match_block(&self, bloom: u64) -> bool {
+        self.phonetic != NO_CODE || self.matcher.block_passes(bloom)
+    }
+
+    /// True if the pattern is too broad to be worth searching, see `Matcher::too_broad`
+    #[inline]
+    pub fn too_broad(&self) -> bool {
+        self.matcher.too_broad()
+    }
+
+    /// Score the record at `index` and keep it if it qualifies
+    #[inline]
+    pub fn visit(&mut self, index: usize) {
+        self.visit_if(index, |_| true);
+    }
+
+    /// As `visit`, but drop the record if `keep` returns false
+    /// `keep` only runs for records that pass the threshold.
+    pub fn visit_if(&mut self, index: usize, keep: impl FnOnce(&RecordView) -> bool) {
+        if let Some(result) = self.score_record(index, keep) {
+            self.results.insert(result, self.records, self.pool);
+        }
+    }
+
+    /// As `visit_if`, ranking by the score `rescore` gives the result
+    /// The threshold applies to the text score; the final one is capped at 1000.
+    pub fn visit_rescored(
+        &mut self,
+        index: usize,
+        keep: impl FnOnce(&RecordView) -> bool,
+        rescore: impl FnOnce(&ScoredResult) -> u32,
+    ) {
+        if let Some(mut result) = self.score_record(index, keep) {
+            result.score = rescore(&result).min(1000) as u16;
+            self.results.insert(result, self.records, self.pool);
+        }
+    }
+
+    /// Score the record at `index` as `visit_if` would, without keeping it
+    /// Returns the result if the record qualifies, for callers that stream
+    /// matches instead of ranking them.
+    pub fn score_record(&self, index: usize, keep: impl FnOnce(&RecordView) -> bool) -> Option<ScoredResult> {
+        let record = &self.records[index];
+        if !record.active {
+            return None;
+        }
+
+        // A phonetic match bypasses the bloom filter: "Katherine" has no 'c'
+        let sounds_alike = self.phonetic != NO_CODE && record.phonetic == self.phonetic;
+        let mut matched = if self.matcher.bloom_passes(record) {
+            self.matcher.match_record(record, self.pool)
+        } else {
+            None
+        };
+        if sounds_alike && matched.map_or(true, |r| r.score < PHONETIC_SCORE) {
+            let (start, end) = phonetic::first_word(record.text(self.pool)).unwrap_or((0, 0));
+            matched = Some(ScoredResult {
+                text_len: record.text_len,
+                ..ScoredResult::new(record.id, PHONETIC_SCORE, start, end)
+            });
+        }
+
+        let mut result = matched.filter(|r| r.score >= self.threshold && keep(record))?;
+        result.score = apply_recency(result.score, record.added_seq, self.next_seq, self.config.recency_weight);
+        result.index = index as u32;
+        Some(result)
+    }
+
+    /// Finish, returning the number of results written
+    /// Counts each kept result's hits first, so only they pay for it.
+    pub fn finish(self) -> usize {
+        for result in &mut self.results.out[..self.results.count] {
+            let record = &self.records[result.index as usize];
+            let text = if result.alias { record.alias(self.pool) } else { record.text(self.pool) };
+            result.hits = self.matcher.count_hits(text);
+        }
+        self.results.finish()
+    }
+}
+
+/// Search `records` for `searcher`'s pattern, writing ranked results to `out`
+///
+/// Text spans in `records` index into `pool`. Returns the number of
+/// results written, at most `config.max_results` and `out.len()`.
+pub fn search_records(
+    records: &[RecordView],
+    pool: &[u8],
+    searcher: &BitapSearcher,
+    config: &SearchConfig,
+    out: &mut [ScoredResult],
+) -> usize {
+    let mut search = RecordSearch::new(records, pool, searcher, config, out);
+    for (index, _) in RecordsView::new(records, pool).slots() {
+        search.visit(index);
+    }
+    search.finish()
+}
+
+/// Length in bytes of the longest prefix shared by every result's record text
+///
+/// Compares bytes exactly, as tab completion would fill them in, and
+/// backs off so a multibyte UTF-8 char is never split. Alias matches
+/// still use the record text. 0 if there are no results.
+pub fn common_prefix_len(records: &[RecordView], pool: &[u8], results: &[ScoredResult]) -> usize {
+    let mut texts = results.iter().map(|r| records[r.index as usize].text(pool));
+    let Some(first) = texts.next() else {
+        return 0;
+    };
+    let mut len = texts.fold(first.len(), |len, text| {
+        first[..len].iter().zip(text).take_while(|(a, b)| a == b).count()
+    });
+    while len > 0 && len < first.len() && first[len] & 0xC0 == 0x80 {
+        len -= 1;
+    }
+    len
+}
+
+/// Drop inactive records, moving active ones down without reordering them
+///
+/// `results` whose `index` points at an active record are renumbered to
+/// its new slot and kept in order; those of removed records are dropped.
+/// Returns `(records kept, results kept)`. Anything else keyed by slot
+/// (block blooms, prefix index) must be rebuilt by the caller.
+pub fn compact_records(records: &mut [RecordView], results: &mut [ScoredResult]) -> (usize, usize) {
+    // Renumber results while removed slots are still in place to count
+    let mut results_kept = 0;
+    for i in 0..results.len() {
+        let slot = results[i].index as usize;
+        if records.get(slot).is_some_and(|r| r.active) {
+            let removed_before = records[..slot].iter().filter(|r| !r.active).count();
+            results[results_kept] = ScoredResult { index: (slot - removed_before) as u32, ..results[i] };
+            results_kept += 1;
+        }
+    }
+
+    let mut kept = 0;
+    for slot in 0..records.len() {
+        if records[slot].active {
+            records[kept] = records[slot];
+            kept += 1;
+        }
+    }
+    (kept, results_kept)
+}
+
+/// Find the active record whose text is closest to `searcher`'s pattern
+///
+/// Ignores thresholds: returns the slot of the record matching with the
+/// fewest errors, at most `max_errors`, ties going to the higher score and
+/// then the earlier slot. None if no record matches within the budget.
+pub fn closest_record(records: &[RecordView], pool: &[u8], searcher: &BitapSearcher, max_errors: u32) -> Option<usize> {
+    let pattern_len = searcher.pattern_len() as u32;
+    let mut best: Option<(usize, u32, u16)> = None;
+    let mut budget = max_errors;
+
+    for (slot, record) in RecordsView::new(records, pool).slots() {
+        if !BloomFilter(record.bloom).might_contain_within(searcher.bloom(), budget) {
+            continue;
+        }
+        let Some(m) = searcher.search(record.text(pool), budget) else {
+            continue;
+        };
+        let score = bitap::compute_score(m.errors, pattern_len, m.end_pos);
+        if best.map_or(true, |(_, errors, best_score)| m.errors < errors || (m.errors == errors && score > best_score)) {
+            best = Some((slot, m.errors, score));
+            // Only as close or closer records can win from here
+            budget = m.errors;
+        }
+    }
+    best.map(|(slot, _, _)| slot)
+}
+
+/// Compare two result lists by record ID
+///
+/// Writes IDs in `current` but not `previous` to `added`, and IDs in
+/// `previous` but not `current` to `removed`, each in its list's order and
+/// truncated to its buffer. Returns the number written to each.
+pub fn diff_result_ids(
+    previous: &[ScoredResult],
+    current: &[ScoredResult],
+    added: &mut [u32],
+    removed: &mut [u32],
+) -> (usize, usize) {
+    let write = |from: &[ScoredResult], other: &[ScoredResult], out: &mut [u32]| {
+        let fresh = from.iter().filter(|r| !other.iter().any(|o| o.id == r.id));
+        let mut count = 0;
+        for (slot, r) in out.iter_mut().zip(fresh) {
+            *slot = r.id;
+            count += 1;
+        }
+        count
+    };
+    (write(current, previous, added), write(previous, current, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack texts into a pool and build active records for them
+    fn build<const N: usize>(texts: [&str; N], pool: &mut [u8]) -> [RecordView; N] {
+        let mut records = [RecordView::default(); N];
+        let mut offset = 0;
+        for (i, text) in texts.iter().enumerate() {
+            pool[offset..offset + text.len()].copy_from_slice(text.as_bytes());
+            records[i] = RecordView {
+                id: i as u32 + 1,
+                text_start: offset as u32,
+                text_len: text.len() as u32,
+                bloom: BloomFilter::from_text(text.as_bytes()).bits(),
+                chars: CharSet::from_text(text.as_bytes()),
+                added_seq: i as u32,
+                phonetic: phonetic::encode(text.as_bytes()),
+                active: true,
+                ..RecordView::default()
+            };
+            offset += text.len();
+        }
+        records
+    }
+
+    #[test]
+    fn test_ranked_results() {
+        let mut pool = [0u8; 64];
+        let records = build(["goodbye", "helo there", "hello world"], &mut pool);
+        let searcher = BitapSearcher::new(b"hello");
+        let mut out = [ScoredResult::default(); 8];
+
+        let n = search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out);
+        assert_eq!(n, 2);
+        assert_eq!(out[0].id, 3);
+        assert_eq!(out[0].errors, 0);
+        assert_eq!(out[1].id, 2);
+        assert_eq!(out[1].index, 1);
+    }
+
+    #[test]
+    fn test_rank_and_relative() {
+        let mut pool = [0u8; 64];
+        let records = build(["goodbye", "helo there", "hello world"], &mut pool);
+        let searcher = BitapSearcher::new(b"hello");
+        let mut out = [ScoredResult::default(); 8];
+        let config = SearchConfig { sort: SortOrder::ById, ..SearchConfig::default() };
+
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 2);
+        // ID order puts the weaker match first; rank still follows score
+        assert_eq!((out[0].id, out[0].rank), (2, 1));
+        assert_eq!((out[1].id, out[1].rank, out[1].relative), (3, 0, 1000));
+        assert_eq!(out[0].relative as u32, out[0].score as u32 * 1000 / out[1].score as u32);
+        assert!(out[0].relative < 1000);
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        let mut pool = [0u8; 64];
+        let records = build(["git commit", "git checkout", "git cherry-pick", "café", "cafè"], &mut pool);
+        let result = |index| ScoredResult { index, ..ScoredResult::default() };
+
+        assert_eq!(common_prefix_len(&records, &pool, &[result(0), result(1), result(2)]), 5);
+        assert_eq!(common_prefix_len(&records, &pool, &[result(1)]), 12);
+        assert_eq!(common_prefix_len(&records, &pool, &[]), 0);
+        // é and è share their first UTF-8 byte, which isn't a whole char
+        assert_eq!(common_prefix_len(&records, &pool, &[result(3), result(4)]), 3);
+    }
+
+    #[test]
+    fn test_compact_records() {
+        let mut pool = [0u8; 64];
+        let mut records = build(["alpha", "beta", "gamma", "delta"], &mut pool);
+        records[0].active = false;
+        records[2].active = false;
+        let result = |index| ScoredResult { index, ..ScoredResult::default() };
+        let mut results = [result(3), result(2), result(1)];
+
+        assert_eq!(compact_records(&mut records, &mut results), (2, 2));
+        let ids: [u32; 2] = core::array::from_fn(|i| records[i].id);
+        assert_eq!(ids, [2, 4]);
+        // delta moved from slot 3 to 1, beta from 1 to 0; gamma's result went
+        assert_eq!((results[0].index, results[1].index), (1, 0));
+        assert_eq!(records[results[0].index as usize].text(&pool), b"delta");
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut pool = [0u8; 32];
+        let records = build(["mouse", "wireless mouse pad"], &mut pool);
+        let searcher = BitapSearcher::new(b"mouse");
+        let mut out = [ScoredResult::default(); 4];
+
+        assert_eq!(search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out), 2);
+        assert_eq!(out[0].coverage(), 1000);
+        assert_eq!(out[1].coverage(), 5 * 1000 / 18);
+    }
+
+    #[test]
+    fn test_fold_diacritics_exact() {
+        let mut pool = [0u8; 32];
+        let records = build(["une naïve idée", "naval"], &mut pool);
+        let config = SearchConfig { fold_case: false, fold_diacritics: true, max_errors: 0, ..SearchConfig::default() };
+        let mut out = [ScoredResult::default(); 4];
+
+        // The record's char set holds 'i' next to the bytes of "ï"
+        let searcher = BitapSearcher::with_folding(b"naive", config.folding());
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 1);
+        assert_eq!((out[0].id, out[0].start, out[0].end), (1, 4, 10));
+
+        let searcher = BitapSearcher::with_folding(b"NAIVE", config.folding());
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 0);
+    }
+
+    #[test]
+    fn test_char_span() {
+        let mut pool = [0u8; 32];
+        let records = build(["café crème brûlée"], &mut pool);
+        let searcher = BitapSearcher::new("brûlée".as_bytes());
+        let mut out = [ScoredResult::default(); 4];
+
+        assert_eq!(search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out), 1);
+        assert_eq!((out[0].start, out[0].end), (13, 21));
+        assert_eq!(out[0].char_span(records[0].matched_text(&out[0], &pool)), (11, 17));
+    }
+
+    #[test]
+    fn test_positions_past_u16() {
+        let mut pool = [b'x'; 70_016];
+        pool[70_000..70_008].copy_from_slice(b"keyboard");
+        let records = [RecordView {
+            id: 1,
+            text_len: pool.len() as u32,
+            bloom: BloomFilter::from_text(&pool).bits(),
+            chars: CharSet::from_text(&pool),
+            active: true,
+            ..RecordView::default()
+        }];
+        let searcher = BitapSearcher::new(b"keyboard");
+        let mut out = [ScoredResult::default(); 4];
+
+        assert_eq!(search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out), 1);
+        // Only the std build's u32 positions reach this far; no_std saturates
+        #[cfg(feature = "std")]
+        assert_eq!((out[0].start, out[0].end), (70_000, 70_008));
+        #[cfg(not(feature = "std"))]
+        assert_eq!((out[0].start, out[0].end), (u16::MAX, u16::MAX));
+    }
+
+    #[test]
+    fn test_records_view_skips_inactive() {
+        let mut pool = [0u8; 32];
+        let mut records = build(["alpha", "beta", "gamma"], &mut pool);
+        records[1].active = false;
+
+        let mut view = RecordsView::new(&records, &pool).iter();
+        assert_eq!(view.next(), Some((1, &b"alpha"[..])));
+        assert_eq!(view.next(), Some((3, &b"gamma"[..])));
+        assert_eq!(view.next(), None);
+        assert_eq!(RecordsView::new(&records, &pool).slots().map(|(slot, _)| slot).last(), Some(2));
+    }
+
+    #[test]
+    fn test_exact_chars_prefilter() {
+        // 'a' and '!' share a bloom bit, so only the char set tells them apart
+        let mut pool = [0u8; 32];
+        let records = build(["alpha", "!lpha"], &mut pool);
+        let searcher = BitapSearcher::new(b"!lph");
+
+        let exact = SearchConfig { max_errors: 0, ..SearchConfig::default() };
+        let matcher = Matcher::new(&searcher, &exact);
+        assert!(!matcher.bloom_passes(&records[0]));
+        assert!(matcher.bloom_passes(&records[1]));
+
+        let fuzzy = SearchConfig::default();
+        assert!(Matcher::new(&searcher, &fuzzy).bloom_passes(&records[0]));
+    }
+
+    #[test]
+    fn test_max_results_and_inactive() {
+        let mut pool = [0u8; 64];
+        let mut records = build(["apple", "apples", "apple pie"], &mut pool);
+        records[0].active = false;
+        let searcher = BitapSearcher::new(b"apple");
+        let mut out = [ScoredResult::default(); 8];
+
+        let config = SearchConfig { max_results: 1, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 1);
+        assert_eq!(out[0].id, 2);
+
+        let config = SearchConfig { max_results: 0, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 0);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut pool = [0u8; 64];
+        let records = build(["report", "report", "reports"], &mut pool);
+        let searcher = BitapSearcher::new(b"report");
+        let mut out = [ScoredResult::default(); 8];
+
+        let config = SearchConfig { dedup: true, ..SearchConfig::default() };
+        let n = search_records(&records, &pool, &searcher, &config, &mut out);
+        assert_eq!(n, 2);
+        assert_eq!(out[0].id, 1);
+        assert_eq!(out[1].id, 3);
+    }
+
+    #[test]
+    fn test_min_match_ratio() {
+        let searcher = BitapSearcher::new(b"keyboards");
+        let config = SearchConfig { max_errors: 4, ..SearchConfig::default() };
+        // Four deletions leave "keybo": 5 of the pattern's 9 bytes
+        let span = |config: &SearchConfig| Matcher::new(&searcher, config).match_text(b"a keybo").map(|r| r.byte_span());
+        assert_eq!(span(&config), Some((2, 7)));
+        assert_eq!(span(&SearchConfig { min_match_ratio: 0.5, ..config }), Some((2, 7)));
+        assert_eq!(span(&SearchConfig { min_match_ratio: 0.6, ..config }), None);
+    }
+
+    #[test]
+    fn test_too_broad() {
+        let mut pool = [0u8; 16];
+        let records = build(["abc"], &mut pool);
+        let broad = |pattern: &[u8], config: &SearchConfig| {
+            let searcher = BitapSearcher::new(pattern);
+            RecordSearch::new(&records, &pool, &searcher, config, &mut []).too_broad()
+        };
+
+        // Off by default
+        let config = SearchConfig { max_errors: 3, ..SearchConfig::default() };
+        assert!(!broad(b"eeeaaa", &config));
+
+        // Bitap searches short patterns exactly, so they never qualify
+        let config = SearchConfig { min_pattern_bits: 2, ..config };
+        assert!(!broad(b"eta", &config));
+        assert!(!broad(b"ee", &config));
+        assert!(!broad(b"eeaa", &config));
+
+        // Three errors leave "eeeaaa" no bit a record must hold
+        assert!(broad(b"eeeaaa", &config));
+        assert!(!broad(b"aeiouy", &config));
+        assert!(!broad(b"eeeaaa", &SearchConfig { max_errors: 2, ..config }));
+        assert!(!broad(b"eeeaaa", &SearchConfig { match_mode: MatchMode::Subsequence, ..config }));
+    }
+
+    #[test]
+    fn test_visit_rescored() {
+        let mut pool = [0u8; 64];
+        let records = build(["hello", "helo", "yellow"], &mut pool);
+        let searcher = BitapSearcher::new(b"hello");
+        let mut out = [ScoredResult::default(); 8];
+        let config = SearchConfig::default();
+        let mut search = RecordSearch::new(&records, &pool, &searcher, &config, &mut out);
+        for index in 0..records.len() {
+            // Favor the later records whatever their text score
+            search.visit_rescored(index, |_| true, |r| r.score as u32 / 10 + index as u32 * 2000);
+        }
+
+        assert_eq!(search.finish(), 2);
+        assert_eq!((out[0].id, out[0].score), (2, 1000));
+        assert_eq!(out[1].id, 1);
+    }
+
+    #[test]
+    fn test_min_score_gap() {
+        let mut pool = [0u8; 64];
+        let records = build(["reprt", "x reprt", "a long way reprt", "report"], &mut pool);
+        let searcher = BitapSearcher::new(b"report");
+        let mut out = [ScoredResult::default(); 8];
+
+        assert_eq!(search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out), 4);
+        // "reprt" scores 800, 775 and 750 by position
+        let config = SearchConfig { min_score_gap: 30, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 3);
+        assert_eq!((out[0].id, out[1].id, out[2].id), (4, 1, 3));
+        let config = SearchConfig { min_score_gap: 50, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 2);
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let mut pool = [0u8; 64];
+        let records = build(["helo", "xx hello", "hello"], &mut pool);
+        let searcher = BitapSearcher::new(b"hello");
+        let mut out = [ScoredResult::default(); 8];
+
+        let n = search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out);
+        assert_eq!(n, 3);
+        assert_eq!(out[2].id, 1);
+
+        let config = SearchConfig { sort: SortOrder::ByPosition, ..SearchConfig::default() };
+        let n = search_records(&records, &pool, &searcher, &config, &mut out);
+        assert_eq!(n, 3);
+        assert_eq!([out[0].id, out[1].id, out[2].id], [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sort_by_density() {
+        let mut pool = [0u8; 64];
+        let records = build(["keyboard mouse", "keyboard and keyboard", "keyboard"], &mut pool);
+        let searcher = BitapSearcher::new(b"keyboard");
+        let mut out = [ScoredResult::default(); 8];
+
+        let config = SearchConfig { max_errors: 0, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 3);
+        assert_eq!([out[0].hits, out[1].hits, out[2].hits], [1, 2, 1]);
+        // 1000 / 14, 2000 / 21, 1000 / 8
+        assert_eq!([out[0].density(), out[1].density(), out[2].density()], [71, 95, 125]);
+
+        let config = SearchConfig { sort: SortOrder::ByDensity, ..config };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 3);
+        assert_eq!([out[0].id, out[1].id, out[2].id], [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_phonetic_admits() {
+        let mut pool = [0u8; 64];
+        let records = build(["Catherine Zeta", "Kathleen", "Katharine"], &mut pool);
+        let searcher = BitapSearcher::new(b"Katherine");
+        let mut out = [ScoredResult::default(); 8];
+
+        let n = search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out);
+        assert_eq!(n, 1);
+        assert_eq!(out[0].id, 3);
+
+        let config = SearchConfig { phonetic: true, ..SearchConfig::default() };
+        let n = search_records(&records, &pool, &searcher, &config, &mut out);
+        assert_eq!(n, 2);
+        assert_eq!(out[1].id, 1);
+        assert_eq!(out[1].score, PHONETIC_SCORE);
+        assert_eq!(out[1].end, 9);
+    }
+
+    #[test]
+    fn test_bloom_disabled() {
+        let mut pool = [0u8; 64];
+        let mut records = build(["hello"], &mut pool);
+        records[0].bloom = 0;
+        let searcher = BitapSearcher::new(b"hello");
+        let mut out = [ScoredResult::default(); 8];
+
+        assert_eq!(search_records(&records, &pool, &searcher, &SearchConfig::default(), &mut out), 0);
+        let config = SearchConfig { bloom: false, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 1);
+    }
+
+    #[test]
+    fn test_bloom_allows_substitution() {
+        let mut pool = [0u8; 64];
+        let records = build(["abcdefgh"], &mut pool);
+        let searcher = BitapSearcher::new(b"abxdefgh");
+        let mut out = [ScoredResult::default(); 8];
+
+        // 'x' is absent from the record, but one substitution covers it
+        let config = SearchConfig { max_errors: 1, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 1);
+        assert_eq!(out[0].errors, 1);
+
+        let config = SearchConfig { max_errors: 0, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 0);
+    }
+
+    #[test]
+    fn test_insert_ranked_total_order() {
+        let result = |score, index| ScoredResult { score, index, ..ScoredResult::default() };
+        let mut out = [ScoredResult::default(); 3];
+
+        // Equal scores rank by index no matter the insertion order
+        let mut n = 0;
+        for r in [result(500, 4), result(900, 2), result(500, 1), result(500, 3)] {
+            n = insert_ranked(&mut out, n, 3, r);
+        }
+        assert_eq!(n, 3);
+        assert_eq!([out[0].index, out[1].index, out[2].index], [2, 1, 3]);
+
+        assert_eq!(insert_ranked(&mut out, 0, 0, result(1000, 0)), 0);
+    }
+
+    #[test]
+    fn test_error_ratio() {
+        let mut pool = [0u8; 64];
+        let records = build(["bard", "a board game for two players"], &mut pool);
+        let searcher = BitapSearcher::new(b"board");
+        let mut out = [ScoredResult::default(); 4];
+
+        // One deletion matches the short record too
+        let config = SearchConfig { max_errors: 1, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 2);
+
+        // floor(min(5, 4) * 0.2) = 0 errors allowed against "bard"
+        let config = SearchConfig { max_errors: 1, error_ratio: 0.2, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 1);
+        assert_eq!(out[0].id, 2);
+
+        // The scaled budget never exceeds max_errors
+        assert_eq!(config.errors_for(32, 40), 1);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut pool = [0u8; 64];
+        let records = build(["Keyboard\nwireless", "Mouse\nkeyboard sold separately"], &mut pool);
+        let searcher = BitapSearcher::new(b"keyboard");
+        let mut out = [ScoredResult::default(); 4];
+
+        let config = SearchConfig { max_errors: 0, ..SearchConfig::default() };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 2);
+
+        // Only the title of the second record falls in the window
+        let config = SearchConfig { window: 10, ..config };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 1);
+        assert_eq!((out[0].id, out[0].text_len), (1, 17));
+
+        // A match must end inside it
+        let config = SearchConfig { window: 7, ..config };
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 0);
+    }
+
+    #[test]
+    fn test_recency_weight() {
+        assert_eq!(apply_recency(800, 0, 4, 0), 800);
+        assert_eq!(apply_recency(800, 3, 4, 40), 800);
+        assert_eq!(apply_recency(800, 0, 4, 40), 770);
+    }
+
+    #[test]
+    fn test_diff_result_ids() {
+        let result = |id| ScoredResult::new(id, 500, 0, 0);
+        let previous = [result(1), result(2), result(3)];
+        let current = [result(3), result(4), result(1), result(5)];
+        let (mut added, mut removed) = ([0u32; 4], [0u32; 1]);
+        assert_eq!(diff_result_ids(&previous, &current, &mut added, &mut removed), (2, 1));
+        assert_eq!(added[..2], [4, 5]);
+        assert_eq!(removed, [2]);
+
+        // Truncated to the buffers
+        assert_eq!(diff_result_ids(&previous, &current, &mut added[..1], &mut []), (1, 0));
+    }
+
+    #[test]
+    fn test_explain_text() {
+        let searcher = BitapSearcher::new(b"board");
+        let config = SearchConfig::default();
+        let matcher = Matcher::new(&searcher, &config);
+        let b = matcher.explain_text(b"key bord").unwrap();
+        assert_eq!((b.base, b.position_bonus, b.penalty), (750, 25, 0));
+        assert_eq!(b.score, matcher.match_text(b"key bord").unwrap().score as u32);
+
+        // Bonuses past the cap count as penalty
+        let config = SearchConfig { match_mode: MatchMode::Subsequence, ..SearchConfig::default() };
+        let matcher = Matcher::new(&searcher, &config);
+        let b = matcher.explain_text(b"board").unwrap();
+        assert_eq!((b.base, b.position_bonus, b.boundary_bonus, b.penalty, b.score), (1000, 50, 30, 80, 1000));
+        assert_eq!(b.lowered_to(970).penalty, 110);
+    }
+
+    #[test]
+    fn test_word_prefix_mode() {
+        let mut pool = [0u8; 64];
+        let records = build(["Johnny Smithers", "John Smith", "Jo Mason", "Sam Jones"], &mut pool);
+        let searcher = BitapSearcher::new(b"sm.jo");
+        let config = SearchConfig { match_mode: MatchMode::WordPrefix, ..SearchConfig::default() };
+        let mut out = [ScoredResult::default(); 8];
+
+        // No record holds the query's '.', yet the prefilter passes them
+        assert_eq!(search_records(&records, &pool, &searcher, &config, &mut out), 2);
+        assert_eq!((out[0].id, out[0].start, out[0].end, out[0].errors), (2, 0, 7, 0));
+        assert_eq!(out[1].id, 1);
+    }
+
+    #[test]
+    fn test_closest_record() {
+        let mut pool = [0u8; 64];
+        let records = build(["freshly squeezed juice", "juice", "banana"], &mut pool);
+
+        // Three errors scores 250 at best, yet it's still the closest
+        let searcher = BitapSearcher::new(b"bxnxnx");
+        assert_eq!(closest_record(&records, &pool, &searcher, 8), Some(2));
+        assert_eq!(closest_record(&records, &pool, &searcher, 2), None);
+
+        // Equal errors: the match at the text start scores higher
+        let searcher = BitapSearcher::new(b"juicy");
+        assert_eq!(closest_record(&records, &pool, &searcher, 8), Some(1));
+    }
+}
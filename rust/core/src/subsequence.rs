@@ -0,0 +1,337 @@
+//! Subsequence matching for abbreviation-style queries
+//!
+//! Accepts a record when every pattern char appears in order, not
+//! necessarily adjacent ("gwb" matches "getWriteBuffer"). Matches that land
+//! on word starts inside identifiers earn a configurable bonus.
+
+use crate::bloom::BloomFilter;
+use crate::charset::CharSet;
+use crate::bitap;
+use crate::types::{ScoreBreakdown, SubsequenceMatch};
+use crate::{fold_byte, MAX_PATTERN_LEN};
+
+/// Score penalty per gap byte inside the matching window
+pub const GAP_PENALTY: u32 = 25;
+
+/// Maximum total gap penalty
+pub const MAX_GAP_PENALTY: u32 = 750;
+
+/// Bytes that separate words unless configured otherwise
+pub const DEFAULT_SEPARATORS: CharSet = CharSet::from_bytes(b"_-. \t");
+
+/// Subsequence searcher with a pre-folded pattern
+pub struct SubsequenceSearcher {
+    /// Lowercased pattern bytes
+    pattern: [u8; MAX_PATTERN_LEN],
+    /// Pattern length
+    pattern_len: usize,
+    /// Bloom filter for the pattern
+    pattern_bloom: BloomFilter,
+    /// Bytes after which a word starts
+    separators: CharSet,
+}
+
+impl SubsequenceSearcher {
+    /// Create a new subsequence searcher from a pattern
+    pub fn new(pattern: &[u8]) -> Self {
+        let len = pattern.len().min(MAX_PATTERN_LEN);
+        let mut folded = [0u8; MAX_PATTERN_LEN];
+        for (dst, &ch) in folded.iter_mut().zip(pattern.iter().take(len)) {
+            *dst = fold_byte(ch);
+        }
+
+        Self {
+            pattern: folded,
+            pattern_len: len,
+            pattern_bloom: BloomFilter::from_text(&pattern[..len]),
+            separators: DEFAULT_SEPARATORS,
+        }
+    }
+
+    /// Use `separators` instead of `DEFAULT_SEPARATORS` to find word starts
+    pub fn with_separators(mut self, separators: CharSet) -> Self {
+        self.separators = separators;
+        self
+    }
+
+    /// Get the pattern length
+    #[inline]
+    pub fn pattern_len(&self) -> usize {
+        self.pattern_len
+    }
+
+    /// Get the pattern's bloom filter
+    #[inline]
+    pub fn bloom(&self) -> BloomFilter {
+        self.pattern_bloom
+    }
+
+    /// Find the pattern as a subsequence of text
+    ///
+    /// A forward pass finds the earliest position where the whole pattern
+    /// has been seen, then a backward pass from there finds the latest
+    /// start, giving the tightest window ending at that position.
+    pub fn search(&self, text: &[u8]) -> Option<SubsequenceMatch> {
+        if self.pattern_len == 0 || text.is_empty() {
+            return None;
+        }
+
+        let pattern = &self.pattern[..self.pattern_len];
+
+        // Forward: earliest end
+        let mut pi = 0;
+        let mut end = 0;
+        for (pos, &ch) in text.iter().enumerate() {
+            if fold_byte(ch) == pattern[pi] {
+                pi += 1;
+                if pi == pattern.len() {
+                    end = pos + 1;
+                    break;
+                }
+            }
+        }
+        if pi < pattern.len() {
+            return None;
+        }
+
+        // Backward: latest start
+        let mut pi = pattern.len();
+        let mut start = end;
+        let mut boundary_hits = 0;
+        for pos in (0..end).rev() {
+            if fold_byte(text[pos]) == pattern[pi - 1] {
+                pi -= 1;
+                if is_word_start_with(text, pos, &self.separators) {
+                    boundary_hits += 1;
+                }
+                if pi == 0 {
+                    start = pos;
+                    break;
+                }
+            }
+        }
+
+        Some(SubsequenceMatch {
+            start,
+            end,
+            gaps: (end - start - pattern.len()) as u32,
+            boundary_hits,
+        })
+    }
+}
+
+impl SubsequenceSearcher {
+    /// Find the pattern as a subsequence starting at the first text byte
+    ///
+    /// The first pattern char must match `text[0]`; the rest are matched
+    /// greedily left to right.
+    pub fn search_anchored(&self, text: &[u8]) -> Option<SubsequenceMatch> {
+        if self.pattern_len == 0 || text.is_empty() {
+            return None;
+        }
+
+        let pattern = &self.pattern[..self.pattern_len];
+        if fold_byte(text[0]) != pattern[0] {
+            return None;
+        }
+
+        let mut pi = 0;
+        let mut boundary_hits = 0;
+        for (pos, &ch) in text.iter().enumerate() {
+            if fold_byte(ch) == pattern[pi] {
+                pi += 1;
+                if is_word_start_with(text, pos, &self.separators) {
+                    boundary_hits += 1;
+                }
+                if pi == pattern.len() {
+                    return Some(SubsequenceMatch {
+                        start: 0,
+                        end: pos + 1,
+                        gaps: (pos + 1 - pattern.len()) as u32,
+                        boundary_hits,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the pattern as a subsequence ending at the last text byte
+    ///
+    /// The last pattern char must match the last text byte; the rest are
+    /// matched greedily right to left.
+    pub fn search_anchored_end(&self, text: &[u8]) -> Option<SubsequenceMatch> {
+        if self.pattern_len == 0 || text.is_empty() {
+            return None;
+        }
+
+        let pattern = &self.pattern[..self.pattern_len];
+        let end = text.len();
+        if fold_byte(text[end - 1]) != pattern[pattern.len() - 1] {
+            return None;
+        }
+
+        let mut pi = pattern.len();
+        let mut boundary_hits = 0;
+        for pos in (0..end).rev() {
+            if fold_byte(text[pos]) == pattern[pi - 1] {
+                pi -= 1;
+                if is_word_start_with(text, pos, &self.separators) {
+                    boundary_hits += 1;
+                }
+                if pi == 0 {
+                    return Some(SubsequenceMatch {
+                        start: pos,
+                        end,
+                        gaps: (end - pos - pattern.len()) as u32,
+                        boundary_hits,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Check if a byte separates words in an identifier (default set)
+#[inline]
+pub fn is_separator(c: u8) -> bool {
+    DEFAULT_SEPARATORS.contains(c)
+}
+
+/// Check if the byte at `pos` starts a word, with the default separators
+#[inline]
+pub fn is_word_start(text: &[u8], pos: usize) -> bool {
+    is_word_start_with(text, pos, &DEFAULT_SEPARATORS)
+}
+
+/// Check if the byte at `pos` starts a word
+///
+/// A word starts at the beginning of the text, right after a separator,
+/// or at an uppercase letter following a lowercase one (camelCase hump).
+#[inline]
+pub fn is_word_start_with(text: &[u8], pos: usize, separators: &CharSet) -> bool {
+    if pos == 0 {
+        return true;
+    }
+    let prev = text[pos - 1];
+    separators.contains(prev) || (text[pos].is_ascii_uppercase() && prev.is_ascii_lowercase())
+}
+
+/// Compute score from a subsequence match
+///
+/// Score formula:
+/// - Base: 1000 - min(gaps * 25, 750)
+/// - Position bonus: +50 for start, +25 for near start
+/// - Boundary bonus: +boundary_bonus per char matched at a word start
+pub fn compute_score(m: &SubsequenceMatch, boundary_bonus: u16) -> u16 {
+    score_breakdown(m, boundary_bonus).score as u16
+}
+
+/// Components of `compute_score`
+pub fn score_breakdown(m: &SubsequenceMatch, boundary_bonus: u16) -> ScoreBreakdown {
+    let base = 1000 - (m.gaps * GAP_PENALTY).min(MAX_GAP_PENALTY);
+    ScoreBreakdown::new(base, bitap::position_bonus(m.start), m.boundary_hits * boundary_bonus as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abbreviation() {
+        let searcher = SubsequenceSearcher::new(b"gwb");
+        let m = searcher.search(b"getWriteBuffer").unwrap();
+
+        assert_eq!(m.start, 0);
+        assert_eq!(m.end, 9);
+        assert_eq!(m.gaps, 6);
+    }
+
+    #[test]
+    fn test_tightest_window() {
+        let searcher = SubsequenceSearcher::new(b"ab");
+        let m = searcher.search(b"a a ab").unwrap();
+
+        // Earliest end is 6; latest 'a' before it is at 4
+        assert_eq!(m.start, 4);
+        assert_eq!(m.gaps, 0);
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let searcher = SubsequenceSearcher::new(b"bwg");
+        assert!(searcher.search(b"getWriteBuffer").is_none());
+    }
+
+    #[test]
+    fn test_score_penalizes_gaps() {
+        let searcher = SubsequenceSearcher::new(b"gwb");
+        let tight = searcher.search(b"gwbuffer").unwrap();
+        let loose = searcher.search(b"getWriteBuffer").unwrap();
+
+        assert_eq!(compute_score(&tight, 0), 1000);
+        assert!(compute_score(&loose, 0) < compute_score(&tight, 0));
+    }
+
+    #[test]
+    fn test_anchored() {
+        let searcher = SubsequenceSearcher::new(b"wb");
+        assert!(searcher.search_anchored(b"getWriteBuffer").is_none());
+
+        let m = searcher.search_anchored(b"WriteBuffer").unwrap();
+        assert_eq!(m.start, 0);
+        assert_eq!(m.end, 6);
+        assert_eq!(m.boundary_hits, 2);
+    }
+
+    #[test]
+    fn test_anchored_end() {
+        let searcher = SubsequenceSearcher::new(b"wb");
+        assert!(searcher.search_anchored_end(b"WriteBufferX").is_none());
+
+        let m = searcher.search_anchored_end(b"getWriteB").unwrap();
+        assert_eq!((m.start, m.end), (3, 9));
+        assert_eq!(m.gaps, 4);
+    }
+
+    #[test]
+    fn test_word_start() {
+        let text = b"get_write.Buffer-x";
+        assert!(is_word_start(text, 0));
+        assert!(is_word_start(text, 4));
+        assert!(is_word_start(text, 10));
+        assert!(is_word_start(text, 17));
+        assert!(!is_word_start(text, 1));
+
+        let camel = b"getWriteBuffer";
+        assert!(is_word_start(camel, 3));
+        assert!(is_word_start(camel, 8));
+        assert!(!is_word_start(camel, 4));
+    }
+
+    #[test]
+    fn test_custom_separators() {
+        let text = b"std::io/read";
+        assert!(!is_word_start(text, 5));
+        assert!(is_word_start_with(text, 5, &CharSet::from_bytes(b":/")));
+        assert!(is_word_start_with(text, 8, &CharSet::from_bytes(b":/")));
+
+        let searcher = SubsequenceSearcher::new(b"ir");
+        let default = searcher.search(text).unwrap();
+        let custom = SubsequenceSearcher::new(b"ir").with_separators(CharSet::from_bytes(b":/")).search(text).unwrap();
+        assert_eq!(default.boundary_hits, 0);
+        assert_eq!(custom.boundary_hits, 2);
+    }
+
+    #[test]
+    fn test_boundary_bonus_ranking() {
+        let searcher = SubsequenceSearcher::new(b"gWB");
+        let good = searcher.search(b"getWriteBuffer").unwrap();
+        let bad = searcher.search(b"gadgetWidgetBar").unwrap();
+
+        assert_eq!(good.boundary_hits, 3);
+        assert_eq!(bad.boundary_hits, 2);
+        assert!(compute_score(&good, 30) > compute_score(&bad, 30));
+    }
+}
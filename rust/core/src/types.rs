@@ -1,15 +1,61 @@
 //! Core types for Flash-Fuzzy
 
+use crate::charset::CharSet;
+use crate::fold::Folding;
+use crate::subsequence::DEFAULT_SEPARATORS;
+
 /// Search result from the Bitap algorithm
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct SearchMatch {
     /// Number of errors (edit distance)
     pub errors: u32,
+    /// Start position of the aligned region in text
+    pub start_pos: usize,
     /// End position of match in text
     pub end_pos: usize,
 }
 
+/// Search result from the subsequence matcher
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SubsequenceMatch {
+    /// Start position of the tightest matching window
+    pub start: usize,
+    /// End position (exclusive) of the matching window
+    pub end: usize,
+    /// Number of unmatched text bytes inside the window
+    pub gaps: u32,
+    /// Number of pattern chars matched at a word start
+    pub boundary_hits: u32,
+}
+
+/// Search result from the word prefix matcher
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WordPrefixMatch {
+    /// Start of the earliest word a query token matched
+    pub start: usize,
+    /// End (exclusive) of the latest token prefix matched
+    pub end: usize,
+    /// Query token bytes matched, i.e. the pattern length without separators
+    pub covered: u32,
+    /// Total length of the record words the tokens matched
+    pub word_len: u32,
+}
+
+/// Match position stored in `ScoredResult`
+///
+/// `u32` with the `std` feature so long records (e.g. article bodies)
+/// keep exact positions; `u16` otherwise, keeping no_std result buffers
+/// small. Positions past the maximum saturate rather than wrap.
+#[cfg(feature = "std")]
+pub type MatchPos = u32;
+#[cfg(not(feature = "std"))]
+pub type MatchPos = u16;
+
 /// Scored search result
+///
+/// `repr(C)` so FFI hosts can read result buffers in place; the position
+/// fields are `MatchPos`, whose width depends on the `std` feature.
+#[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
 pub struct ScoredResult {
     /// Record ID
@@ -17,27 +63,247 @@ pub struct ScoredResult {
     /// Score (0-1000)
     pub score: u16,
     /// Start position of match
-    pub start: u16,
+    pub start: MatchPos,
     /// End position of match
-    pub end: u16,
+    pub end: MatchPos,
+    /// Edit distance of the match that produced the score
+    pub errors: u32,
+    /// Index of the matched record in the searched slice
+    pub index: u32,
+    /// Length of the text (record or alias) the match was found in
+    pub text_len: u32,
+    /// Match was found in the record's alias rather than its text
+    pub alias: bool,
+    /// Position in score order (0 = best), whatever the `SortOrder`
+    pub rank: u32,
+    /// Score relative to the top result's (0-1000)
+    pub relative: u16,
+    /// Non-overlapping occurrences of the pattern in the matched text
+    pub hits: u32,
 }
 
 impl ScoredResult {
-    /// Create a new scored result
-    pub fn new(id: u32, score: u16, start: u16, end: u16) -> Self {
-        Self { id, score, start, end }
+    /// Create a new scored result, saturating positions at `MatchPos::MAX`
+    pub fn new(id: u32, score: u16, start: usize, end: usize) -> Self {
+        let pos = |p: usize| p.min(MatchPos::MAX as usize) as MatchPos;
+        Self { id, score, start: pos(start), end: pos(end), errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }
+    }
+
+    /// Set the edit distance of the underlying match
+    pub fn with_errors(mut self, errors: u32) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Match start and end as `u32`, whatever the width of `MatchPos`
+    #[inline]
+    #[allow(clippy::unnecessary_cast)] // u32 to u32 with the std feature
+    pub fn byte_span(&self) -> (u32, u32) {
+        (self.start as u32, self.end as u32)
+    }
+
+    /// Share of the matched text covered by the match span (0-1000)
+    /// 0 if the text length is unknown.
+    pub fn coverage(&self) -> u32 {
+        if self.text_len == 0 {
+            return 0;
+        }
+        let span = u64::from(self.end.saturating_sub(self.start));
+        (span * 1000 / u64::from(self.text_len)).min(1000) as u32
+    }
+
+    /// Occurrences per 1000 bytes of matched text: `hits * 1000 / text_len`
+    /// 0 if the text length is unknown.
+    pub fn density(&self) -> u32 {
+        (u64::from(self.hits) * 1000).checked_div(u64::from(self.text_len)).unwrap_or(0) as u32
+    }
+
+    /// Match span as char offsets into `text`, the text it was found in
+    /// Counts UTF-8 lead bytes, so each multibyte char counts once.
+    pub fn char_span(&self, text: &[u8]) -> (u32, u32) {
+        let chars_before = |pos: MatchPos| {
+            let pos = (pos as usize).min(text.len());
+            text[..pos].iter().filter(|&&b| b & 0xC0 != 0x80).count() as u32
+        };
+        (chars_before(self.start), chars_before(self.end))
+    }
+}
+
+/// Components of a match score, as written by `ff_explain_result`
+///
+/// `score = base + position_bonus + boundary_bonus + exact_bonus - penalty`,
+/// where the penalty covers points lost to the 1000 cap and to recency.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+    /// 1000 less the cost of errors (bitap) or gaps (subsequence)
+    pub base: u32,
+    /// +50 for a match at the text start, +25 near it
+    pub position_bonus: u32,
+    /// Boundary bonus for pattern chars matched at word starts (subsequence)
+    pub boundary_bonus: u32,
+    /// Bonus for an exact match; scoring awards none yet, so always 0
+    pub exact_bonus: u32,
+    /// Points taken off the sum of the above
+    pub penalty: u32,
+    /// Final score (0-1000)
+    pub score: u32,
+}
+
+impl ScoreBreakdown {
+    /// Sum the components, capping the score at 1000
+    pub fn new(base: u32, position_bonus: u32, boundary_bonus: u32) -> Self {
+        let total = base + position_bonus + boundary_bonus;
+        let score = total.min(1000);
+        Self { base, position_bonus, boundary_bonus, exact_bonus: 0, penalty: total - score, score }
+    }
+
+    /// Lower the score to `score`, counting the difference as penalty
+    pub fn lowered_to(self, score: u32) -> Self {
+        let cut = self.score.saturating_sub(score);
+        Self { penalty: self.penalty + cut, score: self.score - cut, ..self }
+    }
+}
+
+/// Edits turning the pattern into the matched region, as written by
+/// `ff_get_result_edit_profile`
+///
+/// Counted from the pattern's side: an insertion is an extra text byte, a
+/// deletion a pattern char missing from the text. Scoring charges all
+/// three alike; the split lets a host weight them itself, e.g. treating
+/// extra typed chars as more deliberate than dropped ones.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EditProfile {
+    /// Text bytes with no pattern char (region longer than the pattern)
+    pub insertions: u32,
+    /// Pattern chars with no text byte (region shorter than the pattern)
+    pub deletions: u32,
+    /// Pattern chars matched to a different byte
+    pub substitutions: u32,
+}
+
+/// How a pattern is matched against record text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Contiguous match within an edit-distance budget (Bitap)
+    #[default]
+    Bitap,
+    /// All pattern chars appear in order, not necessarily adjacent
+    Subsequence,
+    /// Every pattern word prefixes a different text word, in any order
+    WordPrefix,
+}
+
+impl MatchMode {
+    /// Convert from the numeric mode used by the FFI/WASM exports
+    /// Unknown values fall back to `Bitap`
+    pub fn from_u32(mode: u32) -> Self {
+        match mode {
+            1 => MatchMode::Subsequence,
+            2 => MatchMode::WordPrefix,
+            _ => MatchMode::Bitap,
+        }
+    }
+}
+
+/// Where a match must sit within the record text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    /// Match anywhere in the text
+    #[default]
+    None,
+    /// Match must begin at the start of the text (prefix / autocomplete)
+    Start,
+    /// Match must finish at the end of the text (suffix, e.g. file extensions)
+    End,
+}
+
+impl Anchor {
+    /// Convert from the numeric anchor used by the FFI/WASM exports
+    /// Unknown values fall back to `None`
+    pub fn from_u32(anchor: u32) -> Self {
+        match anchor {
+            1 => Anchor::Start,
+            2 => Anchor::End,
+            _ => Anchor::None,
+        }
+    }
+}
+
+/// Order of the returned results
+///
+/// Results are always selected by score (the best `max_results` are kept);
+/// the order only changes how that final set is arranged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Highest score first
+    #[default]
+    ByScore,
+    /// Record insertion order, then match start (top to bottom)
+    ByPosition,
+    /// Ascending record ID
+    ById,
+    /// Highest score first, then densest matches (see `ScoredResult::density`)
+    ByDensity,
+}
+
+impl SortOrder {
+    /// Convert from the numeric order used by the FFI/WASM exports
+    /// Unknown values fall back to `ByScore`
+    pub fn from_u32(order: u32) -> Self {
+        match order {
+            1 => SortOrder::ByPosition,
+            2 => SortOrder::ById,
+            3 => SortOrder::ByDensity,
+            _ => SortOrder::ByScore,
+        }
     }
 }
 
 /// Configuration for the search engine
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct SearchConfig {
-    /// Maximum number of errors allowed (0-3)
+    /// Maximum number of errors allowed (0-MAX_ERRORS_CEIL)
     pub max_errors: u32,
     /// Minimum score threshold (0-1000)
     pub threshold: u16,
     /// Maximum number of results to return
     pub max_results: usize,
+    /// Matching algorithm
+    pub match_mode: MatchMode,
+    /// Score bonus per subsequence char matched at a word start (camelCase hump or after a separator)
+    pub boundary_bonus: u16,
+    /// Restrict where matches may occur
+    pub anchor: Anchor,
+    /// Drop results whose record text duplicates a higher-scoring result
+    pub dedup: bool,
+    /// Drop results whose matched text equals a better result's and whose score is within this of it; 0 keeps all
+    pub min_score_gap: u16,
+    /// Max score penalty (0-1000) for the oldest record, scaled down for newer ones; 0 disables it
+    pub recency_weight: u16,
+    /// Order of the returned results
+    pub sort: SortOrder,
+    /// Also admit records whose first word sounds like the pattern
+    pub phonetic: bool,
+    /// Skip records whose bloom filter lacks a pattern char
+    pub bloom: bool,
+    /// Errors allowed per matched byte; 0.0 always allows `max_errors`
+    pub error_ratio: f32,
+    /// Shortest bitap match span as a share of the pattern length; 0.0 accepts any
+    pub min_match_ratio: f32,
+    /// Fewest pattern bloom bits a text must hold after the error budget; see `Matcher::too_broad` (0 = off)
+    pub min_pattern_bits: u32,
+    /// Bytes after which a word starts, for the boundary bonus
+    pub separators: CharSet,
+    /// Only match within the first `window` bytes of each text; 0 matches anywhere
+    pub window: usize,
+    /// Derive the threshold from the pattern length (see `bitap::auto_threshold`), ignoring `threshold`
+    pub auto_threshold: bool,
+    /// Ignore ASCII case when matching
+    pub fold_case: bool,
+    /// Ignore accents on Latin letters when matching, so "naive" finds "naïve"
+    pub fold_diacritics: bool,
 }
 
 impl Default for SearchConfig {
@@ -46,6 +312,70 @@ impl Default for SearchConfig {
             max_errors: 2,
             threshold: 250,
             max_results: 50,
+            match_mode: MatchMode::Bitap,
+            boundary_bonus: 30,
+            anchor: Anchor::None,
+            dedup: false,
+            min_score_gap: 0,
+            recency_weight: 0,
+            sort: SortOrder::ByScore,
+            phonetic: false,
+            bloom: true,
+            error_ratio: 0.0,
+            min_match_ratio: 0.0,
+            min_pattern_bits: 0,
+            separators: DEFAULT_SEPARATORS,
+            window: 0,
+            auto_threshold: false,
+            fold_case: true,
+            fold_diacritics: false,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Error budget for matching the pattern against a text
+    ///
+    /// With `error_ratio` set, short texts get a tighter budget:
+    /// `floor(min(pattern_len, text_len) * error_ratio)`, capped at `max_errors`.
+    pub fn errors_for(&self, pattern_len: usize, text_len: usize) -> u32 {
+        if self.error_ratio <= 0.0 {
+            return self.max_errors;
+        }
+        let scaled = (pattern_len.min(text_len) as f32 * self.error_ratio) as u32;
+        scaled.min(self.max_errors)
+    }
+
+    /// Whether a match spanning `match_len` bytes is long enough for the pattern
+    /// Deletions let a long pattern match a much shorter region under a
+    /// generous error budget; `min_match_ratio` rules those out.
+    #[inline]
+    pub fn long_enough(&self, match_len: usize, pattern_len: usize) -> bool {
+        match_len as f32 >= pattern_len as f32 * self.min_match_ratio
+    }
+
+    /// Minimum score for matches of a `pattern_len`-byte pattern
+    #[inline]
+    pub fn threshold_for(&self, pattern_len: usize) -> u16 {
+        if self.auto_threshold {
+            crate::bitap::auto_threshold(pattern_len, self.max_errors)
+        } else {
+            self.threshold
+        }
+    }
+
+    /// What bitap matching ignores, for `BitapSearcher::with_folding`
+    #[inline]
+    pub fn folding(&self) -> Folding {
+        Folding { case: self.fold_case, diacritics: self.fold_diacritics }
+    }
+
+    /// The part of `text` matches may occur in, per `window`
+    #[inline]
+    pub fn windowed<'t>(&self, text: &'t [u8]) -> &'t [u8] {
+        match self.window {
+            0 => text,
+            window => &text[..window.min(text.len())],
         }
     }
 }
@@ -0,0 +1,75 @@
+//! Whitespace collapsing
+//!
+//! Extra spaces cost one error each, so "New   York" misses "new york"
+//! under a small error budget. With whitespace collapsing on, the bindings
+//! replace each run of ASCII whitespace with a single space in record text
+//! and patterns before indexing them. Match positions then refer to the
+//! collapsed text; `original_offset` maps them back onto the text as given.
+
+/// Collapse each run of ASCII whitespace in `buf` to one space, in place
+/// Returns the collapsed length; bytes past it are left as they were.
+pub fn collapse(buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    let mut in_run = false;
+    for i in 0..buf.len() {
+        let c = buf[i];
+        if c.is_ascii_whitespace() {
+            if in_run {
+                continue;
+            }
+            in_run = true;
+            buf[len] = b' ';
+        } else {
+            in_run = false;
+            buf[len] = c;
+        }
+        len += 1;
+    }
+    len
+}
+
+/// Map `pos` in the collapsed form of `original` to the offset in `original`
+///
+/// A position inside the collapsed text maps to the byte that produced it,
+/// so the first whitespace byte of a run stands for the whole run; the
+/// collapsed length maps to `original.len()`. Works for both match starts
+/// and (exclusive) ends.
+pub fn original_offset(original: &[u8], pos: usize) -> usize {
+    let mut collapsed = 0;
+    let mut in_run = false;
+    for (i, &c) in original.iter().enumerate() {
+        let run_continues = c.is_ascii_whitespace() && in_run;
+        in_run = c.is_ascii_whitespace();
+        if run_continues {
+            continue;
+        }
+        if collapsed == pos {
+            return i;
+        }
+        collapsed += 1;
+    }
+    original.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse() {
+        let mut buf = *b"New \t  York\n\ncity ";
+        let len = collapse(&mut buf);
+        assert_eq!(&buf[..len], b"New York city ");
+    }
+
+    #[test]
+    fn test_original_offset() {
+        let original = b"New   York";
+        // Collapsed "New York": "York" at 4..8
+        assert_eq!(original_offset(original, 0), 0);
+        assert_eq!(original_offset(original, 3), 3);
+        assert_eq!(original_offset(original, 4), 6);
+        assert_eq!(original_offset(original, 8), 10);
+        assert_eq!(&original[original_offset(original, 4)..original_offset(original, 8)], b"York");
+    }
+}
@@ -0,0 +1,232 @@
+//! Word prefix matching for contact-picker style queries
+//!
+//! Splits pattern and text into words on the separators and accepts a
+//! text when every pattern word is a prefix of a different text word, in
+//! any order: "sm jo" matches "John Smith". Matching ignores ASCII case.
+//! Scores reward covering more of the matched words, so "john sm" ranks
+//! "John Smith" above "Johnny Smithers".
+
+use crate::bitap;
+use crate::bloom::{BloomFilter, BloomHash};
+use crate::charset::CharSet;
+use crate::subsequence::DEFAULT_SEPARATORS;
+use crate::types::{ScoreBreakdown, WordPrefixMatch};
+use crate::{fold_byte, MAX_PATTERN_LEN};
+
+/// Most pattern words; later ones are ignored
+pub const MAX_TOKENS: usize = 16;
+
+/// Most text words considered; later ones never match
+pub const MAX_WORDS: usize = 64;
+
+/// Score of a match covering none of its words; full coverage scores 1000
+pub const MIN_COVERAGE_SCORE: u32 = 500;
+
+/// Word prefix searcher with a pre-split, pre-folded pattern
+pub struct WordPrefixSearcher {
+    /// Raw pattern bytes, kept to re-split with other separators
+    raw: [u8; MAX_PATTERN_LEN],
+    /// Raw pattern length
+    raw_len: usize,
+    /// Lowercased pattern words, back to back
+    tokens: [u8; MAX_PATTERN_LEN],
+    /// Length of the words in `tokens`
+    tokens_len: usize,
+    /// `(start, len)` of each word in `tokens`, longest first
+    spans: [(u8, u8); MAX_TOKENS],
+    /// Number of pattern words
+    token_count: usize,
+    /// Bytes that separate words
+    separators: CharSet,
+    /// Bloom filter over the pattern words, without separators
+    bloom: BloomFilter,
+    /// Exact set of the pattern words' chars
+    chars: CharSet,
+    /// Scheme `bloom` was built with
+    bloom_hash: BloomHash,
+}
+
+impl WordPrefixSearcher {
+    /// Create a new word prefix searcher from a pattern
+    pub fn new(pattern: &[u8]) -> Self {
+        let len = pattern.len().min(MAX_PATTERN_LEN);
+        let mut raw = [0u8; MAX_PATTERN_LEN];
+        raw[..len].copy_from_slice(&pattern[..len]);
+
+        let mut searcher = Self {
+            raw,
+            raw_len: len,
+            tokens: [0; MAX_PATTERN_LEN],
+            tokens_len: 0,
+            spans: [(0, 0); MAX_TOKENS],
+            token_count: 0,
+            separators: DEFAULT_SEPARATORS,
+            bloom: BloomFilter::new(),
+            chars: CharSet::EMPTY,
+            bloom_hash: BloomHash::default(),
+        };
+        searcher.split();
+        searcher
+    }
+
+    /// Split on `separators` instead of `DEFAULT_SEPARATORS`
+    pub fn with_separators(mut self, separators: CharSet) -> Self {
+        self.separators = separators;
+        self.split();
+        self
+    }
+
+    /// Rebuild the bloom with `hash`, to match records built with it
+    pub fn with_bloom_hash(mut self, hash: BloomHash) -> Self {
+        self.bloom_hash = hash;
+        self.bloom = BloomFilter::with_hash(self.text(), hash);
+        self
+    }
+
+    /// Fill `tokens` and `spans` from the raw pattern
+    fn split(&mut self) {
+        self.tokens_len = 0;
+        self.token_count = 0;
+        let separators = self.separators;
+        let raw = &self.raw[..self.raw_len];
+        for word in raw.split(|&c| separators.contains(c)).filter(|word| !word.is_empty()) {
+            if self.token_count == MAX_TOKENS {
+                break;
+            }
+            let start = self.tokens_len;
+            for (dst, &c) in self.tokens[start..].iter_mut().zip(word) {
+                *dst = fold_byte(c);
+            }
+            self.tokens_len += word.len();
+            self.spans[self.token_count] = (start as u8, word.len() as u8);
+            self.token_count += 1;
+        }
+        // Longest first: see `search`
+        self.spans[..self.token_count].sort_unstable_by_key(|&(_, len)| core::cmp::Reverse(len));
+        self.bloom = BloomFilter::with_hash(self.text(), self.bloom_hash);
+        self.chars = CharSet::from_text(self.text());
+    }
+
+    /// The pattern words, back to back and lowercased
+    #[inline]
+    pub fn text(&self) -> &[u8] {
+        &self.tokens[..self.tokens_len]
+    }
+
+    /// Number of pattern words
+    #[inline]
+    pub fn token_count(&self) -> usize {
+        self.token_count
+    }
+
+    /// Get the bloom filter of the pattern words
+    #[inline]
+    pub fn bloom(&self) -> BloomFilter {
+        self.bloom
+    }
+
+    /// Get the exact set of the pattern words' chars
+    #[inline]
+    pub fn chars(&self) -> CharSet {
+        self.chars
+    }
+
+    /// Match every pattern word to a different text word it prefixes
+    ///
+    /// The text words a pattern word prefixes are nested or disjoint for
+    /// any two pattern words (both prefix a text word only if one prefixes
+    /// the other), so giving the longest pattern words their pick first
+    /// never strands a shorter one that had a choice.
+    pub fn search(&self, text: &[u8]) -> Option<WordPrefixMatch> {
+        if self.token_count == 0 || text.is_empty() {
+            return None;
+        }
+
+        let mut words = [(0usize, 0usize); MAX_WORDS];
+        let mut word_count = 0;
+        let mut start = 0;
+        for (i, &c) in text.iter().enumerate() {
+            if self.separators.contains(c) {
+                if i > start && word_count < MAX_WORDS {
+                    words[word_count] = (start, i);
+                    word_count += 1;
+                }
+                start = i + 1;
+            }
+        }
+        if text.len() > start && word_count < MAX_WORDS {
+            words[word_count] = (start, text.len());
+            word_count += 1;
+        }
+
+        let mut used = 0u64;
+        let mut m = WordPrefixMatch { start: text.len(), ..WordPrefixMatch::default() };
+        for &(token_start, token_len) in &self.spans[..self.token_count] {
+            let token = &self.tokens[token_start as usize..][..token_len as usize];
+            let (i, &(word_start, word_end)) = words[..word_count].iter().enumerate().find(|&(i, &(word_start, word_end))| {
+                used & (1 << i) == 0
+                    && word_end - word_start >= token.len()
+                    && text[word_start..].iter().zip(token).all(|(&c, &t)| fold_byte(c) == t)
+            })?;
+            used |= 1 << i;
+            m.start = m.start.min(word_start);
+            m.end = m.end.max(word_start + token.len());
+            m.covered += token.len() as u32;
+            m.word_len += (word_end - word_start) as u32;
+        }
+        Some(m)
+    }
+}
+
+/// Compute score from a word prefix match
+///
+/// Score formula:
+/// - Base: 500 + 500 * covered / word_len, so whole words score 1000
+/// - Position bonus: +50 for start, +25 for near start
+pub fn compute_score(m: &WordPrefixMatch) -> u16 {
+    score_breakdown(m).score as u16
+}
+
+/// Components of `compute_score`
+pub fn score_breakdown(m: &WordPrefixMatch) -> ScoreBreakdown {
+    let coverage = ((1000 - MIN_COVERAGE_SCORE) * m.covered).checked_div(m.word_len).unwrap_or(0);
+    ScoreBreakdown::new(MIN_COVERAGE_SCORE + coverage, bitap::position_bonus(m.start), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_order() {
+        let searcher = WordPrefixSearcher::new(b"sm jo");
+        assert_eq!(searcher.token_count(), 2);
+        let m = searcher.search(b"John Smith").unwrap();
+        assert_eq!((m.start, m.end, m.covered, m.word_len), (0, 7, 4, 9));
+
+        assert!(searcher.search(b"John Doe").is_none());
+        assert!(searcher.search(b"Josmith").is_none());
+        // Each pattern word needs its own text word
+        assert!(WordPrefixSearcher::new(b"jo jo").search(b"John Smith").is_none());
+        assert!(WordPrefixSearcher::new(b"jo jo").search(b"John Jones").is_some());
+        // "jo" takes "Jo" before "j" can
+        assert!(WordPrefixSearcher::new(b"j jo").search(b"Jo Jx").is_some());
+    }
+
+    #[test]
+    fn test_coverage_ranks() {
+        let searcher = WordPrefixSearcher::new(b"john sm");
+        let close = compute_score(&searcher.search(b"John Smith").unwrap());
+        let far = compute_score(&searcher.search(b"Johnny Smithers").unwrap());
+        assert!(close > far);
+        assert_eq!(compute_score(&WordPrefixSearcher::new(b"smith john").search(b"John Smith").unwrap()), 1000);
+    }
+
+    #[test]
+    fn test_separators() {
+        let searcher = WordPrefixSearcher::new(b"io re").with_separators(CharSet::from_bytes(b":/ "));
+        assert_eq!(searcher.text(), b"iore");
+        assert!(searcher.search(b"std::io/read").is_some());
+        assert!(WordPrefixSearcher::new(b"io re").search(b"std::io/read").is_none());
+    }
+}
@@ -6,287 +6,2420 @@
 //! - Go (CGO)
 //! - Java/Kotlin/Android (JNI)
 //! - Any language with C FFI support
+//!
+//! # Lifecycle
+//!
+//! The engine is a single process-wide index. `ff_init` sets it up (and
+//! resets it at any time); `ff_destroy_all` tears it down and, in the
+//! `std` build, frees its heap buffers. After `ff_destroy_all`, pointers
+//! previously returned by the engine are invalid, adding records returns
+//! -7 and write buffers are null until the next `ff_init`. A C++ host can
+//! tie this to a static owner:
+//!
+//! ```cpp
+//! struct FlashFuzzyEngine {
+//!     FlashFuzzyEngine() { ff_init(); }
+//!     ~FlashFuzzyEngine() { ff_destroy_all(); }
+//!     FlashFuzzyEngine(const FlashFuzzyEngine&) = delete;
+//!     FlashFuzzyEngine& operator=(const FlashFuzzyEngine&) = delete;
+//! };
+//!
+//! static FlashFuzzyEngine engine;
+//! ```
 
-use flash_fuzzy_core::{bitap, bloom::{BloomFilter, to_lower}, BitapSearcher, ScoredResult};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use flash_fuzzy_core::{
+    bitap, bloom::{BloomFilter, BloomHash}, closest_record, common_prefix_len, compact_records, diff_result_ids, digest, phonetic, query,
+    subsequence::DEFAULT_SEPARATORS, whitespace,
+    search::{apply_recency, Matcher, RecordSearch, ResultSet},
+    Anchor, BitapSearcher, CharSet, EditProfile, Limits, MatchMode, PoolAllocator, PrefixIndex, RecordView, RecordsView, ScoreBreakdown, ScoredResult, SearchConfig,
+    SortOrder, FEATURES, FEATURE_STD, MAX_ERRORS_CEIL, MAX_PATTERN_LEN, VERSION,
+};
 
 // ============ Memory Constants ============
 
-const MAX_RECORDS: usize = 100_000;
-const MAX_RESULTS: usize = 100;
-const STRING_POOL_SIZE: usize = 4 * 1024 * 1024; // 4MB
-const SCRATCHPAD_SIZE: usize = 64 * 1024; // 64KB
-const MAX_PATTERN_LEN: usize = 32;
+const MAX_RECORDS: usize = Limits::MAX_RECORDS;
+const MAX_RESULTS: usize = Limits::MAX_RESULTS;
+const STRING_POOL_SIZE: usize = Limits::STRING_POOL_SIZE;
+const SCRATCHPAD_SIZE: usize = Limits::SCRATCHPAD_SIZE;
 
-// ============ Record Structure ============
+/// Records per block summarized by one bloom in BLOCK_BLOOMS
+const BLOOM_BLOCK: usize = 1024;
 
-#[derive(Clone, Copy)]
-struct Record {
-    id: u32,
-    text_start: u32,
-    text_len: u16,
-    bloom: u64,
-    active: bool,
-}
+/// Number of blocks in BLOCK_BLOOMS
+const BLOOM_BLOCKS: usize = blocks_for(MAX_RECORDS);
 
-impl Default for Record {
-    fn default() -> Self {
-        Self { id: 0, text_start: 0, text_len: 0, bloom: 0, active: false }
-    }
+/// Blocks in BLOCK_BLOOMS covering the first `records` slots
+const fn blocks_for(records: usize) -> usize {
+    (records + BLOOM_BLOCK - 1) / BLOOM_BLOCK
 }
 
+/// Maximum terms accepted by `ff_search_terms`
+const MAX_TERMS: usize = 8;
+
+/// Maximum patterns registered for `ff_search_multi`
+const MAX_MULTI_PATTERNS: usize = 8;
+
+/// Records a search visits between checks of the cancel flag
+const CANCEL_POLL: usize = 256;
+
+/// Stride `ff_warmup` touches memory at; smaller than most OS pages
+const WARMUP_STRIDE: usize = 4096;
+
+// ============ Explain Bits ============
+
+const EXPLAIN_BLOOM_PASSED: i32 = 1 << 0;
+const EXPLAIN_MATCHED: i32 = 1 << 1;
+const EXPLAIN_THRESHOLD_PASSED: i32 = 1 << 2;
+const EXPLAIN_ERRORS_SHIFT: u32 = 3;
+const EXPLAIN_SCORE_SHIFT: u32 = 8;
+const EXPLAIN_EXCLUDED: i32 = 1 << 18;
+
 // ============ Global State ============
 
-static mut RECORDS: [Record; MAX_RECORDS] = [Record { id: 0, text_start: 0, text_len: 0, bloom: 0, active: false }; MAX_RECORDS];
+static mut RECORDS: [RecordView; MAX_RECORDS] = [RecordView { id: 0, text_start: 0, text_len: 0, alias_start: 0, alias_len: 0, bloom: 0, chars: CharSet::EMPTY, added_seq: 0, phonetic: 0, active: false }; MAX_RECORDS];
 static mut RECORD_COUNT: usize = 0;
+// OR of the blooms of each BLOOM_BLOCK slots; removals leave bits set
+static mut BLOCK_BLOOMS: [u64; BLOOM_BLOCKS] = [0; BLOOM_BLOCKS];
+static mut ALIAS_COUNT: usize = 0;
+// Removed records still holding a slot, dropped by ff_compact
+static mut INACTIVE_COUNT: usize = 0;
+// Share of slots that may be inactive before add or search compacts; 1.0 never
+static mut AUTO_COMPACT_RATIO: f32 = 1.0;
+// Most slots in use at once since the last clear
+static mut RECORD_HIGH_WATER: usize = 0;
+// Sum of active record hashes, see `digest`
+static mut INDEX_DIGEST: u64 = digest::EMPTY_DIGEST;
 
 static mut STRING_POOL: [u8; STRING_POOL_SIZE] = [0; STRING_POOL_SIZE];
-static mut STRING_POOL_USED: usize = 0;
+static mut POOL_ALLOC: PoolAllocator = PoolAllocator::new(STRING_POOL_SIZE);
+// Host-owned text attached by ff_attach_corpus; records then point into it instead
+static mut CORPUS: Option<&'static [u8]> = None;
+static mut PREFIX_INDEX: PrefixIndex<MAX_RECORDS> = PrefixIndex::new();
 
 static mut PATTERN: [u8; MAX_PATTERN_LEN] = [0; MAX_PATTERN_LEN];
 static mut PATTERN_LEN: usize = 0;
-static mut CHAR_MASKS: [u32; 256] = [0; 256];
+// Error budget parsed from the prepared query's `~N` suffix
+static mut PATTERN_MAX_ERRORS: Option<u32> = None;
 static mut PATTERN_BLOOM: u64 = 0;
+// Searcher for PATTERN, kept so preparing the same query again skips the rebuild
+static mut SEARCHER: Option<BitapSearcher> = None;
+static mut EXCLUDE: [u8; MAX_PATTERN_LEN] = [0; MAX_PATTERN_LEN];
+static mut EXCLUDE_LEN: usize = 0;
 
 static mut MAX_ERRORS: u32 = 2;
 static mut THRESHOLD: u16 = 250;
+// Derive the threshold from the pattern length, see bitap::auto_threshold
+static mut AUTO_THRESHOLD: bool = false;
 static mut MAX_RESULTS_CFG: usize = 50;
+static mut MATCH_MODE: MatchMode = MatchMode::Bitap;
+static mut BOUNDARY_BONUS: u16 = 30;
+static mut SEPARATORS: CharSet = DEFAULT_SEPARATORS;
+static mut RECENCY_WEIGHT: u16 = 0;
+static mut ERROR_RATIO: f32 = 0.0;
+static mut MIN_MATCH_RATIO: f32 = 0.0;
+static mut NEXT_SEQ: u32 = 0;
+static mut ANCHOR: Anchor = Anchor::None;
+static mut DEDUP: bool = false;
+static mut MIN_SCORE_GAP: u16 = 0;
+static mut MIN_PATTERN_BITS: u32 = 0;
+static mut PHONETIC: bool = false;
+static mut COLLAPSE_WHITESPACE: bool = false;
+static mut QUERY_SYNTAX: bool = false;
+static mut BLOOM_ENABLED: bool = true;
+static mut BLOOM_HASH: BloomHash = BloomHash::LowBits;
+static mut FOLD_CASE: bool = true;
+static mut FOLD_DIACRITICS: bool = false;
+static mut UNIQUE_IDS: bool = false;
+// IDs of active records, tracked while UNIQUE_IDS is set
+#[cfg(feature = "std")]
+static mut ACTIVE_IDS: Option<std::collections::HashSet<u32>> = None;
+static mut SORT_ORDER: SortOrder = SortOrder::ByScore;
 
-static mut RESULTS: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0 }; MAX_RESULTS];
+#[cfg(not(feature = "std"))]
+static mut RESULTS: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS];
+// Resized by ff_set_result_capacity; starts at MAX_RESULTS
+#[cfg(feature = "std")]
+static mut RESULTS: Vec<ScoredResult> = Vec::new();
 static mut RESULT_COUNT: usize = 0;
 
+// Last search's results before the threshold, kept for ff_rethreshold
+#[cfg(not(feature = "std"))]
+static mut CANDIDATES: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS];
+#[cfg(feature = "std")]
+static mut CANDIDATES: Vec<ScoredResult> = Vec::new();
+static mut CANDIDATE_COUNT: usize = 0;
+// Config (threshold 0) CANDIDATES were found with; None when stale
+static mut CANDIDATE_CONFIG: Option<SearchConfig> = None;
+static mut CANDIDATE_CACHE: bool = false;
+
+// Results of the search before the last one, kept for ff_result_diff
+#[cfg(not(feature = "std"))]
+static mut PREVIOUS_RESULTS: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS];
+#[cfg(feature = "std")]
+static mut PREVIOUS_RESULTS: Vec<ScoredResult> = Vec::new();
+static mut PREVIOUS_COUNT: usize = 0;
+
+// Patterns registered by ff_add_multi_pattern, and each one's results from ff_search_multi
+// Repeats into the array below, since BitapSearcher isn't Copy
+const NO_PATTERN: Option<BitapSearcher> = None;
+static mut MULTI_PATTERNS: [Option<BitapSearcher>; MAX_MULTI_PATTERNS] = [NO_PATTERN; MAX_MULTI_PATTERNS];
+static mut MULTI_PATTERN_COUNT: usize = 0;
+static mut MULTI_RESULTS: [[ScoredResult; MAX_RESULTS]; MAX_MULTI_PATTERNS] = [[ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS]; MAX_MULTI_PATTERNS];
+static mut MULTI_RESULT_COUNTS: [usize; MAX_MULTI_PATTERNS] = [0; MAX_MULTI_PATTERNS];
+
+#[cfg(not(feature = "std"))]
 static mut SCRATCHPAD: [u8; SCRATCHPAD_SIZE] = [0; SCRATCHPAD_SIZE];
+// Grown on demand by ff_reserve_scratchpad; starts at SCRATCHPAD_SIZE
+#[cfg(feature = "std")]
+static mut SCRATCHPAD: Vec<u8> = Vec::new();
 static mut SCRATCHPAD_LEN: usize = 0;
+// Set while a host holds the write buffer, see ff_get_write_buffer
+static WRITE_BUSY: AtomicBool = AtomicBool::new(false);
+// Set by ff_destroy_all until the next ff_init
+static mut DESTROYED: bool = false;
+// Set by ff_cancel from any thread; cleared when a search starts
+static CANCEL: AtomicBool = AtomicBool::new(false);
+// Whether the last search stopped early on ff_cancel
+static mut LAST_CANCELLED: bool = false;
+// Whether the last search was refused by the min_pattern_bits guard
+static mut LAST_TOO_BROAD: bool = false;
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn scratchpad() -> &'static mut [u8] {
+    unsafe { &mut *core::ptr::addr_of_mut!(SCRATCHPAD) }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn scratchpad() -> &'static mut [u8] {
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(SCRATCHPAD) };
+    // Stays unallocated after ff_destroy_all
+    if buf.is_empty() && !destroyed() {
+        buf.resize(SCRATCHPAD_SIZE, 0);
+    }
+    buf.as_mut_slice()
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn active_ids() -> &'static mut std::collections::HashSet<u32> {
+    unsafe { (*core::ptr::addr_of_mut!(ACTIVE_IDS)).get_or_insert_with(Default::default) }
+}
+
+/// Check whether an active record already uses `id`
+#[cfg(feature = "std")]
+fn id_in_use(id: u32) -> bool {
+    active_ids().contains(&id)
+}
+
+#[cfg(not(feature = "std"))]
+fn id_in_use(id: u32) -> bool {
+    unsafe { RECORDS[..RECORD_COUNT].iter().any(|r| r.id == id && r.active) }
+}
+
+/// Record that `id` was added or removed
+#[cfg(feature = "std")]
+fn track_id(id: u32, active: bool) {
+    if unsafe { UNIQUE_IDS } {
+        if active {
+            active_ids().insert(id);
+        } else if !unsafe { RECORDS[..RECORD_COUNT].iter().any(|r| r.id == id && r.active) } {
+            // Duplicates from before unique IDs were enabled may remain
+            active_ids().remove(&id);
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn track_id(_id: u32, _active: bool) {}
+
+/// Rebuild the tracked ID set from the active records
+#[cfg(feature = "std")]
+fn rebuild_ids() {
+    let ids = active_ids();
+    ids.clear();
+    unsafe {
+        if UNIQUE_IDS {
+            ids.extend(RecordsView::new(&RECORDS[..RECORD_COUNT], string_pool()).iter().map(|(id, _)| id));
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn rebuild_ids() {}
+
+/// Bloom bits for a record text, or all bits while the prefilter is off
+/// Records added with the prefilter off pass it if it's re-enabled later.
+fn text_bloom(text: &[u8]) -> u64 {
+    if unsafe { BLOOM_ENABLED } {
+        BloomFilter::with_hash(text, unsafe { BLOOM_HASH }).bits()
+    } else {
+        BloomFilter::FULL.bits()
+    }
+}
+
+/// Searcher for `pattern` whose bloom uses the records' hash scheme
+/// and whose matching folds as configured
+#[inline]
+fn new_searcher(pattern: &[u8]) -> BitapSearcher {
+    BitapSearcher::with_folding(pattern, current_config().folding()).with_bloom_hash(unsafe { BLOOM_HASH })
+}
+
+/// Length of `buf` after collapsing its whitespace in place, if enabled
+#[inline]
+fn collapsed_len(buf: &mut [u8]) -> usize {
+    if unsafe { COLLAPSE_WHITESPACE } {
+        whitespace::collapse(buf)
+    } else {
+        buf.len()
+    }
+}
+
+/// Bytes record spans index into: the attached corpus, else STRING_POOL
+#[inline]
+fn string_pool() -> &'static [u8] {
+    unsafe {
+        match CORPUS {
+            Some(corpus) => corpus,
+            None => &*core::ptr::addr_of!(STRING_POOL),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn results() -> &'static mut [ScoredResult] {
+    unsafe { &mut *core::ptr::addr_of_mut!(RESULTS) }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn results() -> &'static mut [ScoredResult] {
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(RESULTS) };
+    // Stays unallocated after ff_destroy_all, and so do the buffers sized from it
+    if buf.is_empty() && !destroyed() {
+        buf.resize(MAX_RESULTS, ScoredResult::default());
+    }
+    buf.as_mut_slice()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn candidates() -> &'static mut [ScoredResult] {
+    unsafe { &mut *core::ptr::addr_of_mut!(CANDIDATES) }
+}
+
+/// Candidate buffer, kept the same size as the result buffer
+#[cfg(feature = "std")]
+#[inline]
+fn candidates() -> &'static mut [ScoredResult] {
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(CANDIDATES) };
+    let len = results().len();
+    if buf.len() != len {
+        buf.resize(len, ScoredResult::default());
+    }
+    buf.as_mut_slice()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn previous_results() -> &'static mut [ScoredResult] {
+    unsafe { &mut *core::ptr::addr_of_mut!(PREVIOUS_RESULTS) }
+}
+
+/// Previous result buffer, kept the same size as the result buffer
+#[cfg(feature = "std")]
+#[inline]
+fn previous_results() -> &'static mut [ScoredResult] {
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(PREVIOUS_RESULTS) };
+    let len = results().len();
+    if buf.len() != len {
+        buf.resize(len, ScoredResult::default());
+    }
+    buf.as_mut_slice()
+}
+
+/// Keep the current results as the previous search's before a search overwrites them
+fn remember_results() {
+    unsafe {
+        let count = RESULT_COUNT;
+        previous_results()[..count].copy_from_slice(&results()[..count]);
+        PREVIOUS_COUNT = count;
+    }
+}
+
+/// Drop the cached candidates after records or the pattern change
+#[inline]
+fn invalidate_candidates() {
+    unsafe {
+        CANDIDATE_CONFIG = None;
+    }
+}
+
+#[inline]
+fn pool_alloc() -> &'static mut PoolAllocator {
+    unsafe { &mut *core::ptr::addr_of_mut!(POOL_ALLOC) }
+}
+
+#[inline]
+fn prefix_index() -> &'static mut PrefixIndex<MAX_RECORDS> {
+    unsafe { &mut *core::ptr::addr_of_mut!(PREFIX_INDEX) }
+}
+
+/// Searcher for the prepared pattern, built on first use
+#[inline]
+fn prepared_searcher() -> &'static BitapSearcher {
+    unsafe {
+        (*core::ptr::addr_of_mut!(SEARCHER)).get_or_insert_with(|| new_searcher(&PATTERN[..PATTERN_LEN]))
+    }
+}
+
+// ============ FFI Exports ============
+
+/// Initialize the engine: clear all data and restore default configuration
+/// Use `ff_clear_data` to drop records while keeping the configuration
+#[no_mangle]
+pub extern "C" fn ff_init() {
+    ff_clear_data();
+    unsafe {
+        DESTROYED = false;
+        CORPUS = None;
+        MAX_ERRORS = 2;
+        THRESHOLD = 250;
+        AUTO_THRESHOLD = false;
+        MAX_RESULTS_CFG = 50;
+        MATCH_MODE = MatchMode::Bitap;
+        BOUNDARY_BONUS = 30;
+        SEPARATORS = DEFAULT_SEPARATORS;
+        RECENCY_WEIGHT = 0;
+        ERROR_RATIO = 0.0;
+        MIN_MATCH_RATIO = 0.0;
+        AUTO_COMPACT_RATIO = 1.0;
+        ANCHOR = Anchor::None;
+        DEDUP = false;
+        MIN_SCORE_GAP = 0;
+        MIN_PATTERN_BITS = 0;
+        LAST_TOO_BROAD = false;
+        PHONETIC = false;
+        COLLAPSE_WHITESPACE = false;
+        QUERY_SYNTAX = false;
+        BLOOM_ENABLED = true;
+        BLOOM_HASH = BloomHash::LowBits;
+        FOLD_CASE = true;
+        FOLD_DIACRITICS = false;
+        UNIQUE_IDS = false;
+        SORT_ORDER = SortOrder::ByScore;
+    }
+    rebuild_ids();
+}
+
+/// Tear down the engine, freeing its heap buffers (`std` build)
+/// Drops all records, patterns and results and detaches any corpus.
+/// Until the next `ff_init`, record adds return -7, write buffers and
+/// `ff_results_ptr` are null, sizes and counts are 0 and searches find
+/// nothing; no call allocates the buffers again. Safe to call more than once
+#[no_mangle]
+pub extern "C" fn ff_destroy_all() {
+    ff_init();
+    unsafe {
+        #[cfg(feature = "std")]
+        {
+            RESULTS = Vec::new();
+            CANDIDATES = Vec::new();
+            PREVIOUS_RESULTS = Vec::new();
+            SCRATCHPAD = Vec::new();
+            ACTIVE_IDS = None;
+        }
+        DESTROYED = true;
+    }
+}
+
+#[inline]
+fn destroyed() -> bool {
+    unsafe { DESTROYED }
+}
+
+/// Fault in the fixed arrays so the first search doesn't pay for it
+///
+/// The record table, string pool and prefix index are static arrays the
+/// OS maps lazily, so the first search over them takes page faults. This
+/// reads one byte per page of each (and of the scratchpad) and writes it
+/// back unchanged, making the pages resident. Contents are untouched, so
+/// it can be called at any time; it helps most right after `ff_init`.
+/// Returns the number of pages touched
+#[no_mangle]
+pub extern "C" fn ff_warmup() -> u32 {
+    if destroyed() {
+        return 0;
+    }
+    unsafe {
+        let records = core::ptr::addr_of_mut!(RECORDS);
+        let pool = core::ptr::addr_of_mut!(STRING_POOL);
+        let prefix = core::ptr::addr_of_mut!(PREFIX_INDEX);
+        let blocks = core::ptr::addr_of_mut!(BLOCK_BLOOMS);
+        let scratch = scratchpad();
+
+        touch_pages(records.cast(), core::mem::size_of_val(&*records))
+            + touch_pages(pool.cast(), STRING_POOL_SIZE)
+            + touch_pages(prefix.cast(), core::mem::size_of_val(&*prefix))
+            + touch_pages(blocks.cast(), core::mem::size_of_val(&*blocks))
+            + touch_pages(scratch.as_mut_ptr(), scratch.len())
+    }
+}
+
+/// Rewrite one byte every `WARMUP_STRIDE` of `len` bytes at `start`
+/// Bytes go through `MaybeUninit` since struct padding may be read.
+unsafe fn touch_pages(start: *mut u8, len: usize) -> u32 {
+    let start = start.cast::<core::mem::MaybeUninit<u8>>();
+    for offset in (0..len).step_by(WARMUP_STRIDE) {
+        let byte = start.add(offset);
+        byte.write_volatile(byte.read_volatile());
+    }
+    ((len + WARMUP_STRIDE - 1) / WARMUP_STRIDE) as u32
+}
+
+/// Write the engine's fixed capacities to `out`
+/// Lets hosts size buffers and validate input from the engine's own
+/// limits rather than hardcoded copies. Returns 0, or -1 if `out` is null
+///
+/// # Safety
+/// `out` must be null or valid for writes of a `Limits` (six u32s).
+#[no_mangle]
+pub unsafe extern "C" fn ff_get_limits(out: *mut Limits) -> i32 {
+    if out.is_null() {
+        return -1;
+    }
+    out.write(Limits::CURRENT);
+    0
+}
+
+/// Get the engine version packed as `major << 16 | minor << 8 | patch`
+#[no_mangle]
+pub extern "C" fn ff_version() -> u32 {
+    VERSION
+}
+
+/// Get build feature flags (see `FEATURE_*` in the core crate)
+/// Bits 16-23 hold the bloom width and bits 24-31 the max pattern length
+#[no_mangle]
+pub extern "C" fn ff_features() -> u32 {
+    let std_flag = if cfg!(feature = "std") { FEATURE_STD } else { 0 };
+    FEATURES | std_flag
+}
+
+/// Get pointer to write buffer
+/// The buffer is shared, so only one writer may hold it at a time: returns
+/// null while another write is in progress (until its `ff_commit_write`,
+/// `ff_append_write` or `ff_add_record`) or if `size` exceeds the
+/// scratchpad, and after `ff_destroy_all`.
+#[no_mangle]
+pub extern "C" fn ff_get_write_buffer(size: u32) -> *mut u8 {
+    if destroyed() {
+        return core::ptr::null_mut();
+    }
+    if WRITE_BUSY.swap(true, Ordering::Acquire) {
+        return core::ptr::null_mut();
+    }
+    let buf = scratchpad();
+    if size as usize > buf.len() {
+        release_write_buffer();
+        return core::ptr::null_mut();
+    }
+    buf.as_mut_ptr()
+}
+
+/// End the write in progress, letting `ff_get_write_buffer` hand out the buffer again
+#[inline]
+fn release_write_buffer() {
+    WRITE_BUSY.store(false, Ordering::Release);
+}
+
+/// Get scratchpad capacity in bytes (largest record or query per write)
+#[no_mangle]
+pub extern "C" fn ff_get_scratchpad_size() -> u32 {
+    if destroyed() {
+        return 0;
+    }
+    scratchpad().len() as u32
+}
+
+/// Grow the scratchpad to hold at least `size` bytes
+/// Returns: 1 if the scratchpad can now hold `size` bytes, 0 otherwise.
+/// Only the `std` build can grow; the fixed build is capped at 64KB.
+/// Growing may move the buffer, so this also returns 0 while a write is
+/// in progress.
+#[no_mangle]
+pub extern "C" fn ff_reserve_scratchpad(size: u32) -> i32 {
+    if destroyed() || WRITE_BUSY.swap(true, Ordering::Acquire) {
+        return 0;
+    }
+    let reserved = grow_scratchpad(size as usize);
+    release_write_buffer();
+    reserved as i32
+}
+
+/// Grow the scratchpad to at least `size` bytes if the build allows it
+/// The caller must hold the write flag, since the buffer may move.
+fn grow_scratchpad(size: usize) -> bool {
+    #[cfg(feature = "std")]
+    {
+        let buf = unsafe { &mut *core::ptr::addr_of_mut!(SCRATCHPAD) };
+        if buf.len() < size {
+            buf.resize(size, 0);
+        }
+    }
+    size <= scratchpad().len()
+}
+
+/// Commit written bytes to scratchpad
+#[no_mangle]
+pub extern "C" fn ff_commit_write(len: u32) {
+    unsafe {
+        let l = len as usize;
+        let cap = scratchpad().len();
+        SCRATCHPAD_LEN = if l > cap { cap } else { l };
+    }
+    release_write_buffer();
+}
+
+/// Get pointer to the end of the bytes already in the scratchpad
+/// Lets a large record be written in chunks: write up to `size` bytes here,
+/// then call `ff_append_write`. The `std` build grows the scratchpad as
+/// needed, which may move it, so fetch a fresh pointer for every chunk.
+/// Holds the write buffer like `ff_get_write_buffer` until `ff_append_write`.
+/// Returns null if `size` more bytes don't fit or another write is in
+/// progress, and after `ff_destroy_all`.
+#[no_mangle]
+pub extern "C" fn ff_get_append_buffer(size: u32) -> *mut u8 {
+    if destroyed() || WRITE_BUSY.swap(true, Ordering::Acquire) {
+        return core::ptr::null_mut();
+    }
+    let len = unsafe { SCRATCHPAD_LEN };
+    if !grow_scratchpad(len + size as usize) {
+        release_write_buffer();
+        return core::ptr::null_mut();
+    }
+    scratchpad()[len..].as_mut_ptr()
+}
+
+/// Append `len` bytes written at `ff_get_append_buffer` to the scratchpad
+#[no_mangle]
+pub extern "C" fn ff_append_write(len: u32) {
+    unsafe {
+        let cap = scratchpad().len();
+        SCRATCHPAD_LEN = (SCRATCHPAD_LEN + len as usize).min(cap);
+    }
+    release_write_buffer();
+}
+
+/// Add a record from scratchpad
+/// Returns: 1 on success, negative on error:
+/// -1 record table full, -2 empty text, -3 string pool full,
+/// -4 ID already used by an active record (only with `ff_set_unique_ids`),
+/// -5 a corpus is attached (use `ff_add_record_ref`),
+/// -7 the engine was destroyed (call `ff_init`)
+#[no_mangle]
+pub extern "C" fn ff_add_record(id: u32) -> i32 {
+    add_scratchpad_record(id, None)
+}
+
+/// Add a record from scratchpad with a precomputed bloom, skipping its computation
+/// For reloading records whose blooms were saved earlier: `bloom` must be
+/// built with the current hash scheme (`BloomFilter::with_hash` in the
+/// core crate). The bloom only pre-filters records, so a wrong one can
+/// make the record miss matches but never yields a false result. Returns
+/// as `ff_add_record`
+#[no_mangle]
+pub extern "C" fn ff_add_record_with_bloom(id: u32, bloom: u64) -> i32 {
+    add_scratchpad_record(id, Some(bloom))
+}
+
+/// Add the scratchpad as a record, with the given bloom or one computed from it
+fn add_scratchpad_record(id: u32, bloom: Option<u64>) -> i32 {
+    release_write_buffer();
+    unsafe {
+        SCRATCHPAD_LEN = collapsed_len(&mut scratchpad()[..SCRATCHPAD_LEN]);
+        let status = add_text_record(id, &scratchpad()[..SCRATCHPAD_LEN], bloom);
+        if status == 1 {
+            SCRATCHPAD_LEN = 0;
+        }
+        status
+    }
+}
+
+/// Copy `text` (already whitespace-collapsed) into the string pool as a new record
+/// Returns as `ff_add_record`.
+fn add_text_record(id: u32, text: &[u8], bloom: Option<u64>) -> i32 {
+    if destroyed() {
+        return -7;
+    }
+    auto_compact();
+    unsafe {
+        if RECORD_COUNT >= MAX_RECORDS {
+            return -1;
+        }
+        if text.is_empty() {
+            return -2;
+        }
+        if UNIQUE_IDS && id_in_use(id) {
+            return -4;
+        }
+        if corpus_attached() {
+            return -5;
+        }
+
+        let text_start = match pool_alloc().alloc(text.len()) {
+            Some(start) => start,
+            None => return -3,
+        };
+
+        // Copy text to string pool
+        STRING_POOL[text_start..text_start + text.len()].copy_from_slice(text);
+
+        push_record(id, text_start, text, bloom.unwrap_or_else(|| text_bloom(text)));
+
+        1
+    }
+}
+
+/// A record `ff_add_records` couldn't add, and the `ff_add_record` error code why
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FailedRecord {
+    pub id: u32,
+    pub reason: i32,
+}
+
+/// Add `count` records whose texts sit back to back in the scratchpad
+/// Record `i` has ID `ids[i]` and the next `lens[i]` bytes. A record that
+/// is empty (-2) or has a duplicate ID (-4) is skipped; any other error,
+/// such as a full string pool (-3), stops the batch, as does a length
+/// running past the scratchpad (-6). Each failure, including the one that
+/// stopped the batch, is written to `failed` as its ID and error code (up
+/// to `failed_cap` of them), and their number to `failed_count`. Clears
+/// the scratchpad. Returns the index the batch stopped at, or `count` if
+/// every record was attempted: retry the failures and continue from there
+/// once space is freed.
+///
+/// # Safety
+/// `ids` and `lens` must be valid for reads of `count` u32s; `failed` must
+/// be null or valid for writes of `failed_cap` entries, and `failed_count`
+/// null or valid for one u32 write.
+#[no_mangle]
+pub unsafe extern "C" fn ff_add_records(
+    ids: *const u32,
+    lens: *const u32,
+    count: u32,
+    failed: *mut FailedRecord,
+    failed_cap: u32,
+    failed_count: *mut u32,
+) -> i32 {
+    release_write_buffer();
+    let (ids, lens) = if ids.is_null() || lens.is_null() || count == 0 {
+        (&[][..], &[][..])
+    } else {
+        (core::slice::from_raw_parts(ids, count as usize), core::slice::from_raw_parts(lens, count as usize))
+    };
+    let failed = if failed.is_null() { &mut [][..] } else { core::slice::from_raw_parts_mut(failed, failed_cap as usize) };
+
+    let mut failures = 0;
+    let mut offset = 0;
+    let mut stopped = ids.len();
+    for (i, (&id, &len)) in ids.iter().zip(lens).enumerate() {
+        let end = offset + len as usize;
+        let status = if end > SCRATCHPAD_LEN {
+            -6
+        } else {
+            let text = &mut scratchpad()[offset..end];
+            let text_len = collapsed_len(text);
+            add_text_record(id, &text[..text_len], None)
+        };
+        offset = end;
+
+        if status != 1 {
+            if let Some(slot) = failed.get_mut(failures) {
+                *slot = FailedRecord { id, reason: status };
+            }
+            failures += 1;
+        }
+        if status != 1 && status != -2 && status != -4 {
+            stopped = i;
+            break;
+        }
+    }
+
+    SCRATCHPAD_LEN = 0;
+    if !failed_count.is_null() {
+        *failed_count = failures.min(failed.len()) as u32;
+    }
+    stopped as i32
+}
+
+/// Add each `delimiter`-separated segment of the `len` bytes at `ptr` as a record
+/// Segment `i` gets ID `base_id + i`, so with `\n` IDs follow line numbers
+/// from `base_id`; a `\r` before a `\n` delimiter is dropped. Empty
+/// segments (-2) and duplicate IDs (-4) are skipped but still use up their
+/// ID; any other error, such as a full string pool (-3), stops the load.
+/// While collapsing whitespace, segments are collapsed in the scratchpad,
+/// which is cleared, and longer ones are skipped. Returns the number of
+/// records added, or -5/-7 as `ff_add_record` before adding any.
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ff_add_delimited(ptr: *const u8, len: u32, delimiter: u8, base_id: u32) -> i32 {
+    if destroyed() {
+        return -7;
+    }
+    if corpus_attached() {
+        return -5;
+    }
+    release_write_buffer();
+
+    let mut added = 0;
+    for (i, segment) in raw_bytes(ptr, len).split(|&c| c == delimiter).enumerate() {
+        let segment = match segment {
+            [rest @ .., b'\r'] if delimiter == b'\n' => rest,
+            _ => segment,
+        };
+        let id = base_id.wrapping_add(i as u32);
+        let status = if COLLAPSE_WHITESPACE {
+            match scratchpad().get_mut(..segment.len()) {
+                Some(buf) => {
+                    buf.copy_from_slice(segment);
+                    let text_len = whitespace::collapse(buf);
+                    add_text_record(id, &buf[..text_len], None)
+                }
+                None => -6,
+            }
+        } else {
+            add_text_record(id, segment, None)
+        };
+
+        match status {
+            1 => added += 1,
+            -2 | -4 | -6 => {}
+            _ => break,
+        }
+    }
+
+    if COLLAPSE_WHITESPACE {
+        SCRATCHPAD_LEN = 0;
+    }
+    added
+}
+
+/// Attach a host-owned corpus for `ff_add_record_ref` records to point into
+/// Clears all records first. While attached, search reads record text from
+/// the corpus rather than the string pool, and `ff_add_record` and
+/// `ff_add_record_alias` return -5. A null pointer or zero length detaches
+/// it, as does `ff_init`.
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes, and the bytes must
+/// stay valid and unchanged until the corpus is detached or replaced.
+#[no_mangle]
+pub unsafe extern "C" fn ff_attach_corpus(ptr: *const u8, len: u32) {
+    ff_clear_data();
+    let corpus = raw_bytes(ptr, len);
+    CORPUS = if corpus.is_empty() { None } else { Some(corpus) };
+}
+
+/// Add a record whose text is `len` bytes at `offset` in the attached corpus
+/// The text isn't copied. Returns: 1 on success, negative on error as
+/// `ff_add_record`, with -5 if no corpus is attached and -6 if the span
+/// runs past its end
+#[no_mangle]
+pub extern "C" fn ff_add_record_ref(id: u32, offset: u32, len: u32) -> i32 {
+    if destroyed() {
+        return -7;
+    }
+    auto_compact();
+    unsafe {
+        let Some(corpus) = CORPUS else {
+            return -5;
+        };
+        if RECORD_COUNT >= MAX_RECORDS {
+            return -1;
+        }
+        if len == 0 {
+            return -2;
+        }
+        if UNIQUE_IDS && id_in_use(id) {
+            return -4;
+        }
+
+        let (start, end) = (offset as usize, offset as usize + len as usize);
+        if end > corpus.len() {
+            return -6;
+        }
+        push_record(id, start, &corpus[start..end], text_bloom(&corpus[start..end]));
+
+        1
+    }
+}
+
+#[inline]
+fn corpus_attached() -> bool {
+    unsafe { (*core::ptr::addr_of!(CORPUS)).is_some() }
+}
+
+/// Index `text`, found at `text_start` in `string_pool()`, as a new active record
+unsafe fn push_record(id: u32, text_start: usize, text: &[u8], bloom: u64) {
+    prefix_index().insert(RECORD_COUNT as u32, text);
+
+    BLOCK_BLOOMS[RECORD_COUNT / BLOOM_BLOCK] |= bloom;
+
+    RECORDS[RECORD_COUNT] = RecordView {
+        id,
+        text_start: text_start as u32,
+        text_len: text.len() as u32,
+        alias_start: 0,
+        alias_len: 0,
+        bloom,
+        chars: CharSet::from_text(text),
+        added_seq: NEXT_SEQ,
+        phonetic: phonetic::encode(text),
+        active: true,
+    };
+    RECORD_COUNT += 1;
+    RECORD_HIGH_WATER = RECORD_HIGH_WATER.max(RECORD_COUNT);
+    INDEX_DIGEST = digest::add_record(INDEX_DIGEST, id, text);
+    NEXT_SEQ = NEXT_SEQ.wrapping_add(1);
+    track_id(id, true);
+    invalidate_candidates();
+}
+
+/// Attach an alias (alternate text) from scratchpad to a record
+/// Search matches either text and keeps the higher score; result
+/// positions refer to whichever text scored best. Replaces any previous
+/// alias. Returns: 1 on success, 0 if no active record has this ID,
+/// negative on error (as `ff_add_record`)
+#[no_mangle]
+pub extern "C" fn ff_add_record_alias(id: u32) -> i32 {
+    if destroyed() {
+        return -7;
+    }
+    unsafe {
+        if SCRATCHPAD_LEN == 0 {
+            return -2;
+        }
+        if corpus_attached() {
+            return -5;
+        }
+
+        // Latest active record with this ID
+        let slot = match RECORDS[..RECORD_COUNT].iter().rposition(|r| r.id == id && r.active) {
+            Some(slot) => slot,
+            None => return 0,
+        };
+
+        SCRATCHPAD_LEN = collapsed_len(&mut scratchpad()[..SCRATCHPAD_LEN]);
+        let alias_len = SCRATCHPAD_LEN;
+        let alias_start = match pool_alloc().alloc(alias_len) {
+            Some(start) => start,
+            None => return -3,
+        };
+
+        let src = &scratchpad()[..alias_len];
+        STRING_POOL[alias_start..alias_start + alias_len].copy_from_slice(src);
+
+        let record = &mut RECORDS[slot];
+        if record.alias_len > 0 {
+            pool_alloc().free(record.alias_start as usize, record.alias_len as usize);
+        } else {
+            ALIAS_COUNT += 1;
+        }
+        record.alias_start = alias_start as u32;
+        record.alias_len = alias_len as u32;
+        // OR in the alias bloom and chars so the prefilters pass alias-only matches
+        record.bloom |= text_bloom(src);
+        BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+        record.chars = record.chars.union(CharSet::from_text(src));
+        SCRATCHPAD_LEN = 0;
+        invalidate_candidates();
+
+        1
+    }
+}
+
+/// Replace the text of the record with this ID by the scratchpad
+///
+/// If the (whitespace-collapsed) new text equals the current one nothing
+/// changes, so no bloom is recomputed. Otherwise the record is rewritten in
+/// its slot: it keeps its alias and insertion order and gets a fresh bloom.
+/// A bloom is an OR of bits and can't be updated from the old one, so the
+/// new text is always scanned in full. The slot is reused, so a full record
+/// table never fails an update. With duplicate IDs the latest active record
+/// is replaced. Returns: 1 on success, 0 if no active record has this ID,
+/// negative on error as `ff_add_record`; on error the record is unchanged
+#[no_mangle]
+pub extern "C" fn ff_update_record(id: u32) -> i32 {
+    release_write_buffer();
+    if destroyed() {
+        return -7;
+    }
+    unsafe {
+        if corpus_attached() {
+            return -5;
+        }
+        let Some(slot) = RECORDS[..RECORD_COUNT].iter().rposition(|r| r.id == id && r.active) else {
+            return 0;
+        };
+
+        SCRATCHPAD_LEN = collapsed_len(&mut scratchpad()[..SCRATCHPAD_LEN]);
+        if SCRATCHPAD_LEN == 0 {
+            return -2;
+        }
+        if *RECORDS[slot].text(string_pool()) == scratchpad()[..SCRATCHPAD_LEN] {
+            SCRATCHPAD_LEN = 0;
+            return 1;
+        }
+
+        // Copy the new text before touching the record so a full pool leaves it intact
+        let text_len = SCRATCHPAD_LEN;
+        let Some(text_start) = pool_alloc().alloc(text_len) else {
+            return -3;
+        };
+        STRING_POOL[text_start..text_start + text_len].copy_from_slice(&scratchpad()[..text_len]);
+        let text = &string_pool()[text_start..text_start + text_len];
+
+        let record = &mut RECORDS[slot];
+        let old = record.text(string_pool());
+        INDEX_DIGEST = digest::remove_record(INDEX_DIGEST, id, old);
+        INDEX_DIGEST = digest::add_record(INDEX_DIGEST, id, text);
+        prefix_index().relink(slot as u32, old, text);
+        pool_alloc().free(record.text_start as usize, record.text_len as usize);
+
+        record.text_start = text_start as u32;
+        record.text_len = text_len as u32;
+        record.bloom = text_bloom(text);
+        record.chars = CharSet::from_text(text);
+        if record.alias_len > 0 {
+            let alias = record.alias(string_pool());
+            record.bloom |= text_bloom(alias);
+            record.chars = record.chars.union(CharSet::from_text(alias));
+        }
+        record.phonetic = phonetic::encode(text);
+        BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+        SCRATCHPAD_LEN = 0;
+        invalidate_candidates();
+        1
+    }
+}
+
+/// Remove a record by ID
+/// With duplicate IDs the latest active record is removed, as
+/// `ff_update_record` and `ff_add_record_alias` pick it
+#[no_mangle]
+pub extern "C" fn ff_remove_record(id: u32) -> i32 {
+    unsafe {
+        match RECORDS[..RECORD_COUNT].iter().rposition(|r| r.id == id && r.active) {
+            Some(slot) => {
+                remove_slot(slot);
+                1
+            }
+            None => 0,
+        }
+    }
+}
+
+/// Deactivate the active record at `slot`, freeing its pool space
+unsafe fn remove_slot(slot: usize) {
+    let record = &mut RECORDS[slot];
+    record.active = false;
+    INACTIVE_COUNT += 1;
+    INDEX_DIGEST = digest::remove_record(INDEX_DIGEST, record.id, record.text(string_pool()));
+    // Corpus records never took pool space
+    if !corpus_attached() {
+        pool_alloc().free(record.text_start as usize, record.text_len as usize);
+        pool_alloc().free(record.alias_start as usize, record.alias_len as usize);
+    }
+    if record.alias_len > 0 {
+        ALIAS_COUNT -= 1;
+    }
+    track_id(record.id, false);
+    invalidate_candidates();
+}
+
+/// Set maximum errors allowed (0-MAX_ERRORS_CEIL)
+/// Larger values are clamped to `MAX_ERRORS_CEIL` (8); see it for the
+/// performance and scoring cost of large budgets
+#[no_mangle]
+pub extern "C" fn ff_set_max_errors(errors: u32) {
+    unsafe {
+        MAX_ERRORS = if errors > MAX_ERRORS_CEIL { MAX_ERRORS_CEIL } else { errors };
+    }
+}
+
+/// Get the maximum errors in effect, after `ff_set_max_errors` clamped it
+/// Bitap further caps patterns of 3 chars or fewer at 0 errors and of
+/// 4-5 chars at 1, and a query's inline `~N` budget overrides it
+#[no_mangle]
+pub extern "C" fn ff_get_max_errors() -> u32 {
+    unsafe { MAX_ERRORS }
+}
+
+/// Set minimum score threshold (0-1000), turning off the automatic threshold
+#[no_mangle]
+pub extern "C" fn ff_set_threshold(threshold: u32) {
+    unsafe {
+        THRESHOLD = threshold as u16;
+        AUTO_THRESHOLD = false;
+    }
+}
+
+/// Derive the threshold from the pattern length (nonzero = on, default off)
+/// A pattern of N bytes tolerates one error per 4 bytes, up to max errors:
+/// the threshold is `1000 - 250 * min(max_errors, N / 4)`, so 3-byte
+/// queries need an exact match. `ff_set_threshold` turns it off again
+#[no_mangle]
+pub extern "C" fn ff_set_auto_threshold(enabled: u32) {
+    unsafe {
+        AUTO_THRESHOLD = enabled != 0;
+    }
+}
+
+/// Set maximum results to return (capped at the result buffer capacity)
+#[no_mangle]
+pub extern "C" fn ff_set_max_results(max: u32) {
+    unsafe {
+        let m = max as usize;
+        let cap = results().len();
+        MAX_RESULTS_CFG = if m > cap { cap } else { m };
+    }
+}
+
+/// Resize the result buffer to hold `capacity` results
+/// Only the `std` build can resize; the fixed build stays at 100.
+/// Shrinking discards the current results and lowers max results to fit.
+/// Returns the resulting capacity
+#[no_mangle]
+pub extern "C" fn ff_set_result_capacity(capacity: u32) -> u32 {
+    if destroyed() {
+        return 0;
+    }
+    #[cfg(feature = "std")]
+    unsafe {
+        let buf = &mut *core::ptr::addr_of_mut!(RESULTS);
+        // An empty buffer means "not yet allocated", so keep at least one slot
+        buf.resize(capacity.max(1) as usize, ScoredResult::default());
+        if RESULT_COUNT > buf.len() {
+            RESULT_COUNT = 0;
+        }
+        MAX_RESULTS_CFG = MAX_RESULTS_CFG.min(buf.len());
+        invalidate_candidates();
+    }
+    #[cfg(not(feature = "std"))]
+    let _ = capacity;
+
+    results().len() as u32
+}
+
+/// Set matching mode (0 = bitap, 1 = subsequence, 2 = word prefix)
+/// Word prefix mode accepts a record when every query word prefixes a
+/// different record word, in any order ("sm jo" finds "John Smith"),
+/// splitting both on the `ff_set_separators` set; anchors don't apply
+#[no_mangle]
+pub extern "C" fn ff_set_match_mode(mode: u32) {
+    unsafe {
+        MATCH_MODE = MatchMode::from_u32(mode);
+    }
+}
+
+/// Set match anchor (0 = anywhere, 1 = start of text, 2 = end of text)
+/// Anchored exact queries (max_errors 0) use the prefix index instead of a full scan
+#[no_mangle]
+pub extern "C" fn ff_set_anchor(anchor: u32) {
+    unsafe {
+        ANCHOR = Anchor::from_u32(anchor);
+    }
+}
+
+/// Enable/disable result deduplication (nonzero = on)
+/// When on, a result whose record text is byte-identical to an already
+/// accepted, higher-scoring result is dropped
+#[no_mangle]
+pub extern "C" fn ff_set_dedup(enabled: u32) {
+    unsafe {
+        DEDUP = enabled != 0;
+    }
+}
+
+/// Drop near-duplicate results (0 = off, the default)
+/// A result whose matched bytes equal those of an accepted result scoring
+/// within `gap` of it is dropped, keeping the higher score. Lighter than
+/// `ff_set_dedup`: only the matched span has to agree, so "report.pdf"
+/// and "report.txt" collapse for "report" when their scores are close
+#[no_mangle]
+pub extern "C" fn ff_set_min_score_gap(gap: u32) {
+    unsafe {
+        MIN_SCORE_GAP = gap.min(1000) as u16;
+    }
+}
+
+/// Set the fewest pattern bloom bits a match must share with its record (0 = off, default)
+/// Patterns whose error budget is at least half their length match
+/// nearly every record; when fewer than `bits` pattern bits remain after
+/// the budget, searches return no results and `ff_search_too_broad`
+/// reports 1 instead of scanning. Bitap searches patterns of up to 5
+/// chars with at most 1 error, so they are never refused; 2 is a good value
+#[no_mangle]
+pub extern "C" fn ff_set_min_pattern_bits(bits: u32) {
+    unsafe {
+        MIN_PATTERN_BITS = bits;
+    }
+}
+
+/// Reject adds that reuse an active record's ID (0 = off, 1 = on)
+/// When off (the default), duplicate IDs are appended as separate records:
+/// both can appear in results and `ff_remove_record` removes the oldest.
+/// Enabling doesn't remove duplicates already present. The `std` build
+/// checks IDs with a hash set; otherwise each add scans the records.
+#[no_mangle]
+pub extern "C" fn ff_set_unique_ids(enabled: u32) {
+    unsafe {
+        UNIQUE_IDS = enabled != 0;
+    }
+    rebuild_ids();
+}
+
+/// Enable the bloom prefilter (0 = off, 1 = on, the default)
+/// Off skips the check at search time and the bloom build at add time;
+/// useful for small indexes or very fuzzy queries, where the filter can
+/// reject records bitap would match within the error budget
+#[no_mangle]
+pub extern "C" fn ff_set_bloom_enabled(enabled: u32) {
+    unsafe {
+        BLOOM_ENABLED = enabled != 0;
+    }
+}
+
+/// Choose how bytes map to bloom bits (0 = low bits, the default;
+/// 1 = multiplicative; 2 = nibble fold; unknown values use 0)
+/// Low bits gives each letter its own bit but shares them with digits
+/// and punctuation, so alphanumeric data passes more records on to
+/// bitap; the other schemes spread those apart. Rebuilds every record's
+/// bloom and the prepared and multi-pattern searchers
+#[no_mangle]
+pub extern "C" fn ff_set_bloom_hash(scheme: u32) {
+    unsafe {
+        let hash = BloomHash::from_u32(scheme);
+        if hash == BLOOM_HASH {
+            return;
+        }
+        BLOOM_HASH = hash;
+
+        let pool = string_pool();
+        BLOCK_BLOOMS = [0; BLOOM_BLOCKS];
+        for (slot, record) in RECORDS[..RECORD_COUNT].iter_mut().enumerate() {
+            record.bloom = text_bloom(record.text(pool));
+            if record.alias_len > 0 {
+                record.bloom |= text_bloom(record.alias(pool));
+            }
+            if record.active {
+                BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+            }
+        }
+
+        rebuild_searchers();
+    }
+}
+
+/// Rebuild the prepared and multi-pattern searchers for new settings
+unsafe fn rebuild_searchers() {
+    SEARCHER = None;
+    if PATTERN_LEN > 0 {
+        PATTERN_BLOOM = prepared_searcher().bloom().bits();
+    }
+    for searcher in (*core::ptr::addr_of_mut!(MULTI_PATTERNS)).iter_mut().flatten() {
+        *searcher = new_searcher(searcher.pattern());
+    }
+    invalidate_candidates();
+}
+
+/// Ignore ASCII case when matching (0 = off, 1 = on, the default)
+/// Independent of `ff_set_fold_diacritics`: with case folding off and
+/// diacritic folding on, "naive" matches "naïve" but not "NAÏVE".
+/// Rebuilds the prepared and multi-pattern searchers
+#[no_mangle]
+pub extern "C" fn ff_set_fold_case(enabled: u32) {
+    unsafe {
+        if FOLD_CASE != (enabled != 0) {
+            FOLD_CASE = enabled != 0;
+            rebuild_searchers();
+        }
+    }
+}
+
+/// Ignore accents on Latin letters when matching (0 = off, the default; 1 = on)
+/// Each two-byte UTF-8 letter in U+00C0..U+017F matches its unaccented
+/// base and counts as one char, so "naive" matches "naïve" exactly.
+/// Result positions still refer to the original bytes. Applies to bitap
+/// matching only; exact prefix queries lose the prefix index fast path.
+/// Rebuilds the prepared and multi-pattern searchers; multi-patterns
+/// added while on stay stripped if it's turned off, so re-add those
+#[no_mangle]
+pub extern "C" fn ff_set_fold_diacritics(enabled: u32) {
+    unsafe {
+        if FOLD_DIACRITICS != (enabled != 0) {
+            FOLD_DIACRITICS = enabled != 0;
+            rebuild_searchers();
+        }
+    }
+}
+
+/// Enable inline error budgets in queries (0 = off, 1 = on)
+/// While on, `ff_prepare_pattern` strips a trailing `~N` from the query and
+/// searches it with at most N errors (clamped to `MAX_ERRORS_CEIL`); the
+/// `ff_set_max_errors` value is left intact for later queries. Write `\~N`
+/// to search for a literal `~N` suffix. While off, `~` is always literal
+#[no_mangle]
+pub extern "C" fn ff_set_query_syntax(enabled: u32) {
+    unsafe {
+        QUERY_SYNTAX = enabled != 0;
+    }
+}
+
+/// Enable phonetic matching (0 = off, 1 = on)
+/// Records whose first word sounds like the pattern are returned even if
+/// the fuzzy match fails, scored at `phonetic::PHONETIC_SCORE`
+#[no_mangle]
+pub extern "C" fn ff_set_phonetic(enabled: u32) {
+    unsafe {
+        PHONETIC = enabled != 0;
+    }
+}
+
+/// Collapse whitespace runs to one space before matching (0 = off, 1 = on)
+/// Applies to records added (text and alias) and patterns prepared while
+/// on, so "New   York" matches "new york" exactly; records already added
+/// keep their text, as do `ff_add_record_ref` records, whose corpus isn't
+/// copied. Result positions refer to the collapsed text; map them onto
+/// the text as given with `ff_original_offset`
+#[no_mangle]
+pub extern "C" fn ff_set_collapse_whitespace(enabled: u32) {
+    unsafe {
+        COLLAPSE_WHITESPACE = enabled != 0;
+    }
+}
+
+/// Map `pos` in the whitespace-collapsed form of a text to its offset in
+/// the text as given (`ptr`, `len`)
+/// Positions inside the collapsed text map to the byte that produced them,
+/// a run's first whitespace byte standing for the run; the collapsed
+/// length maps to `len`. Use on result start and end positions of records
+/// added with `ff_set_collapse_whitespace` on.
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ff_original_offset(ptr: *const u8, len: u32, pos: u32) -> u32 {
+    whitespace::original_offset(raw_bytes(ptr, len), pos as usize) as u32
+}
+
+/// Set result order: 0 = by score, 1 = by position, 2 = by ID,
+/// 3 = by score then match density (see `ff_get_result_density`)
+/// The best `max_results` are still chosen by score
+#[no_mangle]
+pub extern "C" fn ff_set_sort_order(order: u32) {
+    unsafe {
+        SORT_ORDER = SortOrder::from_u32(order);
+    }
+}
+
+/// Set score bonus per subsequence char matched at a word start
+#[no_mangle]
+pub extern "C" fn ff_set_boundary_bonus(bonus: u32) {
+    unsafe {
+        BOUNDARY_BONUS = if bonus > 1000 { 1000 } else { bonus as u16 };
+    }
+}
+
+/// Set the bytes after which a word starts, for the boundary bonus
+/// Replaces the default set (`_`, `-`, `.`, space, tab) with exactly the
+/// given bytes, e.g. `:/` for paths and `::`-qualified names. An empty set
+/// leaves only camelCase humps and the text start as word starts;
+/// `ff_init` restores the default.
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ff_set_separators(ptr: *const u8, len: u32) {
+    SEPARATORS = CharSet::from_bytes(raw_bytes(ptr, len));
+}
+
+/// Set how much recency affects score (0-1000)
+/// The oldest record loses up to this many points relative to the newest;
+/// 0 leaves scores untouched
+#[no_mangle]
+pub extern "C" fn ff_set_recency_weight(weight: u32) {
+    unsafe {
+        RECENCY_WEIGHT = if weight > 1000 { 1000 } else { weight as u16 };
+    }
+}
+
+/// Scale the error budget with match length (0 = off)
+/// Each search allows `floor(min(pattern_len, text_len) * ratio)` errors,
+/// capped at the configured max errors, so short records aren't matched
+/// by nearly anything. Negative or NaN ratios disable scaling
+#[no_mangle]
+pub extern "C" fn ff_set_error_ratio(ratio: f32) {
+    unsafe {
+        ERROR_RATIO = if ratio > 0.0 { ratio } else { 0.0 };
+    }
+}
+
+/// Reject matches spanning fewer than `ratio * pattern_len` bytes (0 = off)
+/// With a generous error budget, deletions let a long pattern match a
+/// much shorter region: "keyboards" matches "keybo" at 4 errors. A record
+/// whose best match is too short doesn't match. Subsequence and word
+/// prefix matches are unaffected. Negative or NaN ratios disable it
+#[no_mangle]
+pub extern "C" fn ff_set_min_match_ratio(ratio: f32) {
+    unsafe {
+        MIN_MATCH_RATIO = if ratio > 0.0 { ratio } else { 0.0 };
+    }
+}
+
+/// Compact automatically once removed records exceed `ratio` of the
+/// record slots (0.0-1.0, default 1.0 = never)
+/// Checked at the start of `ff_add_record`, `ff_add_record_ref` and each
+/// search, so tombstones stop costing every scan; see `ff_compact`.
+/// Out-of-range values clamp, NaN disables it
+#[no_mangle]
+pub extern "C" fn ff_set_auto_compact_ratio(ratio: f32) {
+    unsafe {
+        AUTO_COMPACT_RATIO = if ratio.is_nan() { 1.0 } else { ratio.clamp(0.0, 1.0) };
+    }
+}
+
+/// Get current record count
+#[no_mangle]
+pub extern "C" fn ff_get_record_count() -> u32 {
+    unsafe { RECORD_COUNT as u32 }
+}
+
+/// Write the IDs of all active records to `out`, in insertion order
+/// Removed records are skipped. Returns the number written, or minus the
+/// number of active records (writing nothing) if `cap` can't hold them all
+///
+/// # Safety
+/// `out` must be null or valid for writes of `cap` u32s.
+#[no_mangle]
+pub unsafe extern "C" fn ff_get_record_ids(out: *mut u32, cap: u32) -> i32 {
+    let count = RECORD_COUNT - INACTIVE_COUNT;
+    let out = raw_ids_mut(out, cap);
+    if count > out.len() {
+        return -(count as i32);
+    }
+    let records = RecordsView::new(&RECORDS[..RECORD_COUNT], string_pool());
+    for (dst, (id, _)) in out.iter_mut().zip(records) {
+        *dst = id;
+    }
+    count as i32
+}
+
+/// Get maximum number of record slots
+#[no_mangle]
+pub extern "C" fn ff_get_record_capacity() -> u32 {
+    MAX_RECORDS as u32
+}
+
+/// Get number of record slots still free
+#[no_mangle]
+pub extern "C" fn ff_get_records_remaining() -> u32 {
+    unsafe { (MAX_RECORDS - RECORD_COUNT) as u32 }
+}
+
+/// Get the stored text length of a record, for sizing a copy buffer
+/// Returns -1 if no active record has this ID
+#[no_mangle]
+pub extern "C" fn ff_get_record_len(id: u32) -> i32 {
+    unsafe {
+        match RECORDS[..RECORD_COUNT].iter().rfind(|r| r.id == id && r.active) {
+            Some(record) => record.text_len as i32,
+            None => -1,
+        }
+    }
+}
+
+/// Prepare pattern from scratchpad
+/// With query syntax on, a trailing `~N` sets this pattern's error budget.
+/// Returns 1 if the pattern is byte-identical to the previous one and its
+/// searcher was reused, 0 if it was rebuilt
+#[no_mangle]
+pub extern "C" fn ff_prepare_pattern() -> u32 {
+    unsafe {
+        let cached = if QUERY_SYNTAX {
+            let mut pattern = [0u8; MAX_PATTERN_LEN];
+            let (len, budget) = query::parse_error_budget(&scratchpad()[..SCRATCHPAD_LEN], &mut pattern);
+            let cached = set_pattern(&pattern[..len]);
+            PATTERN_MAX_ERRORS = budget.map(|n| n.min(MAX_ERRORS_CEIL));
+            cached
+        } else {
+            let len = if SCRATCHPAD_LEN > MAX_PATTERN_LEN { MAX_PATTERN_LEN } else { SCRATCHPAD_LEN };
+            set_pattern(&scratchpad()[..len])
+        };
+        EXCLUDE_LEN = 0;
+        SCRATCHPAD_LEN = 0;
+        cached as u32
+    }
+}
+
+/// Copy in a search pattern, rebuilding its searcher and bloom filter
+/// unless the cached searcher was built for the same bytes
+/// Returns true if the cached searcher was kept
+unsafe fn set_pattern(src: &[u8]) -> bool {
+    invalidate_candidates();
+    PATTERN[..src.len()].copy_from_slice(src);
+    PATTERN_LEN = collapsed_len(&mut PATTERN[..src.len()]);
+    PATTERN_MAX_ERRORS = None;
+    let src = &PATTERN[..PATTERN_LEN];
+
+    if (*core::ptr::addr_of!(SEARCHER)).as_ref().is_some_and(|s| s.pattern() == src) {
+        return true;
+    }
+    match &mut *core::ptr::addr_of_mut!(SEARCHER) {
+        // Rebuild the previous query's searcher in place
+        Some(searcher) => searcher.reset(src),
+        slot => *slot = Some(new_searcher(src)),
+    }
+    PATTERN_BLOOM = prepared_searcher().bloom().bits();
+    false
+}
+
+/// Get the bloom bits of the prepared pattern (0 if none is prepared)
+/// A record or shard whose bloom `b` lacks any of these bits, i.e.
+/// `b & pattern != pattern`, can't contain an exact match
+#[no_mangle]
+pub extern "C" fn ff_get_pattern_bloom() -> u64 {
+    unsafe { if PATTERN_LEN > 0 { PATTERN_BLOOM } else { 0 } }
+}
+
+/// Get the OR of all active record blooms
+/// Lets a router skip this index when `ff_get_pattern_bloom` isn't contained
+#[no_mangle]
+pub extern "C" fn ff_get_index_bloom() -> u64 {
+    let records = unsafe { &RECORDS[..RECORD_COUNT] };
+    RecordsView::new(records, string_pool()).slots().fold(0, |bits, (_, record)| bits | record.bloom)
+}
+
+/// Get an order-independent digest of the active records' IDs and texts
+/// Indexes holding the same records give the same digest whatever order
+/// they were added in; aliases and configuration aren't included.
+/// Kept up to date on add and remove, so this is O(1)
+#[no_mangle]
+pub extern "C" fn ff_index_digest() -> u64 {
+    unsafe { INDEX_DIGEST }
+}
+
+// ============ Search ============
+
+/// Snapshot the global settings as a core `SearchConfig`
+fn current_config() -> SearchConfig {
+    unsafe {
+        SearchConfig {
+            max_errors: MAX_ERRORS,
+            threshold: THRESHOLD,
+            max_results: MAX_RESULTS_CFG,
+            match_mode: MATCH_MODE,
+            boundary_bonus: BOUNDARY_BONUS,
+            anchor: ANCHOR,
+            dedup: DEDUP,
+            min_score_gap: MIN_SCORE_GAP,
+            recency_weight: RECENCY_WEIGHT,
+            sort: SORT_ORDER,
+            phonetic: PHONETIC,
+            bloom: BLOOM_ENABLED,
+            error_ratio: ERROR_RATIO,
+            min_match_ratio: MIN_MATCH_RATIO,
+            min_pattern_bits: MIN_PATTERN_BITS,
+            separators: SEPARATORS,
+            window: 0,
+            auto_threshold: AUTO_THRESHOLD,
+            fold_case: FOLD_CASE,
+            fold_diacritics: FOLD_DIACRITICS,
+        }
+    }
+}
+
+/// Settings for searching the prepared pattern, with its inline error budget
+fn pattern_config() -> SearchConfig {
+    let config = current_config();
+    let config = match unsafe { PATTERN_MAX_ERRORS } {
+        Some(max_errors) => SearchConfig { max_errors, ..config },
+        None => config,
+    };
+    // Resolve an automatic threshold for the prepared pattern
+    let threshold = config.threshold_for(unsafe { PATTERN_LEN });
+    SearchConfig { threshold, auto_threshold: false, ..config }
+}
+
+/// Searcher for the exclusion term, if one is set
+fn exclusion() -> Option<BitapSearcher> {
+    unsafe { (EXCLUDE_LEN > 0).then(|| new_searcher(&EXCLUDE[..EXCLUDE_LEN])) }
+}
+
+/// Check whether the exclusion term matches the record at or above the threshold
+fn excluded(exclude: Option<&BitapSearcher>, record: &RecordView, config: &SearchConfig) -> bool {
+    let Some(exclude) = exclude else {
+        return false;
+    };
+    if config.bloom && !BloomFilter(record.bloom).might_contain_within(exclude.bloom(), config.max_errors) {
+        return false;
+    }
+
+    let pattern_len = exclude.pattern_len() as u32;
+    let matches = |text: &[u8]| {
+        exclude
+            .search(text, config.max_errors)
+            .is_some_and(|m| bitap::compute_score(m.errors, pattern_len, m.end_pos) >= config.threshold)
+    };
+    matches(record.text(string_pool())) || (record.alias_len > 0 && matches(record.alias(string_pool())))
+}
+
+/// Execute search, return result count
+#[no_mangle]
+pub extern "C" fn ff_search() -> u32 {
+    scan_records(usize::MAX, None, 0)
+}
+
+/// Search only the first `max_records` active records, return result count
+/// For instant previews while the user is typing: records past the limit
+/// aren't examined, so results are partial. Limited searches aren't
+/// cached for `ff_rethreshold`
+#[no_mangle]
+pub extern "C" fn ff_search_limited(max_records: u32) -> u32 {
+    scan_records(max_records as usize, None, 0)
+}
+
+/// Return the `k` best matches whatever their score
+/// Ignores the threshold, so an unusual query still gets its nearest
+/// records; a record must still match within max errors. `k` is capped
+/// at the result capacity. Returns result count
+#[no_mangle]
+pub extern "C" fn ff_search_topk(k: u32) -> u32 {
+    scan_records(usize::MAX, Some(k as usize), 0)
+}
+
+/// Search only the first `max_offset` bytes of each record, return result count
+/// A match must end within the window, e.g. a title at the start of a
+/// "title\ndescription" record. 0 searches whole records, like `ff_search`.
+/// Windowed searches aren't cached for `ff_rethreshold`
+#[no_mangle]
+pub extern "C" fn ff_search_window(max_offset: u32) -> u32 {
+    scan_records(usize::MAX, None, max_offset as usize)
+}
+
+/// Ask the search in progress to stop early; callable from any thread
+/// The search polls the flag every few hundred records and returns the
+/// results found so far; `ff_search_cancelled` then reports 1. The flag
+/// is cleared when the next search starts, so a cancel with no search
+/// running has no effect.
+#[no_mangle]
+pub extern "C" fn ff_cancel() {
+    CANCEL.store(true, Ordering::Relaxed);
+}
+
+/// Get whether the last search was cut short by `ff_cancel` (1) or ran to the end (0)
+#[no_mangle]
+pub extern "C" fn ff_search_cancelled() -> u32 {
+    unsafe { LAST_CANCELLED as u32 }
+}
+
+/// Get whether the last search was refused as too broad (1) or ran (0)
+/// See `ff_set_min_pattern_bits`. Covers every search and `ff_count_matches`;
+/// `ff_search_terms` is refused only if all its terms are too broad, and
+/// `ff_search_multi` skips just the patterns that are and runs the rest.
+#[no_mangle]
+pub extern "C" fn ff_search_too_broad() -> u32 {
+    unsafe { LAST_TOO_BROAD as u32 }
+}
+
+/// Count one visited record, checking the cancel flag every `CANCEL_POLL`
+#[inline]
+fn cancel_requested(visited: &mut usize) -> bool {
+    *visited += 1;
+    *visited % CANCEL_POLL == 0 && CANCEL.load(Ordering::Relaxed)
+}
+
+/// Host function receiving one match from `ff_search_callback`:
+/// record ID, score, start and end. A nonzero return stops the scan
+pub type MatchCallback = extern "C" fn(id: u32, score: u32, start: u32, end: u32) -> i32;
+
+/// Run the prepared search, passing each match to `cb` as it's found
+/// Matches pass the threshold and exclusion as in `ff_search` but arrive
+/// in record order, unranked and unlimited: neither max results nor the
+/// result buffer apply, and the current results are left as they are.
+/// `cb` must not call back into the engine. Returns the number of matches
+/// passed to `cb`, counting the one that stopped the scan; 0 if `cb` is null
+#[no_mangle]
+pub extern "C" fn ff_search_callback(cb: Option<MatchCallback>) -> u32 {
+    let Some(cb) = cb else {
+        return 0;
+    };
+    auto_compact();
+    unsafe {
+        LAST_TOO_BROAD = false;
+        if PATTERN_LEN == 0 {
+            return 0;
+        }
+
+        let config = pattern_config();
+        let exclude = exclusion();
+        let keep = |record: &RecordView| !excluded(exclude.as_ref(), record, &config);
+        let search = RecordSearch::new(&RECORDS[..RECORD_COUNT], string_pool(), prepared_searcher(), &config, &mut []);
+        if search.too_broad() {
+            LAST_TOO_BROAD = true;
+            return 0;
+        }
+
+        let mut sent = 0;
+        for_each_match(&search, keep, |result| {
+            sent += 1;
+            let (start, end) = result.byte_span();
+            cb(result.id, result.score as u32, start, end) == 0
+        });
+        sent
+    }
+}
+
+/// Host function giving the final score of a match from `ff_search_rerank`:
+/// record ID and text score (0-1000), returning the score to rank by
+pub type RerankCallback = extern "C" fn(id: u32, text_score: u32) -> u32;
+
+/// Run the prepared search, ranking each match by the score `cb` returns
+/// Lets the host blend its own signals (popularity, recency) into the
+/// ranking. Matches must pass the threshold on their text score; returned
+/// scores are capped at 1000 and replace the text score in the results.
+/// `cb` must not call back into the engine. Returns the number of results;
+/// 0 if `cb` is null
+#[no_mangle]
+pub extern "C" fn ff_search_rerank(cb: Option<RerankCallback>) -> u32 {
+    unsafe {
+        LAST_CANCELLED = false;
+        LAST_TOO_BROAD = false;
+        remember_results();
+        RESULT_COUNT = 0;
+        invalidate_candidates();
+        auto_compact();
+
+        let Some(cb) = cb else {
+            return 0;
+        };
+        if PATTERN_LEN == 0 {
+            return 0;
+        }
+
+        let config = pattern_config();
+        let exclude = exclusion();
+        let keep = |record: &RecordView| !excluded(exclude.as_ref(), record, &config);
+        let mut search = RecordSearch::new(&RECORDS[..RECORD_COUNT], string_pool(), prepared_searcher(), &config, results());
+        if search.too_broad() {
+            LAST_TOO_BROAD = true;
+            return 0;
+        }
+
+        let blocks = &*core::ptr::addr_of!(BLOCK_BLOOMS);
+        for (block, &bloom) in blocks[..blocks_for(RECORD_COUNT)].iter().enumerate() {
+            if !search.may_match_block(bloom) {
+                continue;
+            }
+            let start = block * BLOOM_BLOCK;
+            for slot in start..(start + BLOOM_BLOCK).min(RECORD_COUNT) {
+                search.visit_rescored(slot, keep, |result| cb(result.id, result.score as u32));
+            }
+        }
+        RESULT_COUNT = search.finish();
+        RESULT_COUNT as u32
+    }
+}
+
+/// Count the records `query` matches, without ranking or storing them
+/// The query is matched as-is, without query syntax or an exclusion term,
+/// under the current config. Neither the prepared pattern nor the results
+/// are touched and nothing is sorted, so counting many candidate queries
+/// (e.g. for facet counts) costs one scan each. Returns 0 for an empty query
+///
+/// # Safety
+/// `query_ptr` must be null or valid for reads of `query_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ff_count_matches(query_ptr: *const u8, query_len: u32) -> u32 {
+    let query = raw_bytes(query_ptr, query_len);
+    let mut pattern = [0u8; MAX_PATTERN_LEN];
+    let len = query.len().min(MAX_PATTERN_LEN);
+    pattern[..len].copy_from_slice(&query[..len]);
+    let len = collapsed_len(&mut pattern[..len]);
+    LAST_TOO_BROAD = false;
+    if len == 0 {
+        return 0;
+    }
+    auto_compact();
+
+    let searcher = new_searcher(&pattern[..len]);
+    let config = current_config();
+    let search = RecordSearch::new(&RECORDS[..RECORD_COUNT], string_pool(), &searcher, &config, &mut []);
+    if search.too_broad() {
+        LAST_TOO_BROAD = true;
+        return 0;
+    }
+
+    let mut count = 0;
+    for_each_match(&search, |_| true, |_| {
+        count += 1;
+        true
+    });
+    count
+}
+
+/// Pass each record `search` keeps to `f`, in slot order, until it returns false
+/// Skips whole blocks whose summary bloom rules out a match.
+fn for_each_match(search: &RecordSearch, keep: impl Fn(&RecordView) -> bool, mut f: impl FnMut(ScoredResult) -> bool) {
+    let count = unsafe { RECORD_COUNT };
+    let blocks = unsafe { &*core::ptr::addr_of!(BLOCK_BLOOMS) };
+    for (block, &bloom) in blocks[..blocks_for(count)].iter().enumerate() {
+        if !search.may_match_block(bloom) {
+            continue;
+        }
+        let start = block * BLOOM_BLOCK;
+        for slot in start..(start + BLOOM_BLOCK).min(count) {
+            if let Some(result) = search.score_record(slot, &keep) {
+                if !f(result) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Run the prepared search over at most `limit` active records, in slot order
+/// With `top_k`, keep that many results and ignore the threshold
+fn scan_records(limit: usize, top_k: Option<usize>, window: usize) -> u32 {
+    CANCEL.store(false, Ordering::Relaxed);
+    unsafe {
+        LAST_CANCELLED = false;
+        LAST_TOO_BROAD = false;
+        remember_results();
+        RESULT_COUNT = 0;
+        invalidate_candidates();
+        auto_compact();
+
+        if PATTERN_LEN == 0 {
+            return 0;
+        }
+
+        let searcher = prepared_searcher();
+        let config = SearchConfig { window, ..pattern_config() };
+        let exclude = exclusion();
+        let keep = |record: &RecordView| !excluded(exclude.as_ref(), record, &config);
+
+        // Exclusion and recency both depend on the threshold, so only plain
+        // searches collect candidates for ff_rethreshold
+        let cache = CANDIDATE_CACHE
+            && top_k.is_none()
+            && limit == usize::MAX
+            && window == 0
+            && exclude.is_none()
+            && config.recency_weight == 0;
+        let scan_config = match top_k {
+            Some(k) => SearchConfig { threshold: 0, max_results: k, ..config },
+            None if cache => SearchConfig { threshold: 0, ..config },
+            None => config,
+        };
+        let out = if cache { candidates() } else { results() };
+
+        let records = &RECORDS[..RECORD_COUNT];
+        let mut search = RecordSearch::new(records, string_pool(), searcher, &scan_config, out);
+        if search.too_broad() {
+            LAST_TOO_BROAD = true;
+            return 0;
+        }
+        let mut visited = 0;
+        let mut cancelled = false;
+
+        // Exact prefix queries only need records sharing the pattern's first bytes.
+        // Aliases aren't in the prefix index, phonetic matches ignore the
+        // prefix and the index keys keep accents, so any of them forces a full scan.
+        let exact_prefix = config.anchor == Anchor::Start && config.max_errors == 0 && config.match_mode == MatchMode::Bitap;
+        if exact_prefix && ALIAS_COUNT == 0 && !config.phonetic && !config.fold_diacritics {
+            let slots = prefix_index().candidates(searcher.pattern()).map(|slot| slot as usize);
+            for slot in slots.filter(|&slot| records[slot].active).take(limit) {
+                if cancel_requested(&mut visited) {
+                    cancelled = true;
+                    break;
+                }
+                search.visit_if(slot, keep);
+            }
+        } else if limit == usize::MAX {
+            // Skip whole blocks whose summary bloom rules the pattern out
+            let blocks = &*core::ptr::addr_of!(BLOCK_BLOOMS);
+            'blocks: for (block, &bloom) in blocks[..blocks_for(RECORD_COUNT)].iter().enumerate() {
+                if !search.may_match_block(bloom) {
+                    continue;
+                }
+                let start = block * BLOOM_BLOCK;
+                for slot in start..(start + BLOOM_BLOCK).min(RECORD_COUNT) {
+                    if cancel_requested(&mut visited) {
+                        cancelled = true;
+                        break 'blocks;
+                    }
+                    search.visit_if(slot, keep);
+                }
+            }
+        } else {
+            for (slot, _) in RecordsView::new(records, string_pool()).slots().take(limit) {
+                if cancel_requested(&mut visited) {
+                    cancelled = true;
+                    break;
+                }
+                search.visit_if(slot, keep);
+            }
+        }
+        LAST_CANCELLED = cancelled;
+
+        if cache {
+            CANDIDATE_COUNT = search.finish();
+            // Partial candidates can't stand in for a full search
+            if !cancelled {
+                CANDIDATE_CONFIG = Some(scan_config);
+            }
+            return filter_candidates(config.threshold);
+        }
+        RESULT_COUNT = search.finish();
+        RESULT_COUNT as u32
+    }
+}
+
+/// Cache the candidates of each `ff_search` for `ff_rethreshold` (0 = off, default)
+/// Candidates are the results before the threshold is applied, up to
+/// max results of them. Searches with an exclusion term or a recency
+/// weight aren't cached.
+#[no_mangle]
+pub extern "C" fn ff_set_candidate_cache(enabled: u32) {
+    unsafe {
+        CANDIDATE_CACHE = enabled != 0;
+    }
+    invalidate_candidates();
+}
+
+/// Set the threshold and re-filter the last search at it
+/// Uses the candidates cached by the last `ff_search` when they are still
+/// current: no record, pattern or other setting has changed since.
+/// Otherwise runs `ff_search`. Returns result count
+#[no_mangle]
+pub extern "C" fn ff_rethreshold(threshold: u32) -> u32 {
+    unsafe {
+        THRESHOLD = threshold as u16;
+        AUTO_THRESHOLD = false;
+        if CANDIDATE_CONFIG == Some(SearchConfig { threshold: 0, ..pattern_config() }) {
+            remember_results();
+            filter_candidates(THRESHOLD)
+        } else {
+            ff_search()
+        }
+    }
+}
+
+/// Rank the cached candidates scoring at least `threshold` into the results
+unsafe fn filter_candidates(threshold: u16) -> u32 {
+    let Some(config) = CANDIDATE_CONFIG else {
+        return 0;
+    };
+    let config = SearchConfig { threshold, ..config };
+    let records = &RECORDS[..RECORD_COUNT];
+    let mut results = ResultSet::new(results(), &config);
+    for &candidate in &candidates()[..CANDIDATE_COUNT] {
+        if candidate.score >= threshold {
+            results.insert(candidate, records, string_pool());
+        }
+    }
+    RESULT_COUNT = results.finish();
+    RESULT_COUNT as u32
+}
+
+/// Report which result IDs entered and left between the last two searches
+/// Writes up to `cap` IDs in the current results but not the previous
+/// ones to `out_added`, and up to `cap` IDs that dropped out to
+/// `out_removed`, each in rank order. A null list receives nothing.
+/// Returns the added count in bits 0-15 and the removed count in bits 16-31
+///
+/// # Safety
+/// Each pointer must be null or valid for writes of `cap` u32s.
+#[no_mangle]
+pub unsafe extern "C" fn ff_result_diff(out_added: *mut u32, out_removed: *mut u32, cap: u32) -> u32 {
+    if destroyed() {
+        return 0;
+    }
+    let cap = cap.min(0xFFFF);
+    let previous = previous_results();
+    let (added, removed) = diff_result_ids(
+        &previous[..PREVIOUS_COUNT.min(previous.len())],
+        &results()[..RESULT_COUNT],
+        raw_ids_mut(out_added, cap),
+        raw_ids_mut(out_removed, cap),
+    );
+    (removed << 16 | added) as u32
+}
+
+/// Search for `include`, dropping records that match `exclude`
+/// A record is dropped when the exclusion term fuzzy-matches its text or
+/// alias with a score at or above the threshold. The exclusion stays in
+/// effect for later `ff_search` calls until the next `ff_prepare_pattern`.
+/// Returns result count
+///
+/// # Safety
+/// Each pointer must be null or valid for reads of its length in bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ff_search_with_exclusion(
+    include_ptr: *const u8,
+    include_len: u32,
+    exclude_ptr: *const u8,
+    exclude_len: u32,
+) -> u32 {
+    let include = raw_bytes(include_ptr, include_len);
+    let exclude = raw_bytes(exclude_ptr, exclude_len);
+
+    set_pattern(&include[..include.len().min(MAX_PATTERN_LEN)]);
+    let len = exclude.len().min(MAX_PATTERN_LEN);
+    EXCLUDE[..len].copy_from_slice(&exclude[..len]);
+    EXCLUDE_LEN = collapsed_len(&mut EXCLUDE[..len]);
+
+    ff_search()
+}
+
+/// View a caller-provided buffer as a byte slice (empty if null)
+unsafe fn raw_bytes<'a>(ptr: *const u8, len: u32) -> &'a [u8] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(ptr, len as usize)
+    }
+}
 
-// ============ FFI Exports ============
+/// View a host-provided u32 buffer, treating null as empty
+unsafe fn raw_ids_mut<'a>(ptr: *mut u32, len: u32) -> &'a mut [u32] {
+    if ptr.is_null() || len == 0 {
+        &mut []
+    } else {
+        core::slice::from_raw_parts_mut(ptr, len as usize)
+    }
+}
 
-/// Initialize the engine (reset all state)
+/// Search for every whitespace-separated term in the scratchpad
+/// A record matches only if each term fuzzy-matches its text or alias
+/// within the error budget, in any order. Its score is the weakest term's
+/// score and its span covers all term matches. Terms past MAX_TERMS are
+/// ignored. Returns result count
 #[no_mangle]
-pub extern "C" fn ff_init() {
+pub extern "C" fn ff_search_terms() -> u32 {
     unsafe {
-        RECORD_COUNT = 0;
-        STRING_POOL_USED = 0;
-        PATTERN_LEN = 0;
+        LAST_TOO_BROAD = false;
+        remember_results();
         RESULT_COUNT = 0;
+        invalidate_candidates();
+
+        let query = &scratchpad()[..SCRATCHPAD_LEN];
+        let mut terms: [Option<BitapSearcher>; MAX_TERMS] = Default::default();
+        let mut term_count = 0;
+        let mut terms_bloom = BloomFilter::new();
+        for term in query.split(|c| c.is_ascii_whitespace()).filter(|t| !t.is_empty()).take(MAX_TERMS) {
+            let searcher = new_searcher(&term[..term.len().min(MAX_PATTERN_LEN)]);
+            terms_bloom = BloomFilter(terms_bloom.bits() | searcher.bloom().bits());
+            terms[term_count] = Some(searcher);
+            term_count += 1;
+        }
         SCRATCHPAD_LEN = 0;
-        MAX_ERRORS = 2;
-        THRESHOLD = 250;
-        MAX_RESULTS_CFG = 50;
-        CHAR_MASKS = [0; 256];
+
+        if term_count == 0 {
+            return 0;
+        }
+
+        // Terms are plain bitap matches anywhere in the text
+        let config = current_config();
+        let term_config = SearchConfig { match_mode: MatchMode::Bitap, anchor: Anchor::None, ..config };
+        let matchers: [Option<Matcher>; MAX_TERMS] =
+            core::array::from_fn(|i| terms[i].as_ref().map(|searcher| Matcher::new(searcher, &term_config)));
+        // Every term must match, so one narrow term narrows the query
+        if matchers.iter().flatten().all(Matcher::too_broad) {
+            LAST_TOO_BROAD = true;
+            return 0;
+        }
+
+        let records = &RECORDS[..RECORD_COUNT];
+        let pool = string_pool();
+        let mut results = ResultSet::new(results(), &config);
+        // Each term may miss up to max_errors of its chars
+        let terms_missing = config.max_errors * term_count as u32;
+
+        'records: for (slot, record) in RecordsView::new(records, pool).slots() {
+            if config.bloom && !BloomFilter(record.bloom).might_contain_within(terms_bloom, terms_missing) {
+                continue;
+            }
+
+            let mut combined: Option<ScoredResult> = None;
+            for (matcher, term) in matchers.iter().flatten().zip(terms.iter().flatten()) {
+                let Some(hit) = matcher.match_record(record, pool) else {
+                    continue 'records;
+                };
+                if hit.score < config.threshold_for(term.pattern_len()) {
+                    continue 'records;
+                }
+                combined = Some(match combined {
+                    None => hit,
+                    Some(c) => ScoredResult {
+                        score: c.score.min(hit.score),
+                        start: c.start.min(hit.start),
+                        end: c.end.max(hit.end),
+                        errors: c.errors + hit.errors,
+                        text_len: c.text_len.max(hit.text_len),
+                        ..c
+                    },
+                });
+            }
+
+            if let Some(mut result) = combined {
+                result.score = apply_recency(result.score, record.added_seq, NEXT_SEQ, config.recency_weight);
+                result.index = slot as u32;
+                results.insert(result, records, pool);
+            }
+        }
+
+        RESULT_COUNT = results.finish();
+        RESULT_COUNT as u32
     }
 }
 
-/// Get pointer to write buffer
+/// Register the scratchpad as a pattern for `ff_search_multi`
+/// Patterns are matched as-is, without query syntax or an exclusion term.
+/// Returns the pattern's index, -1 if MAX_MULTI_PATTERNS are registered
+/// or -2 if the scratchpad is empty
 #[no_mangle]
-pub extern "C" fn ff_get_write_buffer(size: u32) -> *mut u8 {
+pub extern "C" fn ff_add_multi_pattern() -> i32 {
     unsafe {
-        if size as usize > SCRATCHPAD_SIZE {
-            return core::ptr::null_mut();
+        let len = collapsed_len(&mut scratchpad()[..SCRATCHPAD_LEN]).min(MAX_PATTERN_LEN);
+        SCRATCHPAD_LEN = 0;
+        if len == 0 {
+            return -2;
+        }
+        if MULTI_PATTERN_COUNT >= MAX_MULTI_PATTERNS {
+            return -1;
         }
-        SCRATCHPAD.as_mut_ptr()
+
+        let index = MULTI_PATTERN_COUNT;
+        MULTI_PATTERNS[index] = Some(new_searcher(&scratchpad()[..len]));
+        MULTI_PATTERN_COUNT += 1;
+        MULTI_RESULT_COUNTS[index] = 0;
+        index as i32
     }
 }
 
-/// Commit written bytes to scratchpad
+/// Remove every pattern registered for `ff_search_multi`, and their results
 #[no_mangle]
-pub extern "C" fn ff_commit_write(len: u32) {
+pub extern "C" fn ff_clear_multi_patterns() {
     unsafe {
-        let l = len as usize;
-        SCRATCHPAD_LEN = if l > SCRATCHPAD_SIZE { SCRATCHPAD_SIZE } else { l };
+        MULTI_PATTERNS = [NO_PATTERN; MAX_MULTI_PATTERNS];
+        MULTI_PATTERN_COUNT = 0;
+        MULTI_RESULT_COUNTS = [0; MAX_MULTI_PATTERNS];
     }
 }
 
-/// Add a record from scratchpad
-/// Returns: 1 on success, negative on error
+/// Search for every registered pattern in a single pass over the records
+///
+/// The bloom prefilter has two levels. Each record is first checked
+/// against the bits every pattern's bloom sets; one that misses more of
+/// them than the error budget fails every pattern, so it is skipped with
+/// a single comparison instead of one per pattern. Records that pass are
+/// bloom-checked and scored against each pattern in turn, and each pattern
+/// keeps its own results under the current config.
+///
+/// The more the patterns overlap, the more shared bits there are to
+/// reject on: with P patterns a rejected record costs 1 comparison rather
+/// than P, and a passing one P + 1. Patterns with no char in common share
+/// no bits, and then the first level is skipped.
+/// Returns the total result count across patterns
 #[no_mangle]
-pub extern "C" fn ff_add_record(id: u32) -> i32 {
+pub extern "C" fn ff_search_multi() -> u32 {
     unsafe {
-        if RECORD_COUNT >= MAX_RECORDS {
-            return -1;
-        }
-        if SCRATCHPAD_LEN == 0 {
-            return -2;
+        MULTI_RESULT_COUNTS = [0; MAX_MULTI_PATTERNS];
+        LAST_TOO_BROAD = false;
+
+        let config = current_config();
+        let records = &RECORDS[..RECORD_COUNT];
+        let pool = string_pool();
+        let patterns = &*core::ptr::addr_of!(MULTI_PATTERNS);
+        let mut buffers = (*core::ptr::addr_of_mut!(MULTI_RESULTS)).iter_mut();
+        let mut searches: [Option<RecordSearch>; MAX_MULTI_PATTERNS] = core::array::from_fn(|i| {
+            let out = buffers.next()?;
+            patterns[i].as_ref().map(|searcher| RecordSearch::new(records, pool, searcher, &config, out))
+        });
+        // Too broad patterns are left out; the rest still run
+        for search in &mut searches {
+            if search.as_ref().is_some_and(RecordSearch::too_broad) {
+                *search = None;
+                LAST_TOO_BROAD = true;
+            }
         }
 
-        let text_start = STRING_POOL_USED;
-        let text_len = SCRATCHPAD_LEN;
+        // Phonetic matches bypass blooms, so they can't be rejected up front
+        let running = || patterns.iter().zip(&searches).filter(|(_, search)| search.is_some()).filter_map(|(p, _)| p.as_ref());
+        let shared = running().fold(u64::MAX, |bits, searcher| bits & searcher.bloom().bits());
+        let prefilter = config.bloom && !config.phonetic && running().count() > 1 && shared != 0;
 
-        if text_start + text_len > STRING_POOL_SIZE {
-            return -3;
+        for (slot, record) in RecordsView::new(records, pool).slots() {
+            if prefilter && !BloomFilter(record.bloom).might_contain_within(BloomFilter(shared), config.max_errors) {
+                continue;
+            }
+            for search in searches.iter_mut().flatten() {
+                search.visit(slot);
+            }
         }
 
-        // Copy text to string pool
-        let src = &SCRATCHPAD[..text_len];
-        let dst = &mut STRING_POOL[text_start..text_start + text_len];
-        dst.copy_from_slice(src);
-        STRING_POOL_USED += text_len;
-
-        // Pre-compute bloom filter
-        let bloom = BloomFilter::from_text(src);
-
-        RECORDS[RECORD_COUNT] = Record {
-            id,
-            text_start: text_start as u32,
-            text_len: text_len as u16,
-            bloom: bloom.bits(),
-            active: true,
-        };
-        RECORD_COUNT += 1;
-        SCRATCHPAD_LEN = 0;
-
-        1
+        let mut total = 0;
+        for (i, search) in searches.into_iter().enumerate() {
+            if let Some(search) = search {
+                MULTI_RESULT_COUNTS[i] = search.finish();
+                total += MULTI_RESULT_COUNTS[i];
+            }
+        }
+        total as u32
     }
 }
 
-/// Remove a record by ID
+/// Get the result count of multi pattern `pattern` from the last `ff_search_multi`
 #[no_mangle]
-pub extern "C" fn ff_remove_record(id: u32) -> i32 {
-    unsafe {
-        for i in 0..RECORD_COUNT {
-            if RECORDS[i].id == id && RECORDS[i].active {
-                RECORDS[i].active = false;
-                return 1;
-            }
-        }
-        0
-    }
+pub extern "C" fn ff_get_multi_result_count(pattern: u32) -> u32 {
+    multi_results(pattern).len() as u32
 }
 
-/// Set maximum errors allowed (0-3)
+/// Get multi pattern `pattern`'s result ID at index
 #[no_mangle]
-pub extern "C" fn ff_set_max_errors(errors: u32) {
-    unsafe {
-        MAX_ERRORS = if errors > 3 { 3 } else { errors };
-    }
+pub extern "C" fn ff_get_multi_result_id(pattern: u32, index: u32) -> u32 {
+    multi_results(pattern).get(index as usize).map_or(0, |r| r.id)
 }
 
-/// Set minimum score threshold (0-1000)
+/// Get multi pattern `pattern`'s result score at index
 #[no_mangle]
-pub extern "C" fn ff_set_threshold(threshold: u32) {
-    unsafe {
-        THRESHOLD = threshold as u16;
-    }
+pub extern "C" fn ff_get_multi_result_score(pattern: u32, index: u32) -> u32 {
+    multi_results(pattern).get(index as usize).map_or(0, |r| r.score as u32)
 }
 
-/// Set maximum results to return
+/// Get a bitmask of the multi patterns whose last results include `id`
+/// Bit N is set for pattern index N. Only results kept under max results count.
 #[no_mangle]
-pub extern "C" fn ff_set_max_results(max: u32) {
+pub extern "C" fn ff_get_multi_match_mask(id: u32) -> u32 {
+    (0..MAX_MULTI_PATTERNS as u32)
+        .filter(|&p| multi_results(p).iter().any(|r| r.id == id))
+        .fold(0, |mask, p| mask | 1 << p)
+}
+
+/// Results of multi pattern `pattern` from the last `ff_search_multi`
+fn multi_results(pattern: u32) -> &'static [ScoredResult] {
+    let p = pattern as usize;
+    if p >= MAX_MULTI_PATTERNS {
+        return &[];
+    }
     unsafe {
-        let m = max as usize;
-        MAX_RESULTS_CFG = if m > MAX_RESULTS { MAX_RESULTS } else { m };
+        let results = &*core::ptr::addr_of!(MULTI_RESULTS);
+        &results[p][..MULTI_RESULT_COUNTS[p]]
     }
 }
 
-/// Get current record count
+/// Write the text of the record closest to the prepared pattern to `out`
+/// A "did you mean" for searches with no results: ignores the threshold
+/// and picks the record matching with the fewest errors, up to
+/// MAX_ERRORS_CEIL and fewer than the pattern length. The text is cut to
+/// `cap` bytes at a UTF-8 char boundary. Returns the bytes written, or -1
+/// if no pattern is prepared or no record comes close
+///
+/// # Safety
+/// `out` must be null or valid for writes of `cap` bytes.
 #[no_mangle]
-pub extern "C" fn ff_get_record_count() -> u32 {
-    unsafe { RECORD_COUNT as u32 }
+pub unsafe extern "C" fn ff_suggest(out: *mut u8, cap: u32) -> i32 {
+    if PATTERN_LEN == 0 {
+        return -1;
+    }
+
+    let budget = (PATTERN_LEN as u32 - 1).min(MAX_ERRORS_CEIL);
+    let records = &RECORDS[..RECORD_COUNT];
+    let Some(slot) = closest_record(records, string_pool(), prepared_searcher(), budget) else {
+        return -1;
+    };
+
+    let text = records[slot].text(string_pool());
+    let mut len = text.len().min(cap as usize);
+    while len < text.len() && len > 0 && text[len] & 0xC0 == 0x80 {
+        len -= 1;
+    }
+    if !out.is_null() {
+        core::ptr::copy_nonoverlapping(text.as_ptr(), out, len);
+    }
+    len as i32
 }
 
-/// Prepare pattern from scratchpad
+/// Find the prepared pattern in one record, at or after `from_offset`
+/// For find-in-page: returns the start of the first match `search_all`
+/// would report in the record text from `from_offset` on, or -1 if there
+/// is none, no pattern is prepared or no active record has this ID. Step
+/// forward by passing the previous start + 1; for previous, step from 0
+/// and keep the last start before the cursor. Uses the prepared pattern's
+/// error budget but ignores the match mode, anchor and threshold
 #[no_mangle]
-pub extern "C" fn ff_prepare_pattern() {
+pub extern "C" fn ff_find_in_record(id: u32, from_offset: u32) -> i32 {
     unsafe {
-        let len = if SCRATCHPAD_LEN > MAX_PATTERN_LEN { MAX_PATTERN_LEN } else { SCRATCHPAD_LEN };
-        PATTERN_LEN = len;
-
-        let src = &SCRATCHPAD[..len];
-        let dst = &mut PATTERN[..len];
-        dst.copy_from_slice(src);
-
-        // Build character masks and bloom filter using core
-        let searcher = BitapSearcher::new(src);
-        PATTERN_BLOOM = searcher.bloom().bits();
-
-        // Rebuild char masks (we need them for search)
-        CHAR_MASKS = [0; 256];
-        for (i, &ch) in src.iter().enumerate() {
-            let lower = to_lower(ch);
-            let bit = 1u32 << i;
-            CHAR_MASKS[lower as usize] |= bit;
-            if lower != ch {
-                CHAR_MASKS[ch as usize] |= bit;
-            }
+        if PATTERN_LEN == 0 {
+            return -1;
         }
+        let Some(record) = RECORDS[..RECORD_COUNT].iter().rfind(|r| r.id == id && r.active) else {
+            return -1;
+        };
 
-        SCRATCHPAD_LEN = 0;
+        let searcher = prepared_searcher();
+        let text = record.text(string_pool());
+        let max_errors = pattern_config().errors_for(searcher.pattern_len(), text.len());
+        match searcher.next_match(text, from_offset as usize, max_errors) {
+            Some(m) => m.start_pos as i32,
+            None => -1,
+        }
     }
 }
 
-/// Execute search, return result count
+/// Explain how the prepared pattern fares against one record
+/// Runs every stage regardless of earlier failures, so a bloom rejection
+/// of a record that would otherwise match is visible.
+/// Returns -1 if no pattern is prepared or no active record has this ID,
+/// otherwise a bitfield:
+/// - bit 0: bloom prefilter passed
+/// - bit 1: pattern matched (bitap or subsequence)
+/// - bit 2: score passed the threshold
+/// - bits 3-7: match errors (saturates at 31)
+/// - bits 8-17: match score (0-1000)
+/// - bit 18: record matches the exclusion term
 #[no_mangle]
-pub extern "C" fn ff_search() -> u32 {
+pub extern "C" fn ff_explain(id: u32) -> i32 {
     unsafe {
-        RESULT_COUNT = 0;
-
         if PATTERN_LEN == 0 {
-            return 0;
+            return -1;
         }
 
-        let pattern = &PATTERN[..PATTERN_LEN];
-        let searcher = BitapSearcher::new(pattern);
-        let pattern_bloom = BloomFilter(PATTERN_BLOOM);
-        let threshold = THRESHOLD;
-        let max_errors = MAX_ERRORS;
+        let record = match RECORDS[..RECORD_COUNT].iter().rfind(|r| r.id == id && r.active) {
+            Some(record) => record,
+            None => return -1,
+        };
 
-        for i in 0..RECORD_COUNT {
-            let record = &RECORDS[i];
-            if !record.active {
-                continue;
-            }
+        let searcher = prepared_searcher();
+        let config = pattern_config();
+        let matcher = Matcher::new(searcher, &config);
+        let mut bits = 0i32;
 
-            // Bloom filter pre-check
-            let text_bloom = BloomFilter(record.bloom);
-            if !text_bloom.might_contain(pattern_bloom) {
-                continue;
+        if matcher.bloom_passes(record) {
+            bits |= EXPLAIN_BLOOM_PASSED;
+        }
+        if let Some(hit) = matcher.match_record(record, string_pool()) {
+            bits |= EXPLAIN_MATCHED;
+            if hit.score >= config.threshold {
+                bits |= EXPLAIN_THRESHOLD_PASSED;
             }
+            bits |= (hit.errors.min(31) as i32) << EXPLAIN_ERRORS_SHIFT;
+            bits |= (hit.score as i32) << EXPLAIN_SCORE_SHIFT;
+        }
+        if excluded(exclusion().as_ref(), record, &config) {
+            bits |= EXPLAIN_EXCLUDED;
+        }
+        bits
+    }
+}
 
-            let start = record.text_start as usize;
-            let end = start + record.text_len as usize;
-            let text = &STRING_POOL[start..end];
+/// Break down the score of the `ff_search` result at `index`
+/// Re-matches the record under the prepared pattern and current config,
+/// so call it before changing either. A phonetic match reports its fixed
+/// score as the base. Returns 0, or -1 if there's no such result or `out`
+/// is null
+///
+/// # Safety
+/// `out` must be null or valid for writes of a `ScoreBreakdown` (six u32s).
+#[no_mangle]
+pub unsafe extern "C" fn ff_explain_result(index: u32, out: *mut ScoreBreakdown) -> i32 {
+    let i = index as usize;
+    if i >= RESULT_COUNT || out.is_null() || PATTERN_LEN == 0 {
+        return -1;
+    }
 
-            if let Some(m) = searcher.search(text, max_errors) {
-                let score = bitap::compute_score(m.errors, PATTERN_LEN as u32, m.end_pos);
+    let result = results()[i];
+    let record = &RECORDS[result.index as usize];
+    let text = if result.alias { record.alias(string_pool()) } else { record.text(string_pool()) };
+    let config = pattern_config();
+    let breakdown = match Matcher::new(prepared_searcher(), &config).explain_text(text) {
+        Some(breakdown) if breakdown.score >= result.score as u32 => breakdown,
+        // Phonetic match, or the text no longer matches
+        _ => ScoreBreakdown::new(result.score as u32, 0, 0),
+    };
+    // Recency lowered the score after matching
+    out.write(breakdown.lowered_to(result.score as u32));
+    0
+}
 
-                if score >= threshold {
-                    let start_pos = m.end_pos.saturating_sub(PATTERN_LEN);
+/// Count the insertions, deletions and substitutions in the `ff_search`
+/// result at `index`
+/// Re-matches the record as `ff_explain_result` does, so call it before
+/// changing the pattern or config. Returns 0, or -1 if there's no such
+/// result, `out` is null or the record no longer matches by text (a
+/// phonetic match has no edits to count)
+///
+/// # Safety
+/// `out` must be null or valid for writes of an `EditProfile` (three u32s).
+#[no_mangle]
+pub unsafe extern "C" fn ff_get_result_edit_profile(index: u32, out: *mut EditProfile) -> i32 {
+    let i = index as usize;
+    if i >= RESULT_COUNT || out.is_null() || PATTERN_LEN == 0 {
+        return -1;
+    }
 
-                    insert_result(ScoredResult::new(
-                        record.id,
-                        score,
-                        start_pos as u16,
-                        m.end_pos as u16,
-                    ));
-                }
-            }
+    let result = results()[i];
+    let record = &RECORDS[result.index as usize];
+    let text = if result.alias { record.alias(string_pool()) } else { record.text(string_pool()) };
+    let config = pattern_config();
+    match Matcher::new(prepared_searcher(), &config).edit_profile(text) {
+        Some(profile) => {
+            out.write(profile);
+            0
         }
-
-        RESULT_COUNT as u32
+        None => -1,
     }
 }
 
-fn insert_result(result: ScoredResult) {
-    unsafe {
-        if RESULT_COUNT >= MAX_RESULTS_CFG {
-            if result.score <= RESULTS[RESULT_COUNT - 1].score {
-                return;
-            }
-            RESULT_COUNT -= 1;
-        }
-
-        // Insertion sort (descending by score)
-        let mut pos = RESULT_COUNT;
-        while pos > 0 && RESULTS[pos - 1].score < result.score {
-            if pos < MAX_RESULTS {
-                RESULTS[pos] = RESULTS[pos - 1];
-            }
-            pos -= 1;
-        }
+/// Get the prepared pattern's bitap mask for `byte` (debugging aid)
+/// Bit `i` is set if pattern char `i` equals `byte`, ASCII case folded,
+/// for the first 32 chars. 0 if no pattern is prepared
+#[no_mangle]
+pub extern "C" fn ff_get_char_mask(byte: u32) -> u32 {
+    if unsafe { PATTERN_LEN } == 0 {
+        return 0;
+    }
+    prepared_searcher().char_mask(byte as u8) as u32
+}
 
-        if pos < MAX_RESULTS {
-            RESULTS[pos] = result;
-            RESULT_COUNT += 1;
-        }
+/// Pointer to the last search's results, `ff_results_len` of them
+/// For hosts in the same address space: read every result in place
+/// instead of calling a getter per field. Entries are core
+/// `ScoredResult`s (`repr(C)`); positions are u32 in the `std` build and
+/// u16 otherwise. Valid only until the next search, add, remove, or
+/// `ff_set_result_capacity`/`ff_destroy_all` call, whichever comes first
+#[no_mangle]
+pub extern "C" fn ff_results_ptr() -> *const ScoredResult {
+    if destroyed() {
+        return core::ptr::null();
     }
+    results().as_ptr()
+}
+
+/// Number of results at `ff_results_ptr`, as the last search returned
+#[no_mangle]
+pub extern "C" fn ff_results_len() -> u32 {
+    unsafe { RESULT_COUNT as u32 }
 }
 
 /// Get result ID at index
@@ -294,7 +2427,7 @@ fn insert_result(result: ScoredResult) {
 pub extern "C" fn ff_get_result_id(index: u32) -> u32 {
     unsafe {
         let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].id } else { 0 }
+        if i < RESULT_COUNT { results()[i].id } else { 0 }
     }
 }
 
@@ -303,54 +2436,435 @@ pub extern "C" fn ff_get_result_id(index: u32) -> u32 {
 pub extern "C" fn ff_get_result_score(index: u32) -> u32 {
     unsafe {
         let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].score as u32 } else { 0 }
+        if i < RESULT_COUNT { results()[i].score as u32 } else { 0 }
     }
 }
 
 /// Get result start position at index
+/// Saturates at 65535 unless built with the `std` feature
 #[no_mangle]
 pub extern "C" fn ff_get_result_start(index: u32) -> u32 {
     unsafe {
         let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].start as u32 } else { 0 }
+        if i < RESULT_COUNT { results()[i].byte_span().0 } else { 0 }
     }
 }
 
 /// Get result end position at index
+/// Saturates at 65535 unless built with the `std` feature
 #[no_mangle]
 pub extern "C" fn ff_get_result_end(index: u32) -> u32 {
     unsafe {
         let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].end as u32 } else { 0 }
+        if i < RESULT_COUNT { results()[i].byte_span().1 } else { 0 }
     }
 }
 
-/// Reset all data
+/// Get result edit distance at index
 #[no_mangle]
-pub extern "C" fn ff_reset() {
+pub extern "C" fn ff_get_result_errors(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { results()[i].errors } else { 0 }
+    }
+}
+
+/// Get the score rank of the result at index (0 = best)
+/// Equals the index under the default score order; with `ff_set_sort_order`
+/// it still gives the result's position by score
+#[no_mangle]
+pub extern "C" fn ff_get_result_rank(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { results()[i].rank } else { 0 }
+    }
+}
+
+/// Get the result's score relative to the top result (0-1000)
+/// `score * 1000 / top_score`: 1000 for the best match, and e.g. 500 for
+/// one scoring half as well
+#[no_mangle]
+pub extern "C" fn ff_get_result_relative(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { results()[i].relative as u32 } else { 0 }
+    }
+}
+
+/// Get the calibrated confidence (0-1000) that the result is the intended match
+/// `1000 * (1 - errors / max(pattern_len, match_len))` for the prepared
+/// pattern, so the same cutoff means the same across query lengths
+#[no_mangle]
+pub extern "C" fn ff_get_result_confidence(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i >= RESULT_COUNT {
+            return 0;
+        }
+        let result = results()[i];
+        let (start, end) = result.byte_span();
+        bitap::confidence(result.errors, PATTERN_LEN, (end - start) as usize) as u32
+    }
+}
+
+/// Get how densely the query occurs in the result's matched text
+/// `hits * 1000 / text_len`, counting non-overlapping matches: among equal
+/// scores, a record repeating the query in fewer bytes ranks higher
+#[no_mangle]
+pub extern "C" fn ff_get_result_density(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { results()[i].density() } else { 0 }
+    }
+}
+
+/// Get the share of the matched text covered by the match at index (0-1000)
+/// Measured against the record text, or the alias if that matched better;
+/// 1000 means the match spans the whole text
+#[no_mangle]
+pub extern "C" fn ff_get_result_coverage(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { results()[i].coverage() } else { 0 }
+    }
+}
+
+/// Get the length in bytes of the longest prefix shared by all result texts
+/// For tab-completion style auto-fill: compares record texts (not
+/// aliases) byte for byte and never splits a UTF-8 char. 0 with no results
+#[no_mangle]
+pub extern "C" fn ff_results_common_prefix() -> u32 {
+    unsafe { common_prefix_len(&RECORDS[..RECORD_COUNT], string_pool(), &results()[..RESULT_COUNT]) as u32 }
+}
+
+/// Start and end of the result at index as char offsets, (0, 0) if out of range
+fn result_char_span(index: u32) -> (u32, u32) {
+    unsafe {
+        let i = index as usize;
+        if i >= RESULT_COUNT {
+            return (0, 0);
+        }
+        let result = &results()[i];
+        let record = &RECORDS[result.index as usize];
+        result.char_span(record.matched_text(result, string_pool()))
+    }
+}
+
+/// Get result start position at index in chars rather than bytes
+/// Counts UTF-8 lead bytes, so each multibyte char counts once. Chars
+/// outside the BMP count once here but twice in UTF-16 strings
+#[no_mangle]
+pub extern "C" fn ff_get_result_char_start(index: u32) -> u32 {
+    result_char_span(index).0
+}
+
+/// Get result end position at index in chars rather than bytes
+#[no_mangle]
+pub extern "C" fn ff_get_result_char_end(index: u32) -> u32 {
+    result_char_span(index).1
+}
+
+/// Clear records, the prepared pattern and results, keeping all configuration
+/// Settings such as max errors, threshold and max results survive; use
+/// `ff_init` to restore the defaults as well
+#[no_mangle]
+pub extern "C" fn ff_clear_data() {
     unsafe {
         RECORD_COUNT = 0;
-        STRING_POOL_USED = 0;
-        RESULT_COUNT = 0;
+        INACTIVE_COUNT = 0;
+        BLOCK_BLOOMS = [0; BLOOM_BLOCKS];
+        RECORD_HIGH_WATER = 0;
+        ALIAS_COUNT = 0;
+        INDEX_DIGEST = digest::EMPTY_DIGEST;
+        NEXT_SEQ = 0;
+        pool_alloc().reset();
+        prefix_index().reset();
         PATTERN_LEN = 0;
+        SEARCHER = None;
+        EXCLUDE_LEN = 0;
+        RESULT_COUNT = 0;
+        SCRATCHPAD_LEN = 0;
     }
+    release_write_buffer();
+    ff_clear_multi_patterns();
+    invalidate_candidates();
+    rebuild_ids();
 }
 
-/// Compact records (remove inactive)
+/// Reset all data (same as `ff_clear_data`)
+#[deprecated(note = "use ff_clear_data to keep configuration or ff_init for a full reset")]
+#[no_mangle]
+pub extern "C" fn ff_reset() {
+    ff_clear_data();
+}
+
+/// Compact records: drop removed records, freeing their slots
+/// Active records keep their order, so ranking ties don't change. Current
+/// results are renumbered, and those of removed records dropped. The
+/// string pool isn't moved. Returns the record count afterwards
 #[no_mangle]
 pub extern "C" fn ff_compact() -> u32 {
-    unsafe { RECORD_COUNT as u32 }
+    unsafe {
+        let (count, result_count) = compact_records(&mut RECORDS[..RECORD_COUNT], &mut results()[..RESULT_COUNT]);
+        RECORD_COUNT = count;
+        RESULT_COUNT = result_count;
+        INACTIVE_COUNT = 0;
+        ALIAS_COUNT = RECORDS[..RECORD_COUNT].iter().filter(|r| r.alias_len > 0).count();
+
+        // Records changed slot: rebuild what's keyed by slot
+        BLOCK_BLOOMS = [0; BLOOM_BLOCKS];
+        prefix_index().reset();
+        let pool = string_pool();
+        for (slot, record) in RECORDS[..RECORD_COUNT].iter().enumerate() {
+            BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+            prefix_index().insert(slot as u32, record.text(pool));
+        }
+        invalidate_candidates();
+        RECORD_COUNT as u32
+    }
+}
+
+/// Compact if removed records exceed AUTO_COMPACT_RATIO of the slots
+fn auto_compact() {
+    unsafe {
+        if INACTIVE_COUNT > 0 && INACTIVE_COUNT as f32 > AUTO_COMPACT_RATIO * RECORD_COUNT as f32 {
+            ff_compact();
+        }
+    }
 }
 
-/// Get string pool used bytes
+/// Get string pool used bytes (high-water mark, including freed spans)
 #[no_mangle]
 pub extern "C" fn ff_get_string_pool_used() -> u32 {
-    unsafe { STRING_POOL_USED as u32 }
+    pool_alloc().used() as u32
 }
 
-/// Get available memory
+/// Get available memory (pool tail plus reusable freed spans)
 #[no_mangle]
 pub extern "C" fn ff_get_available_memory() -> u32 {
-    unsafe { (STRING_POOL_SIZE - STRING_POOL_USED) as u32 }
+    pool_alloc().available() as u32
+}
+
+/// Get total string pool size in bytes
+#[no_mangle]
+pub extern "C" fn ff_get_pool_capacity() -> u32 {
+    STRING_POOL_SIZE as u32
+}
+
+/// Get pool bytes not held by any active record
+/// Text of removed records and replaced aliases below the high-water mark
+/// (`ff_get_string_pool_used`). Freed spans are reused by later adds, but
+/// only compaction returns them all; `reclaimable / used` is the
+/// fragmentation ratio. O(records)
+#[no_mangle]
+pub extern "C" fn ff_get_pool_reclaimable() -> u32 {
+    let records = unsafe { &RECORDS[..RECORD_COUNT] };
+    let live = RecordsView::new(records, string_pool()).live_bytes();
+    pool_alloc().used().saturating_sub(live) as u32
 }
 
+/// Get the most record slots in use at once since the last clear
+#[no_mangle]
+pub extern "C" fn ff_get_record_high_water() -> u32 {
+    unsafe { RECORD_HIGH_WATER as u32 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Tests share the engine's globals, so they take turns
+    fn engine() -> MutexGuard<'static, ()> {
+        static ENGINE: Mutex<()> = Mutex::new(());
+        ENGINE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(text: &[u8]) {
+        let buf = ff_get_write_buffer(text.len() as u32);
+        unsafe { core::ptr::copy_nonoverlapping(text.as_ptr(), buf, text.len()) };
+        ff_commit_write(text.len() as u32);
+    }
+
+    fn add(id: u32, text: &[u8]) -> i32 {
+        write(text);
+        ff_add_record(id)
+    }
+
+    fn search(query: &[u8]) -> u32 {
+        write(query);
+        ff_prepare_pattern();
+        ff_search()
+    }
+
+    #[test]
+    fn test_clear_data_keeps_config() {
+        let _engine = engine();
+        ff_init();
+        ff_set_max_errors(1);
+        ff_set_threshold(600);
+        ff_set_max_results(5);
+        assert_eq!(add(1, b"keyboard"), 1);
+
+        ff_clear_data();
+        assert_eq!(ff_get_record_count(), 0);
+        assert_eq!(ff_get_string_pool_used(), 0);
+        let config = current_config();
+        assert_eq!((config.max_errors, config.threshold, config.max_results), (1, 600, 5));
+
+        ff_init();
+        let config = current_config();
+        assert_eq!((config.max_errors, config.threshold, config.max_results), (2, 250, 50));
+    }
+
+    #[test]
+    fn test_write_buffer_has_one_holder() {
+        let _engine = engine();
+        ff_init();
+        assert!(!ff_get_write_buffer(4).is_null());
+        assert!(ff_get_write_buffer(4).is_null());
+        assert!(ff_get_append_buffer(4).is_null());
+        assert_eq!(ff_reserve_scratchpad(16), 0);
+        ff_commit_write(0);
+
+        assert!(!ff_get_append_buffer(4).is_null());
+        assert!(ff_get_write_buffer(4).is_null());
+        assert!(ff_get_append_buffer(4).is_null());
+        assert_eq!(ff_reserve_scratchpad(16), 0);
+        ff_append_write(0);
+
+        assert_eq!(ff_reserve_scratchpad(16), 1);
+        assert!(!ff_get_write_buffer(4).is_null());
+        ff_commit_write(0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_destroy_frees_buffers() {
+        let _engine = engine();
+        ff_init();
+        assert_eq!(add(1, b"keyboard"), 1);
+        assert_eq!(search(b"keyboard"), 1);
+
+        ff_destroy_all();
+        assert_eq!(ff_get_scratchpad_size(), 0);
+        assert_eq!(ff_warmup(), 0);
+        assert!(ff_results_ptr().is_null());
+        assert_eq!(ff_results_len(), 0);
+        assert_eq!(ff_get_result_id(0), 0);
+        assert_eq!(unsafe { ff_result_diff(core::ptr::null_mut(), core::ptr::null_mut(), 0) }, 0);
+        assert_eq!(ff_set_result_capacity(10), 0);
+        assert_eq!(ff_reserve_scratchpad(16), 0);
+        assert!(ff_get_write_buffer(4).is_null());
+        assert_eq!(ff_search(), 0);
+        unsafe {
+            assert_eq!((*core::ptr::addr_of!(SCRATCHPAD)).capacity(), 0);
+            assert_eq!((*core::ptr::addr_of!(RESULTS)).capacity(), 0);
+            assert_eq!((*core::ptr::addr_of!(CANDIDATES)).capacity(), 0);
+            assert_eq!((*core::ptr::addr_of!(PREVIOUS_RESULTS)).capacity(), 0);
+        }
+
+        ff_init();
+        assert_eq!(ff_get_scratchpad_size() as usize, SCRATCHPAD_SIZE);
+    }
+
+    #[test]
+    fn test_alias_count_follows_removal() {
+        let _engine = engine();
+        ff_init();
+        assert_eq!(add(1, b"keyboard"), 1);
+        assert_eq!(add(2, b"mouse"), 1);
+        write(b"kb");
+        assert_eq!(ff_add_record_alias(1), 1);
+        assert_eq!(unsafe { ALIAS_COUNT }, 1);
+
+        // Removing the aliased record turns the prefix index back on
+        assert_eq!(ff_remove_record(1), 1);
+        assert_eq!(unsafe { ALIAS_COUNT }, 0);
+
+        write(b"mm");
+        assert_eq!(ff_add_record_alias(2), 1);
+        unsafe { ALIAS_COUNT = 5 };
+        ff_compact();
+        assert_eq!(unsafe { ALIAS_COUNT }, 1);
+    }
+
+    #[test]
+    fn test_update_with_full_table() {
+        let _engine = engine();
+        ff_init();
+        for id in 0..MAX_RECORDS as u32 {
+            assert_eq!(add(id, b"banana"), 1);
+        }
+        assert_eq!(add(MAX_RECORDS as u32, b"cherry"), -1);
+
+        // The slot is reused, and the prefix index follows the new text
+        write(b"apple");
+        assert_eq!(ff_update_record(7), 1);
+        ff_set_anchor(1);
+        ff_set_max_errors(0);
+        assert_eq!(search(b"ap"), 1);
+        assert_eq!(ff_get_result_id(0), 7);
+        assert_eq!(unsafe { RECORDS[7].id }, 7);
+    }
+
+    #[test]
+    fn test_update_with_full_pool() {
+        let _engine = engine();
+        ff_init();
+        assert_eq!(add(1, b"keyboard"), 1);
+        let rest = pool_alloc().available();
+        assert!(pool_alloc().alloc(rest).is_some());
+
+        write(b"mouse");
+        assert_eq!(ff_update_record(1), -3);
+        assert_eq!(unsafe { RECORDS[0].text(string_pool()) }, b"keyboard");
+        assert_eq!(search(b"keyboard"), 1);
+        assert_eq!(ff_get_result_id(0), 1);
+    }
+
+    #[test]
+    fn test_update_keeps_alias() {
+        let _engine = engine();
+        ff_init();
+        assert_eq!(add(1, b"keyboard"), 1);
+        assert_eq!(add(2, b"mouse"), 1);
+        write(b"kb");
+        assert_eq!(ff_add_record_alias(1), 1);
+        let seq = unsafe { RECORDS[0].added_seq };
+
+        write(b"trackpad");
+        assert_eq!(ff_update_record(1), 1);
+        assert_eq!(unsafe { ALIAS_COUNT }, 1);
+        assert_eq!(unsafe { RECORDS[0].added_seq }, seq);
+        assert_eq!(search(b"kb"), 1);
+        assert_eq!(ff_get_result_id(0), 1);
+        assert_eq!(search(b"trackpad"), 1);
+        assert_eq!(search(b"keyboard"), 0);
+    }
+
+    #[test]
+    fn test_too_broad_query() {
+        let _engine = engine();
+        ff_init();
+        ff_set_max_errors(3);
+        ff_set_threshold(0);
+        assert_eq!(add(1, b"eeebbb"), 1);
+        assert_eq!(add(2, b"keyboard"), 1);
+
+        // Three errors leave "eeeaaa" no char a record must hold
+        ff_set_min_pattern_bits(2);
+        assert_eq!(search(b"eeeaaa"), 0);
+        assert_eq!(ff_search_too_broad(), 1);
+        assert_eq!(unsafe { ff_count_matches(b"eeeaaa".as_ptr(), 6) }, 0);
+        assert_eq!(ff_search_too_broad(), 1);
+
+        // Short queries are searched exactly, so they always run
+        assert_eq!(search(b"key"), 1);
+        assert_eq!(ff_search_too_broad(), 0);
+
+        ff_set_min_pattern_bits(0);
+        assert_eq!(search(b"eeeaaa"), 1);
+        assert_eq!(ff_search_too_broad(), 0);
+    }
+}
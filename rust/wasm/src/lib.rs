@@ -1,63 +1,201 @@
 //! Flash-Fuzzy WASM Module
 //! WebAssembly-specific wrapper around the core library
 
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
-use flash_fuzzy_core::{bitap, bloom::BloomFilter, BitapSearcher, ScoredResult};
+use flash_fuzzy_core::{
+    bitap, bloom::{BloomFilter, BloomHash}, closest_record, common_prefix_len, compact_records, diff_result_ids, digest, phonetic, query,
+    subsequence::DEFAULT_SEPARATORS, whitespace,
+    search::{apply_recency, Matcher, RecordSearch, ResultSet},
+    Anchor, BitapSearcher, CharSet, EditProfile, Limits, MatchMode, PoolAllocator, PrefixIndex, RecordView, RecordsView, ScoreBreakdown, ScoredResult, SearchConfig,
+    SortOrder, FEATURES, MAX_ERRORS_CEIL, MAX_PATTERN_LEN, VERSION,
+};
 
 // ============ Memory Constants ============
 
-const MAX_RECORDS: usize = 100_000;
-const MAX_RESULTS: usize = 100;
-const STRING_POOL_SIZE: usize = 4 * 1024 * 1024; // 4MB
-const SCRATCHPAD_SIZE: usize = 64 * 1024; // 64KB
-const MAX_PATTERN_LEN: usize = 32;
+const MAX_RECORDS: usize = Limits::MAX_RECORDS;
+const MAX_RESULTS: usize = Limits::MAX_RESULTS;
+const STRING_POOL_SIZE: usize = Limits::STRING_POOL_SIZE;
+const SCRATCHPAD_SIZE: usize = Limits::SCRATCHPAD_SIZE;
+const BLOOM_BLOCK: usize = 1024;
+const BLOOM_BLOCKS: usize = blocks_for(MAX_RECORDS);
 
-// ============ Record Structure ============
-
-#[derive(Clone, Copy)]
-struct Record {
-    id: u32,
-    text_start: u32,
-    text_len: u16,
-    bloom: u64,
-    active: bool,
+/// Blocks in BLOCK_BLOOMS covering the first `records` slots
+const fn blocks_for(records: usize) -> usize {
+    (records + BLOOM_BLOCK - 1) / BLOOM_BLOCK
 }
 
-impl Default for Record {
-    fn default() -> Self {
-        Self { id: 0, text_start: 0, text_len: 0, bloom: 0, active: false }
-    }
-}
+const MAX_TERMS: usize = 8;
+const MAX_MULTI_PATTERNS: usize = 8;
+
+// ============ Explain Bits ============
+
+const EXPLAIN_BLOOM_PASSED: i32 = 1 << 0;
+const EXPLAIN_MATCHED: i32 = 1 << 1;
+const EXPLAIN_THRESHOLD_PASSED: i32 = 1 << 2;
+const EXPLAIN_ERRORS_SHIFT: u32 = 3;
+const EXPLAIN_SCORE_SHIFT: u32 = 8;
+const EXPLAIN_EXCLUDED: i32 = 1 << 18;
 
 // ============ Global State ============
 
-static mut RECORDS: [Record; MAX_RECORDS] = [Record { id: 0, text_start: 0, text_len: 0, bloom: 0, active: false }; MAX_RECORDS];
+static mut RECORDS: [RecordView; MAX_RECORDS] = [RecordView { id: 0, text_start: 0, text_len: 0, alias_start: 0, alias_len: 0, bloom: 0, chars: CharSet::EMPTY, added_seq: 0, phonetic: 0, active: false }; MAX_RECORDS];
 static mut RECORD_COUNT: usize = 0;
+// OR of the blooms of each BLOOM_BLOCK slots; removals leave bits set
+static mut BLOCK_BLOOMS: [u64; BLOOM_BLOCKS] = [0; BLOOM_BLOCKS];
+static mut ALIAS_COUNT: usize = 0;
+static mut INACTIVE_COUNT: usize = 0;
+static mut AUTO_COMPACT_RATIO: f32 = 1.0;
+// Most slots in use at once since the last clear
+static mut RECORD_HIGH_WATER: usize = 0;
+// Sum of active record hashes, see `digest`
+static mut INDEX_DIGEST: u64 = digest::EMPTY_DIGEST;
 
 static mut STRING_POOL: [u8; STRING_POOL_SIZE] = [0; STRING_POOL_SIZE];
-static mut STRING_POOL_USED: usize = 0;
+static mut POOL_ALLOC: PoolAllocator = PoolAllocator::new(STRING_POOL_SIZE);
+static mut PREFIX_INDEX: PrefixIndex<MAX_RECORDS> = PrefixIndex::new();
 
 static mut PATTERN: [u8; MAX_PATTERN_LEN] = [0; MAX_PATTERN_LEN];
 static mut PATTERN_LEN: usize = 0;
-static mut CHAR_MASKS: [u32; 256] = [0; 256];
+// Error budget parsed from the prepared query's `~N` suffix
+static mut PATTERN_MAX_ERRORS: Option<u32> = None;
 static mut PATTERN_BLOOM: u64 = 0;
+// Searcher for PATTERN, kept so preparing the same query again skips the rebuild
+static mut SEARCHER: Option<BitapSearcher> = None;
+static mut EXCLUDE: [u8; MAX_PATTERN_LEN] = [0; MAX_PATTERN_LEN];
+static mut EXCLUDE_LEN: usize = 0;
 
 static mut MAX_ERRORS: u32 = 2;
 static mut THRESHOLD: u16 = 250;
+// Derive the threshold from the pattern length, see bitap::auto_threshold
+static mut AUTO_THRESHOLD: bool = false;
 static mut MAX_RESULTS_CFG: usize = 50;
+static mut MATCH_MODE: MatchMode = MatchMode::Bitap;
+static mut BOUNDARY_BONUS: u16 = 30;
+static mut SEPARATORS: CharSet = DEFAULT_SEPARATORS;
+static mut RECENCY_WEIGHT: u16 = 0;
+static mut ERROR_RATIO: f32 = 0.0;
+static mut MIN_MATCH_RATIO: f32 = 0.0;
+static mut NEXT_SEQ: u32 = 0;
+static mut ANCHOR: Anchor = Anchor::None;
+static mut DEDUP: bool = false;
+static mut MIN_SCORE_GAP: u16 = 0;
+static mut MIN_PATTERN_BITS: u32 = 0;
+static mut LAST_TOO_BROAD: bool = false;
+static mut PHONETIC: bool = false;
+static mut COLLAPSE_WHITESPACE: bool = false;
+static mut QUERY_SYNTAX: bool = false;
+static mut BLOOM_ENABLED: bool = true;
+static mut BLOOM_HASH: BloomHash = BloomHash::LowBits;
+static mut FOLD_CASE: bool = true;
+static mut FOLD_DIACRITICS: bool = false;
+static mut UNIQUE_IDS: bool = false;
+static mut SORT_ORDER: SortOrder = SortOrder::ByScore;
 
-static mut RESULTS: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0 }; MAX_RESULTS];
+static mut RESULTS: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS];
 static mut RESULT_COUNT: usize = 0;
 
+// Last search's results before the threshold, kept for rethreshold
+static mut CANDIDATES: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS];
+static mut CANDIDATE_COUNT: usize = 0;
+// Config (threshold 0) CANDIDATES were found with; None when stale
+static mut CANDIDATE_CONFIG: Option<SearchConfig> = None;
+static mut CANDIDATE_CACHE: bool = false;
+
+// Results of the search before the last one, kept for resultDiff
+static mut PREVIOUS_RESULTS: [ScoredResult; MAX_RESULTS] = [ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS];
+static mut PREVIOUS_COUNT: usize = 0;
+
+// Patterns registered by addMultiPattern, and each one's results from searchMulti
+// Repeats into the array below, since BitapSearcher isn't Copy
+const NO_PATTERN: Option<BitapSearcher> = None;
+static mut MULTI_PATTERNS: [Option<BitapSearcher>; MAX_MULTI_PATTERNS] = [NO_PATTERN; MAX_MULTI_PATTERNS];
+static mut MULTI_PATTERN_COUNT: usize = 0;
+static mut MULTI_RESULTS: [[ScoredResult; MAX_RESULTS]; MAX_MULTI_PATTERNS] = [[ScoredResult { id: 0, score: 0, start: 0, end: 0, errors: 0, index: 0, text_len: 0, alias: false, rank: 0, relative: 0, hits: 0 }; MAX_RESULTS]; MAX_MULTI_PATTERNS];
+static mut MULTI_RESULT_COUNTS: [usize; MAX_MULTI_PATTERNS] = [0; MAX_MULTI_PATTERNS];
+
 static mut SCRATCHPAD: [u8; SCRATCHPAD_SIZE] = [0; SCRATCHPAD_SIZE];
 static mut SCRATCHPAD_LEN: usize = 0;
 
+fn text_bloom(text: &[u8]) -> u64 {
+    if unsafe { BLOOM_ENABLED } {
+        BloomFilter::with_hash(text, unsafe { BLOOM_HASH }).bits()
+    } else {
+        BloomFilter::FULL.bits()
+    }
+}
+
+#[inline]
+fn new_searcher(pattern: &[u8]) -> BitapSearcher {
+    BitapSearcher::with_folding(pattern, current_config().folding()).with_bloom_hash(unsafe { BLOOM_HASH })
+}
+
+#[inline]
+fn collapsed_len(buf: &mut [u8]) -> usize {
+    if unsafe { COLLAPSE_WHITESPACE } {
+        whitespace::collapse(buf)
+    } else {
+        buf.len()
+    }
+}
+
+#[inline]
+fn string_pool() -> &'static [u8] {
+    unsafe { &*core::ptr::addr_of!(STRING_POOL) }
+}
+
+#[inline]
+fn results() -> &'static mut [ScoredResult] {
+    unsafe { &mut *core::ptr::addr_of_mut!(RESULTS) }
+}
+
+#[inline]
+fn candidates() -> &'static mut [ScoredResult] {
+    unsafe { &mut *core::ptr::addr_of_mut!(CANDIDATES) }
+}
+
+fn remember_results() {
+    unsafe {
+        let previous = &mut *core::ptr::addr_of_mut!(PREVIOUS_RESULTS);
+        previous[..RESULT_COUNT].copy_from_slice(&RESULTS[..RESULT_COUNT]);
+        PREVIOUS_COUNT = RESULT_COUNT;
+    }
+}
+
+#[inline]
+fn invalidate_candidates() {
+    unsafe {
+        CANDIDATE_CONFIG = None;
+    }
+}
+
+#[inline]
+fn pool_alloc() -> &'static mut PoolAllocator {
+    unsafe { &mut *core::ptr::addr_of_mut!(POOL_ALLOC) }
+}
+
+#[inline]
+fn prefix_index() -> &'static mut PrefixIndex<MAX_RECORDS> {
+    unsafe { &mut *core::ptr::addr_of_mut!(PREFIX_INDEX) }
+}
+
+#[inline]
+fn prepared_searcher() -> &'static BitapSearcher {
+    unsafe {
+        (*core::ptr::addr_of_mut!(SEARCHER)).get_or_insert_with(|| new_searcher(&PATTERN[..PATTERN_LEN]))
+    }
+}
+
 // ============ Panic Handler ============
 
+// Trap so the host sees a RuntimeError rather than a hung tab
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_: &core::panic::PanicInfo) -> ! {
+    #[cfg(target_arch = "wasm32")]
+    core::arch::wasm32::unreachable();
+    #[cfg(not(target_arch = "wasm32"))]
     loop {}
 }
 
@@ -66,38 +204,109 @@ fn panic(_: &core::panic::PanicInfo) -> ! {
 
 #[no_mangle]
 pub extern "C" fn init() {
+    clearData();
     unsafe {
-        RECORD_COUNT = 0;
-        STRING_POOL_USED = 0;
-        PATTERN_LEN = 0;
-        RESULT_COUNT = 0;
-        SCRATCHPAD_LEN = 0;
         MAX_ERRORS = 2;
         THRESHOLD = 250;
+        AUTO_THRESHOLD = false;
         MAX_RESULTS_CFG = 50;
-        CHAR_MASKS = [0; 256];
+        MATCH_MODE = MatchMode::Bitap;
+        BOUNDARY_BONUS = 30;
+        SEPARATORS = DEFAULT_SEPARATORS;
+        RECENCY_WEIGHT = 0;
+        ERROR_RATIO = 0.0;
+        MIN_MATCH_RATIO = 0.0;
+        AUTO_COMPACT_RATIO = 1.0;
+        ANCHOR = Anchor::None;
+        DEDUP = false;
+        MIN_SCORE_GAP = 0;
+        MIN_PATTERN_BITS = 0;
+        LAST_TOO_BROAD = false;
+        PHONETIC = false;
+        COLLAPSE_WHITESPACE = false;
+        QUERY_SYNTAX = false;
+        BLOOM_ENABLED = true;
+        BLOOM_HASH = BloomHash::LowBits;
+        FOLD_CASE = true;
+        FOLD_DIACRITICS = false;
+        UNIQUE_IDS = false;
+        SORT_ORDER = SortOrder::ByScore;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getVersion() -> u32 {
+    VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn getFeatures() -> u32 {
+    FEATURES
+}
+
+/// Write the fixed capacities (six u32s, see `Limits`); 0 on success, -1 if `out` is null
+///
+/// # Safety
+/// `out` must be null or point into linear memory valid for six u32s.
+#[no_mangle]
+pub unsafe extern "C" fn getLimits(out: *mut Limits) -> i32 {
+    if out.is_null() {
+        return -1;
     }
+    out.write(Limits::CURRENT);
+    0
 }
 
 #[no_mangle]
 pub extern "C" fn getWriteBuffer(size: usize) -> *mut u8 {
+    if size > SCRATCHPAD_SIZE {
+        return core::ptr::null_mut();
+    }
+    core::ptr::addr_of_mut!(SCRATCHPAD) as *mut u8
+}
+
+#[no_mangle]
+pub extern "C" fn getScratchpadSize() -> u32 {
+    SCRATCHPAD_SIZE as u32
+}
+
+#[no_mangle]
+pub extern "C" fn commitWrite(len: usize) {
+    unsafe {
+        SCRATCHPAD_LEN = if len > SCRATCHPAD_SIZE { SCRATCHPAD_SIZE } else { len };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getAppendBuffer(size: usize) -> *mut u8 {
     unsafe {
-        if size > SCRATCHPAD_SIZE {
+        if SCRATCHPAD_LEN + size > SCRATCHPAD_SIZE {
             return core::ptr::null_mut();
         }
-        SCRATCHPAD.as_mut_ptr()
+        (core::ptr::addr_of_mut!(SCRATCHPAD) as *mut u8).add(SCRATCHPAD_LEN)
     }
 }
 
 #[no_mangle]
-pub extern "C" fn commitWrite(len: usize) {
+pub extern "C" fn appendWrite(len: usize) {
     unsafe {
-        SCRATCHPAD_LEN = if len > SCRATCHPAD_SIZE { SCRATCHPAD_SIZE } else { len };
+        SCRATCHPAD_LEN = (SCRATCHPAD_LEN + len).min(SCRATCHPAD_SIZE);
     }
 }
 
 #[no_mangle]
 pub extern "C" fn addRecord(id: u32) -> i32 {
+    add_scratchpad_record(id, None)
+}
+
+/// Add a record with a precomputed bloom; a wrong bloom can only cause missed matches
+#[no_mangle]
+pub extern "C" fn addRecordWithBloom(id: u32, bloom: u64) -> i32 {
+    add_scratchpad_record(id, Some(bloom))
+}
+
+fn add_scratchpad_record(id: u32, bloom: Option<u64>) -> i32 {
+    auto_compact();
     unsafe {
         if RECORD_COUNT >= MAX_RECORDS {
             return -1;
@@ -105,30 +314,86 @@ pub extern "C" fn addRecord(id: u32) -> i32 {
         if SCRATCHPAD_LEN == 0 {
             return -2;
         }
+        if UNIQUE_IDS && RECORDS[..RECORD_COUNT].iter().any(|r| r.id == id && r.active) {
+            return -4;
+        }
 
-        let text_start = STRING_POOL_USED;
+        SCRATCHPAD_LEN = collapsed_len(&mut SCRATCHPAD[..SCRATCHPAD_LEN]);
         let text_len = SCRATCHPAD_LEN;
-
-        if text_start + text_len > STRING_POOL_SIZE {
-            return -3;
-        }
+        let text_start = match pool_alloc().alloc(text_len) {
+            Some(start) => start,
+            None => return -3,
+        };
 
         let src = &SCRATCHPAD[..text_len];
         let dst = &mut STRING_POOL[text_start..text_start + text_len];
         dst.copy_from_slice(src);
-        STRING_POOL_USED += text_len;
 
-        let bloom = BloomFilter::from_text(src);
+        prefix_index().insert(RECORD_COUNT as u32, src);
+
+        let bloom = bloom.unwrap_or_else(|| text_bloom(src));
 
-        RECORDS[RECORD_COUNT] = Record {
+        BLOCK_BLOOMS[RECORD_COUNT / BLOOM_BLOCK] |= bloom;
+        RECORDS[RECORD_COUNT] = RecordView {
             id,
             text_start: text_start as u32,
-            text_len: text_len as u16,
-            bloom: bloom.bits(),
+            text_len: text_len as u32,
+            alias_start: 0,
+            alias_len: 0,
+            bloom,
+            chars: CharSet::from_text(src),
+            added_seq: NEXT_SEQ,
+            phonetic: phonetic::encode(src),
             active: true,
         };
         RECORD_COUNT += 1;
+        RECORD_HIGH_WATER = RECORD_HIGH_WATER.max(RECORD_COUNT);
+        INDEX_DIGEST = digest::add_record(INDEX_DIGEST, id, src);
+        NEXT_SEQ = NEXT_SEQ.wrapping_add(1);
+        SCRATCHPAD_LEN = 0;
+        invalidate_candidates();
+
+        1
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn addRecordAlias(id: u32) -> i32 {
+    unsafe {
+        if SCRATCHPAD_LEN == 0 {
+            return -2;
+        }
+
+        // Latest active record with this ID
+        let slot = match RECORDS[..RECORD_COUNT].iter().rposition(|r| r.id == id && r.active) {
+            Some(slot) => slot,
+            None => return 0,
+        };
+
+        SCRATCHPAD_LEN = collapsed_len(&mut SCRATCHPAD[..SCRATCHPAD_LEN]);
+        let alias_len = SCRATCHPAD_LEN;
+        let alias_start = match pool_alloc().alloc(alias_len) {
+            Some(start) => start,
+            None => return -3,
+        };
+
+        let src = &SCRATCHPAD[..alias_len];
+        STRING_POOL[alias_start..alias_start + alias_len].copy_from_slice(src);
+
+        let record = &mut RECORDS[slot];
+        if record.alias_len > 0 {
+            pool_alloc().free(record.alias_start as usize, record.alias_len as usize);
+        } else {
+            ALIAS_COUNT += 1;
+        }
+        record.alias_start = alias_start as u32;
+        record.alias_len = alias_len as u32;
+        // OR in the alias bloom and chars so the prefilters pass alias-only matches
+        record.bloom |= text_bloom(src);
+        BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+        record.chars = record.chars.union(CharSet::from_text(src));
         SCRATCHPAD_LEN = 0;
+        invalidate_candidates();
 
         1
     }
@@ -137,14 +402,29 @@ pub extern "C" fn addRecord(id: u32) -> i32 {
 #[no_mangle]
 pub extern "C" fn setMaxErrors(errors: u32) {
     unsafe {
-        MAX_ERRORS = if errors > 3 { 3 } else { errors };
+        MAX_ERRORS = if errors > MAX_ERRORS_CEIL { MAX_ERRORS_CEIL } else { errors };
     }
 }
 
+/// After clamping to `MAX_ERRORS_CEIL`
+#[no_mangle]
+pub extern "C" fn getMaxErrors() -> u32 {
+    unsafe { MAX_ERRORS }
+}
+
 #[no_mangle]
 pub extern "C" fn setThreshold(threshold: u32) {
     unsafe {
         THRESHOLD = threshold as u16;
+        AUTO_THRESHOLD = false;
+    }
+}
+
+/// Derive the threshold from the pattern length (see `bitap::auto_threshold`); setThreshold turns it off
+#[no_mangle]
+pub extern "C" fn setAutoThreshold(enabled: u32) {
+    unsafe {
+        AUTO_THRESHOLD = enabled != 0;
     }
 }
 
@@ -156,184 +436,1293 @@ pub extern "C" fn setMaxResults(max: u32) {
     }
 }
 
+/// 0 = bitap, 1 = subsequence, 2 = word prefix
 #[no_mangle]
-pub extern "C" fn getRecordCount() -> u32 {
-    unsafe { RECORD_COUNT as u32 }
+pub extern "C" fn setMatchMode(mode: u32) {
+    unsafe {
+        MATCH_MODE = MatchMode::from_u32(mode);
+    }
 }
 
 #[no_mangle]
-pub extern "C" fn preparePattern() {
+pub extern "C" fn setAnchor(anchor: u32) {
     unsafe {
-        let len = if SCRATCHPAD_LEN > MAX_PATTERN_LEN { MAX_PATTERN_LEN } else { SCRATCHPAD_LEN };
-        PATTERN_LEN = len;
+        ANCHOR = Anchor::from_u32(anchor);
+    }
+}
 
-        let src = &SCRATCHPAD[..len];
-        let dst = &mut PATTERN[..len];
-        dst.copy_from_slice(src);
+#[no_mangle]
+pub extern "C" fn setDedup(enabled: u32) {
+    unsafe {
+        DEDUP = enabled != 0;
+    }
+}
 
-        CHAR_MASKS = [0; 256];
-        PATTERN_BLOOM = 0;
+/// Refuse patterns leaving fewer than `bits` bloom bits past the error budget (0 = off)
+#[no_mangle]
+pub extern "C" fn setMinPatternBits(bits: u32) {
+    unsafe {
+        MIN_PATTERN_BITS = bits;
+    }
+}
 
-        for (i, &ch) in src.iter().enumerate() {
-            let lower = to_lower(ch);
-            let bit = 1u32 << i;
-            CHAR_MASKS[lower as usize] |= bit;
-            if lower != ch {
-                CHAR_MASKS[ch as usize] |= bit;
-            }
-            let bloom_idx = (lower & 0x3F) as u64;
-            PATTERN_BLOOM |= 1u64 << bloom_idx;
-        }
+/// Drop results matching the same bytes as a kept one scoring within `gap`
+#[no_mangle]
+pub extern "C" fn setMinScoreGap(gap: u32) {
+    unsafe {
+        MIN_SCORE_GAP = gap.min(1000) as u16;
+    }
+}
 
-        SCRATCHPAD_LEN = 0;
+#[no_mangle]
+pub extern "C" fn setUniqueIds(enabled: u32) {
+    unsafe {
+        UNIQUE_IDS = enabled != 0;
     }
 }
 
 #[no_mangle]
-pub extern "C" fn search() -> u32 {
+pub extern "C" fn setBloomEnabled(enabled: u32) {
     unsafe {
-        RESULT_COUNT = 0;
+        BLOOM_ENABLED = enabled != 0;
+    }
+}
 
-        if PATTERN_LEN == 0 {
-            return 0;
+/// 0 = low bits (default), 1 = multiplicative, 2 = nibble fold
+#[no_mangle]
+pub extern "C" fn setBloomHash(scheme: u32) {
+    unsafe {
+        let hash = BloomHash::from_u32(scheme);
+        if hash == BLOOM_HASH {
+            return;
         }
+        BLOOM_HASH = hash;
 
-        let pattern = &PATTERN[..PATTERN_LEN];
-        let searcher = BitapSearcher::new(pattern);
-        let pattern_bloom = BloomFilter(PATTERN_BLOOM);
-        let threshold = THRESHOLD;
-        let max_errors = MAX_ERRORS;
-
-        for i in 0..RECORD_COUNT {
-            let record = &RECORDS[i];
-            if !record.active {
-                continue;
+        let pool = string_pool();
+        BLOCK_BLOOMS = [0; BLOOM_BLOCKS];
+        for (slot, record) in RECORDS[..RECORD_COUNT].iter_mut().enumerate() {
+            record.bloom = text_bloom(record.text(pool));
+            if record.alias_len > 0 {
+                record.bloom |= text_bloom(record.alias(pool));
             }
-
-            let text_bloom = BloomFilter(record.bloom);
-            if !text_bloom.might_contain(pattern_bloom) {
-                continue;
+            if record.active {
+                BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
             }
+        }
 
-            let start = record.text_start as usize;
-            let end = start + record.text_len as usize;
-            let text = &STRING_POOL[start..end];
+        rebuild_searchers();
+    }
+}
 
-            if let Some(m) = searcher.search(text, max_errors) {
-                let score = bitap::compute_score(m.errors, PATTERN_LEN as u32, m.end_pos);
+unsafe fn rebuild_searchers() {
+    SEARCHER = None;
+    if PATTERN_LEN > 0 {
+        PATTERN_BLOOM = prepared_searcher().bloom().bits();
+    }
+    for searcher in (*core::ptr::addr_of_mut!(MULTI_PATTERNS)).iter_mut().flatten() {
+        *searcher = new_searcher(searcher.pattern());
+    }
+    invalidate_candidates();
+}
 
-                if score >= threshold {
-                    let start_pos = m.end_pos.saturating_sub(PATTERN_LEN);
+#[no_mangle]
+pub extern "C" fn setFoldCase(enabled: u32) {
+    unsafe {
+        if FOLD_CASE != (enabled != 0) {
+            FOLD_CASE = enabled != 0;
+            rebuild_searchers();
+        }
+    }
+}
 
-                    insert_result(ScoredResult::new(
-                        record.id,
-                        score,
-                        start_pos as u16,
-                        m.end_pos as u16,
-                    ));
-                }
-            }
+/// "naive" then matches "naïve"; independent of `setFoldCase`
+#[no_mangle]
+pub extern "C" fn setFoldDiacritics(enabled: u32) {
+    unsafe {
+        if FOLD_DIACRITICS != (enabled != 0) {
+            FOLD_DIACRITICS = enabled != 0;
+            rebuild_searchers();
         }
+    }
+}
 
-        RESULT_COUNT as u32
+/// Positions then refer to the collapsed text, see `originalOffset`
+#[no_mangle]
+pub extern "C" fn setCollapseWhitespace(enabled: u32) {
+    unsafe {
+        COLLAPSE_WHITESPACE = enabled != 0;
     }
 }
 
-#[inline]
-fn to_lower(c: u8) -> u8 {
-    c | (0x20 * ((c >= b'A' && c <= b'Z') as u8))
+/// Map a position in the collapsed text of the `len` bytes at `ptr` back onto them
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn originalOffset(ptr: *const u8, len: u32, pos: u32) -> u32 {
+    whitespace::original_offset(raw_bytes(ptr, len), pos as usize) as u32
 }
 
-fn insert_result(result: ScoredResult) {
+#[no_mangle]
+pub extern "C" fn setQuerySyntax(enabled: u32) {
     unsafe {
-        if RESULT_COUNT >= MAX_RESULTS_CFG {
-            if result.score <= RESULTS[RESULT_COUNT - 1].score {
-                return;
-            }
-            RESULT_COUNT -= 1;
-        }
+        QUERY_SYNTAX = enabled != 0;
+    }
+}
 
-        let mut pos = RESULT_COUNT;
-        while pos > 0 && RESULTS[pos - 1].score < result.score {
-            if pos < MAX_RESULTS {
-                RESULTS[pos] = RESULTS[pos - 1];
-            }
-            pos -= 1;
-        }
+#[no_mangle]
+pub extern "C" fn setPhonetic(enabled: u32) {
+    unsafe {
+        PHONETIC = enabled != 0;
+    }
+}
 
-        if pos < MAX_RESULTS {
-            RESULTS[pos] = result;
-            RESULT_COUNT += 1;
-        }
+#[no_mangle]
+pub extern "C" fn setSortOrder(order: u32) {
+    unsafe {
+        SORT_ORDER = SortOrder::from_u32(order);
     }
 }
 
 #[no_mangle]
-pub extern "C" fn getResultId(index: u32) -> u32 {
+pub extern "C" fn setBoundaryBonus(bonus: u32) {
     unsafe {
-        let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].id } else { 0 }
+        BOUNDARY_BONUS = if bonus > 1000 { 1000 } else { bonus as u16 };
     }
 }
 
+/// # Safety
+/// `ptr` must be null or point into linear memory valid for `len` bytes.
 #[no_mangle]
-pub extern "C" fn getResultScore(index: u32) -> u32 {
+pub unsafe extern "C" fn setSeparators(ptr: *const u8, len: u32) {
+    SEPARATORS = CharSet::from_bytes(raw_bytes(ptr, len));
+}
+
+#[no_mangle]
+pub extern "C" fn setRecencyWeight(weight: u32) {
     unsafe {
-        let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].score as u32 } else { 0 }
+        RECENCY_WEIGHT = if weight > 1000 { 1000 } else { weight as u16 };
     }
 }
 
 #[no_mangle]
-pub extern "C" fn getResultStart(index: u32) -> u32 {
+pub extern "C" fn setErrorRatio(ratio: f32) {
     unsafe {
-        let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].start as u32 } else { 0 }
+        ERROR_RATIO = if ratio > 0.0 { ratio } else { 0.0 };
     }
 }
 
+/// Reject bitap matches spanning fewer than `ratio * pattern_len` bytes (0 = off)
 #[no_mangle]
-pub extern "C" fn getResultEnd(index: u32) -> u32 {
+pub extern "C" fn setMinMatchRatio(ratio: f32) {
     unsafe {
-        let i = index as usize;
-        if i < RESULT_COUNT { RESULTS[i].end as u32 } else { 0 }
+        MIN_MATCH_RATIO = if ratio > 0.0 { ratio } else { 0.0 };
     }
 }
 
+/// 0.0-1.0 share of removed slots that triggers compaction on add/search (1.0 = never)
 #[no_mangle]
-pub extern "C" fn reset() {
+pub extern "C" fn setAutoCompactRatio(ratio: f32) {
     unsafe {
-        RECORD_COUNT = 0;
-        STRING_POOL_USED = 0;
-        RESULT_COUNT = 0;
-        PATTERN_LEN = 0;
+        AUTO_COMPACT_RATIO = if ratio.is_nan() { 1.0 } else { ratio.clamp(0.0, 1.0) };
     }
 }
 
 #[no_mangle]
-pub extern "C" fn compact() -> u32 {
+pub extern "C" fn getRecordCount() -> u32 {
     unsafe { RECORD_COUNT as u32 }
 }
 
+/// # Safety
+/// `out` must be null or valid for writes of `cap` u32s.
 #[no_mangle]
-pub extern "C" fn removeRecord(id: u32) -> i32 {
+pub unsafe extern "C" fn getRecordIds(out: *mut u32, cap: u32) -> i32 {
+    let count = RECORD_COUNT - INACTIVE_COUNT;
+    let out = raw_ids_mut(out, cap);
+    if count > out.len() {
+        return -(count as i32);
+    }
+    let records = RecordsView::new(&RECORDS[..RECORD_COUNT], string_pool());
+    for (dst, (id, _)) in out.iter_mut().zip(records) {
+        *dst = id;
+    }
+    count as i32
+}
+
+#[no_mangle]
+pub extern "C" fn getRecordCapacity() -> u32 {
+    MAX_RECORDS as u32
+}
+
+#[no_mangle]
+pub extern "C" fn getRecordsRemaining() -> u32 {
+    unsafe { (MAX_RECORDS - RECORD_COUNT) as u32 }
+}
+
+#[no_mangle]
+pub extern "C" fn getRecordLen(id: u32) -> i32 {
     unsafe {
-        for i in 0..RECORD_COUNT {
-            if RECORDS[i].id == id && RECORDS[i].active {
-                RECORDS[i].active = false;
-                return 1;
-            }
+        match RECORDS[..RECORD_COUNT].iter().rfind(|r| r.id == id && r.active) {
+            Some(record) => record.text_len as i32,
+            None => -1,
         }
-        0
     }
 }
 
 #[no_mangle]
-pub extern "C" fn getStringPoolUsed() -> u32 {
-    unsafe { STRING_POOL_USED as u32 }
+pub extern "C" fn preparePattern() -> u32 {
+    unsafe {
+        let cached = if QUERY_SYNTAX {
+            let mut pattern = [0u8; MAX_PATTERN_LEN];
+            let (len, budget) = query::parse_error_budget(&SCRATCHPAD[..SCRATCHPAD_LEN], &mut pattern);
+            let cached = set_pattern(&pattern[..len]);
+            PATTERN_MAX_ERRORS = budget.map(|n| n.min(MAX_ERRORS_CEIL));
+            cached
+        } else {
+            let len = if SCRATCHPAD_LEN > MAX_PATTERN_LEN { MAX_PATTERN_LEN } else { SCRATCHPAD_LEN };
+            set_pattern(&SCRATCHPAD[..len])
+        };
+        EXCLUDE_LEN = 0;
+        SCRATCHPAD_LEN = 0;
+        cached as u32
+    }
+}
+
+unsafe fn set_pattern(src: &[u8]) -> bool {
+    invalidate_candidates();
+    PATTERN[..src.len()].copy_from_slice(src);
+    PATTERN_LEN = collapsed_len(&mut PATTERN[..src.len()]);
+    PATTERN_MAX_ERRORS = None;
+    let src = &PATTERN[..PATTERN_LEN];
+    if (*core::ptr::addr_of!(SEARCHER)).as_ref().is_some_and(|s| s.pattern() == src) {
+        return true;
+    }
+    match &mut *core::ptr::addr_of_mut!(SEARCHER) {
+        // Rebuild the previous query's searcher in place
+        Some(searcher) => searcher.reset(src),
+        slot => *slot = Some(new_searcher(src)),
+    }
+    PATTERN_BLOOM = prepared_searcher().bloom().bits();
+    false
 }
 
 #[no_mangle]
-pub extern "C" fn getAvailableMemory() -> u32 {
-    unsafe { (STRING_POOL_SIZE - STRING_POOL_USED) as u32 }
+pub extern "C" fn getPatternBloom() -> u64 {
+    unsafe { if PATTERN_LEN > 0 { PATTERN_BLOOM } else { 0 } }
+}
+
+#[no_mangle]
+pub extern "C" fn getIndexBloom() -> u64 {
+    let records = unsafe { &RECORDS[..RECORD_COUNT] };
+    RecordsView::new(records, string_pool()).slots().fold(0, |bits, (_, record)| bits | record.bloom)
+}
+
+/// Order-independent digest of active record IDs and texts (aliases excluded)
+#[no_mangle]
+pub extern "C" fn indexDigest() -> u64 {
+    unsafe { INDEX_DIGEST }
+}
+
+// ============ Search ============
+
+/// Snapshot the global settings as a core `SearchConfig`
+fn current_config() -> SearchConfig {
+    unsafe {
+        SearchConfig {
+            max_errors: MAX_ERRORS,
+            threshold: THRESHOLD,
+            max_results: MAX_RESULTS_CFG,
+            match_mode: MATCH_MODE,
+            boundary_bonus: BOUNDARY_BONUS,
+            anchor: ANCHOR,
+            dedup: DEDUP,
+            min_score_gap: MIN_SCORE_GAP,
+            recency_weight: RECENCY_WEIGHT,
+            sort: SORT_ORDER,
+            phonetic: PHONETIC,
+            bloom: BLOOM_ENABLED,
+            error_ratio: ERROR_RATIO,
+            min_match_ratio: MIN_MATCH_RATIO,
+            min_pattern_bits: MIN_PATTERN_BITS,
+            separators: SEPARATORS,
+            window: 0,
+            auto_threshold: AUTO_THRESHOLD,
+            fold_case: FOLD_CASE,
+            fold_diacritics: FOLD_DIACRITICS,
+        }
+    }
+}
+
+/// Settings for searching the prepared pattern, with its inline error budget
+fn pattern_config() -> SearchConfig {
+    let config = current_config();
+    let config = match unsafe { PATTERN_MAX_ERRORS } {
+        Some(max_errors) => SearchConfig { max_errors, ..config },
+        None => config,
+    };
+    // Resolve an automatic threshold for the prepared pattern
+    let threshold = config.threshold_for(unsafe { PATTERN_LEN });
+    SearchConfig { threshold, auto_threshold: false, ..config }
+}
+
+/// Searcher for the exclusion term, if one is set
+fn exclusion() -> Option<BitapSearcher> {
+    unsafe { (EXCLUDE_LEN > 0).then(|| new_searcher(&EXCLUDE[..EXCLUDE_LEN])) }
+}
+
+/// Check whether the exclusion term matches the record at or above the threshold
+fn excluded(exclude: Option<&BitapSearcher>, record: &RecordView, config: &SearchConfig) -> bool {
+    let Some(exclude) = exclude else {
+        return false;
+    };
+    if config.bloom && !BloomFilter(record.bloom).might_contain_within(exclude.bloom(), config.max_errors) {
+        return false;
+    }
+
+    let pattern_len = exclude.pattern_len() as u32;
+    let matches = |text: &[u8]| {
+        exclude
+            .search(text, config.max_errors)
+            .is_some_and(|m| bitap::compute_score(m.errors, pattern_len, m.end_pos) >= config.threshold)
+    };
+    matches(record.text(string_pool())) || (record.alias_len > 0 && matches(record.alias(string_pool())))
+}
+
+#[no_mangle]
+pub extern "C" fn search() -> u32 {
+    scan_records(usize::MAX, None, 0)
+}
+
+/// Whether the last search was refused as too broad (1) or ran (0)
+#[no_mangle]
+pub extern "C" fn searchTooBroad() -> u32 {
+    unsafe { LAST_TOO_BROAD as u32 }
+}
+
+/// Search only the first `max_records` active records (partial results for previews)
+#[no_mangle]
+pub extern "C" fn searchLimited(max_records: u32) -> u32 {
+    scan_records(max_records as usize, None, 0)
+}
+
+#[no_mangle]
+pub extern "C" fn searchTopK(k: u32) -> u32 {
+    scan_records(usize::MAX, Some(k as usize), 0)
+}
+
+/// Search only the first `max_offset` bytes of each record (0 = whole records)
+#[no_mangle]
+pub extern "C" fn searchWindow(max_offset: u32) -> u32 {
+    scan_records(usize::MAX, None, max_offset as usize)
+}
+
+fn scan_records(limit: usize, top_k: Option<usize>, window: usize) -> u32 {
+    unsafe {
+        LAST_TOO_BROAD = false;
+        remember_results();
+        RESULT_COUNT = 0;
+        invalidate_candidates();
+        auto_compact();
+
+        if PATTERN_LEN == 0 {
+            return 0;
+        }
+
+        let searcher = prepared_searcher();
+        let config = SearchConfig { window, ..pattern_config() };
+        let exclude = exclusion();
+        let keep = |record: &RecordView| !excluded(exclude.as_ref(), record, &config);
+
+        // Exclusion and recency depend on the threshold; only cache plain searches
+        let cache = CANDIDATE_CACHE
+            && top_k.is_none()
+            && limit == usize::MAX
+            && window == 0
+            && exclude.is_none()
+            && config.recency_weight == 0;
+        let scan_config = match top_k {
+            Some(k) => SearchConfig { threshold: 0, max_results: k, ..config },
+            None if cache => SearchConfig { threshold: 0, ..config },
+            None => config,
+        };
+        let out = if cache { candidates() } else { results() };
+
+        let records = &RECORDS[..RECORD_COUNT];
+        let mut search = RecordSearch::new(records, string_pool(), searcher, &scan_config, out);
+        if search.too_broad() {
+            LAST_TOO_BROAD = true;
+            return 0;
+        }
+
+        // Exact prefix queries only need records sharing the pattern's first bytes.
+        // Aliases aren't in the prefix index, phonetic matches ignore the
+        // prefix and the index keys keep accents, so any of them forces a full scan.
+        let exact_prefix = config.anchor == Anchor::Start && config.max_errors == 0 && config.match_mode == MatchMode::Bitap;
+        if exact_prefix && ALIAS_COUNT == 0 && !config.phonetic && !config.fold_diacritics {
+            let slots = prefix_index().candidates(searcher.pattern()).map(|slot| slot as usize);
+            for slot in slots.filter(|&slot| records[slot].active).take(limit) {
+                search.visit_if(slot, keep);
+            }
+        } else if limit == usize::MAX {
+            // Skip whole blocks whose summary bloom rules the pattern out
+            let blocks = &*core::ptr::addr_of!(BLOCK_BLOOMS);
+            for (block, &bloom) in blocks[..blocks_for(RECORD_COUNT)].iter().enumerate() {
+                if !search.may_match_block(bloom) {
+                    continue;
+                }
+                let start = block * BLOOM_BLOCK;
+                for slot in start..(start + BLOOM_BLOCK).min(RECORD_COUNT) {
+                    search.visit_if(slot, keep);
+                }
+            }
+        } else {
+            for (slot, _) in RecordsView::new(records, string_pool()).slots().take(limit) {
+                search.visit_if(slot, keep);
+            }
+        }
+
+        if cache {
+            CANDIDATE_COUNT = search.finish();
+            CANDIDATE_CONFIG = Some(scan_config);
+            return filter_candidates(config.threshold);
+        }
+        RESULT_COUNT = search.finish();
+        RESULT_COUNT as u32
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn setCandidateCache(enabled: u32) {
+    unsafe {
+        CANDIDATE_CACHE = enabled != 0;
+    }
+    invalidate_candidates();
+}
+
+/// Set the threshold and re-filter the last search's cached candidates,
+/// or run `search` if they are stale
+#[no_mangle]
+pub extern "C" fn rethreshold(threshold: u32) -> u32 {
+    unsafe {
+        THRESHOLD = threshold as u16;
+        AUTO_THRESHOLD = false;
+        if CANDIDATE_CONFIG == Some(SearchConfig { threshold: 0, ..pattern_config() }) {
+            remember_results();
+            filter_candidates(THRESHOLD)
+        } else {
+            search()
+        }
+    }
+}
+
+unsafe fn filter_candidates(threshold: u16) -> u32 {
+    let Some(config) = CANDIDATE_CONFIG else {
+        return 0;
+    };
+    let config = SearchConfig { threshold, ..config };
+    let records = &RECORDS[..RECORD_COUNT];
+    let mut results = ResultSet::new(results(), &config);
+    for &candidate in &candidates()[..CANDIDATE_COUNT] {
+        if candidate.score >= threshold {
+            results.insert(candidate, records, string_pool());
+        }
+    }
+    RESULT_COUNT = results.finish();
+    RESULT_COUNT as u32
+}
+
+/// IDs entering and leaving the results between the last two searches
+/// Returns the added count in bits 0-15 and the removed count in bits 16-31
+///
+/// # Safety
+/// Each pointer must be null or point into linear memory valid for `cap` u32s.
+#[no_mangle]
+pub unsafe extern "C" fn resultDiff(out_added: *mut u32, out_removed: *mut u32, cap: u32) -> u32 {
+    let cap = cap.min(0xFFFF);
+    let previous = &*core::ptr::addr_of!(PREVIOUS_RESULTS);
+    let (added, removed) = diff_result_ids(
+        &previous[..PREVIOUS_COUNT],
+        &RESULTS[..RESULT_COUNT],
+        raw_ids_mut(out_added, cap),
+        raw_ids_mut(out_removed, cap),
+    );
+    (removed << 16 | added) as u32
+}
+
+/// # Safety
+/// Each pointer must be null or point into linear memory valid for its length.
+#[no_mangle]
+pub unsafe extern "C" fn searchWithExclusion(
+    include_ptr: *const u8,
+    include_len: u32,
+    exclude_ptr: *const u8,
+    exclude_len: u32,
+) -> u32 {
+    let include = raw_bytes(include_ptr, include_len);
+    let exclude = raw_bytes(exclude_ptr, exclude_len);
+
+    set_pattern(&include[..include.len().min(MAX_PATTERN_LEN)]);
+    let len = exclude.len().min(MAX_PATTERN_LEN);
+    EXCLUDE[..len].copy_from_slice(&exclude[..len]);
+    EXCLUDE_LEN = collapsed_len(&mut EXCLUDE[..len]);
+
+    search()
+}
+
+unsafe fn raw_bytes<'a>(ptr: *const u8, len: u32) -> &'a [u8] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(ptr, len as usize)
+    }
+}
+
+unsafe fn raw_bytes_mut<'a>(ptr: *mut u8, len: u32) -> &'a mut [u8] {
+    if ptr.is_null() || len == 0 {
+        &mut []
+    } else {
+        core::slice::from_raw_parts_mut(ptr, len as usize)
+    }
+}
+
+unsafe fn raw_ids_mut<'a>(ptr: *mut u32, len: u32) -> &'a mut [u32] {
+    if ptr.is_null() || len == 0 {
+        &mut []
+    } else {
+        core::slice::from_raw_parts_mut(ptr, len as usize)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn searchTerms() -> u32 {
+    unsafe {
+        LAST_TOO_BROAD = false;
+        remember_results();
+        RESULT_COUNT = 0;
+        invalidate_candidates();
+
+        let query = &SCRATCHPAD[..SCRATCHPAD_LEN];
+        let mut terms: [Option<BitapSearcher>; MAX_TERMS] = Default::default();
+        let mut term_count = 0;
+        let mut terms_bloom = BloomFilter::new();
+        for term in query.split(|c| c.is_ascii_whitespace()).filter(|t| !t.is_empty()).take(MAX_TERMS) {
+            let searcher = new_searcher(&term[..term.len().min(MAX_PATTERN_LEN)]);
+            terms_bloom = BloomFilter(terms_bloom.bits() | searcher.bloom().bits());
+            terms[term_count] = Some(searcher);
+            term_count += 1;
+        }
+        SCRATCHPAD_LEN = 0;
+
+        if term_count == 0 {
+            return 0;
+        }
+
+        // Terms are plain bitap matches anywhere in the text
+        let config = current_config();
+        let term_config = SearchConfig { match_mode: MatchMode::Bitap, anchor: Anchor::None, ..config };
+        let matchers: [Option<Matcher>; MAX_TERMS] =
+            core::array::from_fn(|i| terms[i].as_ref().map(|searcher| Matcher::new(searcher, &term_config)));
+        // Every term must match, so one narrow term narrows the query
+        if matchers.iter().flatten().all(Matcher::too_broad) {
+            LAST_TOO_BROAD = true;
+            return 0;
+        }
+
+        let records = &RECORDS[..RECORD_COUNT];
+        let pool = string_pool();
+        let mut results = ResultSet::new(results(), &config);
+        // Each term may miss up to max_errors of its chars
+        let terms_missing = config.max_errors * term_count as u32;
+
+        'records: for (slot, record) in RecordsView::new(records, pool).slots() {
+            if config.bloom && !BloomFilter(record.bloom).might_contain_within(terms_bloom, terms_missing) {
+                continue;
+            }
+
+            let mut combined: Option<ScoredResult> = None;
+            for (matcher, term) in matchers.iter().flatten().zip(terms.iter().flatten()) {
+                let Some(hit) = matcher.match_record(record, pool) else {
+                    continue 'records;
+                };
+                if hit.score < config.threshold_for(term.pattern_len()) {
+                    continue 'records;
+                }
+                combined = Some(match combined {
+                    None => hit,
+                    Some(c) => ScoredResult {
+                        score: c.score.min(hit.score),
+                        start: c.start.min(hit.start),
+                        end: c.end.max(hit.end),
+                        errors: c.errors + hit.errors,
+                        text_len: c.text_len.max(hit.text_len),
+                        ..c
+                    },
+                });
+            }
+
+            if let Some(mut result) = combined {
+                result.score = apply_recency(result.score, record.added_seq, NEXT_SEQ, config.recency_weight);
+                result.index = slot as u32;
+                results.insert(result, records, pool);
+            }
+        }
+
+        RESULT_COUNT = results.finish();
+        RESULT_COUNT as u32
+    }
+}
+
+/// Register the scratchpad as a pattern for `searchMulti`
+/// Returns its index, -1 if MAX_MULTI_PATTERNS are registered or -2 if empty
+#[no_mangle]
+pub extern "C" fn addMultiPattern() -> i32 {
+    unsafe {
+        let len = collapsed_len(&mut SCRATCHPAD[..SCRATCHPAD_LEN]).min(MAX_PATTERN_LEN);
+        SCRATCHPAD_LEN = 0;
+        if len == 0 {
+            return -2;
+        }
+        if MULTI_PATTERN_COUNT >= MAX_MULTI_PATTERNS {
+            return -1;
+        }
+
+        let index = MULTI_PATTERN_COUNT;
+        MULTI_PATTERNS[index] = Some(new_searcher(&SCRATCHPAD[..len]));
+        MULTI_PATTERN_COUNT += 1;
+        MULTI_RESULT_COUNTS[index] = 0;
+        index as i32
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn clearMultiPatterns() {
+    unsafe {
+        MULTI_PATTERNS = [NO_PATTERN; MAX_MULTI_PATTERNS];
+        MULTI_PATTERN_COUNT = 0;
+        MULTI_RESULT_COUNTS = [0; MAX_MULTI_PATTERNS];
+    }
+}
+
+/// Search for every registered pattern in one pass; returns the total result count
+#[no_mangle]
+pub extern "C" fn searchMulti() -> u32 {
+    unsafe {
+        MULTI_RESULT_COUNTS = [0; MAX_MULTI_PATTERNS];
+        LAST_TOO_BROAD = false;
+
+        let config = current_config();
+        let records = &RECORDS[..RECORD_COUNT];
+        let pool = string_pool();
+        let patterns = &*core::ptr::addr_of!(MULTI_PATTERNS);
+        let mut buffers = (*core::ptr::addr_of_mut!(MULTI_RESULTS)).iter_mut();
+        let mut searches: [Option<RecordSearch>; MAX_MULTI_PATTERNS] = core::array::from_fn(|i| {
+            let out = buffers.next()?;
+            patterns[i].as_ref().map(|searcher| RecordSearch::new(records, pool, searcher, &config, out))
+        });
+        // Too broad patterns are left out; the rest still run
+        for search in &mut searches {
+            if search.as_ref().is_some_and(RecordSearch::too_broad) {
+                *search = None;
+                LAST_TOO_BROAD = true;
+            }
+        }
+
+        for (slot, _) in RecordsView::new(records, pool).slots() {
+            for search in searches.iter_mut().flatten() {
+                search.visit(slot);
+            }
+        }
+
+        let mut total = 0;
+        for (i, search) in searches.into_iter().enumerate() {
+            if let Some(search) = search {
+                MULTI_RESULT_COUNTS[i] = search.finish();
+                total += MULTI_RESULT_COUNTS[i];
+            }
+        }
+        total as u32
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getMultiResultCount(pattern: u32) -> u32 {
+    multi_results(pattern).len() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn getMultiResultId(pattern: u32, index: u32) -> u32 {
+    multi_results(pattern).get(index as usize).map_or(0, |r| r.id)
+}
+
+#[no_mangle]
+pub extern "C" fn getMultiResultScore(pattern: u32, index: u32) -> u32 {
+    multi_results(pattern).get(index as usize).map_or(0, |r| r.score as u32)
+}
+
+/// Bit N is set if multi pattern N's last results include `id`
+#[no_mangle]
+pub extern "C" fn getMultiMatchMask(id: u32) -> u32 {
+    (0..MAX_MULTI_PATTERNS as u32)
+        .filter(|&p| multi_results(p).iter().any(|r| r.id == id))
+        .fold(0, |mask, p| mask | 1 << p)
+}
+
+fn multi_results(pattern: u32) -> &'static [ScoredResult] {
+    let p = pattern as usize;
+    if p >= MAX_MULTI_PATTERNS {
+        return &[];
+    }
+    unsafe {
+        let results = &*core::ptr::addr_of!(MULTI_RESULTS);
+        &results[p][..MULTI_RESULT_COUNTS[p]]
+    }
+}
+
+/// Write the text of the record closest to the pattern, ignoring the threshold
+/// Returns the bytes written, or -1 if none comes close
+///
+/// # Safety
+/// `out` must be null or point into linear memory valid for `cap` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn suggest(out: *mut u8, cap: u32) -> i32 {
+    if PATTERN_LEN == 0 {
+        return -1;
+    }
+
+    let budget = (PATTERN_LEN as u32 - 1).min(MAX_ERRORS_CEIL);
+    let records = &RECORDS[..RECORD_COUNT];
+    let Some(slot) = closest_record(records, string_pool(), prepared_searcher(), budget) else {
+        return -1;
+    };
+
+    let text = records[slot].text(string_pool());
+    let mut len = text.len().min(cap as usize);
+    while len < text.len() && len > 0 && text[len] & 0xC0 == 0x80 {
+        len -= 1;
+    }
+    if !out.is_null() {
+        core::ptr::copy_nonoverlapping(text.as_ptr(), out, len);
+    }
+    len as i32
+}
+
+/// Start of the first match at or after `from_offset` in one record, or -1
+#[no_mangle]
+pub extern "C" fn findInRecord(id: u32, from_offset: u32) -> i32 {
+    unsafe {
+        if PATTERN_LEN == 0 {
+            return -1;
+        }
+        let Some(record) = RECORDS[..RECORD_COUNT].iter().rfind(|r| r.id == id && r.active) else {
+            return -1;
+        };
+
+        let searcher = prepared_searcher();
+        let text = record.text(string_pool());
+        let max_errors = pattern_config().errors_for(searcher.pattern_len(), text.len());
+        match searcher.next_match(text, from_offset as usize, max_errors) {
+            Some(m) => m.start_pos as i32,
+            None => -1,
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn explain(id: u32) -> i32 {
+    unsafe {
+        if PATTERN_LEN == 0 {
+            return -1;
+        }
+
+        let record = match RECORDS[..RECORD_COUNT].iter().rfind(|r| r.id == id && r.active) {
+            Some(record) => record,
+            None => return -1,
+        };
+
+        let searcher = prepared_searcher();
+        let config = pattern_config();
+        let matcher = Matcher::new(searcher, &config);
+        let mut bits = 0i32;
+
+        if matcher.bloom_passes(record) {
+            bits |= EXPLAIN_BLOOM_PASSED;
+        }
+        if let Some(hit) = matcher.match_record(record, string_pool()) {
+            bits |= EXPLAIN_MATCHED;
+            if hit.score >= config.threshold {
+                bits |= EXPLAIN_THRESHOLD_PASSED;
+            }
+            bits |= (hit.errors.min(31) as i32) << EXPLAIN_ERRORS_SHIFT;
+            bits |= (hit.score as i32) << EXPLAIN_SCORE_SHIFT;
+        }
+        if excluded(exclusion().as_ref(), record, &config) {
+            bits |= EXPLAIN_EXCLUDED;
+        }
+        bits
+    }
+}
+
+/// Break down the score of the result at `index`; 0 on success, -1 if none
+///
+/// # Safety
+/// `out` must be null or point into linear memory valid for six u32s.
+#[no_mangle]
+pub unsafe extern "C" fn explainResult(index: u32, out: *mut ScoreBreakdown) -> i32 {
+    let i = index as usize;
+    if i >= RESULT_COUNT || out.is_null() || PATTERN_LEN == 0 {
+        return -1;
+    }
+
+    let result = RESULTS[i];
+    let record = &RECORDS[result.index as usize];
+    let text = if result.alias { record.alias(string_pool()) } else { record.text(string_pool()) };
+    let config = pattern_config();
+    let breakdown = match Matcher::new(prepared_searcher(), &config).explain_text(text) {
+        Some(breakdown) if breakdown.score >= result.score as u32 => breakdown,
+        // Phonetic match, or the text no longer matches
+        _ => ScoreBreakdown::new(result.score as u32, 0, 0),
+    };
+    // Recency lowered the score after matching
+    out.write(breakdown.lowered_to(result.score as u32));
+    0
+}
+
+/// Insertions, deletions and substitutions of the result at `index`; 0 on success, -1 if none
+///
+/// # Safety
+/// `out` must be null or point into linear memory valid for three u32s.
+#[no_mangle]
+pub unsafe extern "C" fn getResultEditProfile(index: u32, out: *mut EditProfile) -> i32 {
+    let i = index as usize;
+    if i >= RESULT_COUNT || out.is_null() || PATTERN_LEN == 0 {
+        return -1;
+    }
+
+    let result = RESULTS[i];
+    let record = &RECORDS[result.index as usize];
+    let text = if result.alias { record.alias(string_pool()) } else { record.text(string_pool()) };
+    let config = pattern_config();
+    match Matcher::new(prepared_searcher(), &config).edit_profile(text) {
+        Some(profile) => {
+            out.write(profile);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Prepared pattern's bitap mask for `byte`, first 32 chars (debugging aid)
+#[no_mangle]
+pub extern "C" fn getCharMask(byte: u32) -> u32 {
+    if unsafe { PATTERN_LEN } == 0 {
+        return 0;
+    }
+    prepared_searcher().char_mask(byte as u8) as u32
+}
+
+#[no_mangle]
+pub extern "C" fn getResultId(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].id } else { 0 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultScore(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].score as u32 } else { 0 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultStart(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].byte_span().0 } else { 0 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultEnd(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].byte_span().1 } else { 0 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultErrors(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].errors } else { 0 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultRank(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].rank } else { 0 }
+    }
+}
+
+/// Write the results as a JSON array of `{"id","score","start","end"}`
+/// One call instead of four getters per result. Scores are 0-1000 and
+/// offsets are bytes, as from the getters. Returns the bytes written, or
+/// minus the bytes needed if `cap` is too small (nothing usable is written)
+///
+/// # Safety
+/// `out` must be null or point into linear memory valid for `cap` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn resultsToJson(out: *mut u8, cap: u32) -> i32 {
+    let mut json = JsonWriter { out: raw_bytes_mut(out, cap), len: 0 };
+    json.push(b"[");
+    for (i, result) in RESULTS[..RESULT_COUNT].iter().enumerate() {
+        let (start, end) = result.byte_span();
+        json.push(if i == 0 { b"{\"id\":" } else { b",{\"id\":" });
+        json.number(result.id);
+        json.push(b",\"score\":");
+        json.number(result.score as u32);
+        json.push(b",\"start\":");
+        json.number(start);
+        json.push(b",\"end\":");
+        json.number(end);
+        json.push(b"}");
+    }
+    json.push(b"]");
+
+    if json.len > json.out.len() {
+        -(json.len as i32)
+    } else {
+        json.len as i32
+    }
+}
+
+/// Appends to a caller buffer, counting bytes past its end without writing them
+struct JsonWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl JsonWriter<'_> {
+    fn push(&mut self, bytes: &[u8]) {
+        if let Some(dst) = self.out.get_mut(self.len..self.len + bytes.len()) {
+            dst.copy_from_slice(bytes);
+        }
+        self.len += bytes.len();
+    }
+
+    fn number(&mut self, mut n: u32) {
+        let mut digits = [0u8; 10];
+        let mut i = digits.len();
+        loop {
+            i -= 1;
+            digits[i] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.push(&digits[i..]);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultRelative(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].relative as u32 } else { 0 }
+    }
+}
+
+/// Calibrated confidence (0-1000), see `bitap::confidence`
+#[no_mangle]
+pub extern "C" fn getResultConfidence(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i >= RESULT_COUNT {
+            return 0;
+        }
+        let result = RESULTS[i];
+        let (start, end) = result.byte_span();
+        bitap::confidence(result.errors, PATTERN_LEN, (end - start) as usize) as u32
+    }
+}
+
+/// Query occurrences per 1000 bytes of the result's matched text
+#[no_mangle]
+pub extern "C" fn getResultDensity(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].density() } else { 0 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn resultsCommonPrefix() -> u32 {
+    unsafe { common_prefix_len(&RECORDS[..RECORD_COUNT], string_pool(), &results()[..RESULT_COUNT]) as u32 }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultCoverage(index: u32) -> u32 {
+    unsafe {
+        let i = index as usize;
+        if i < RESULT_COUNT { RESULTS[i].coverage() } else { 0 }
+    }
+}
+
+unsafe fn result_char_span(index: u32) -> (u32, u32) {
+    let i = index as usize;
+    if i >= RESULT_COUNT {
+        return (0, 0);
+    }
+    let result = &results()[i];
+    let record = &RECORDS[result.index as usize];
+    result.char_span(record.matched_text(result, string_pool()))
+}
+
+/// Start and end counted in chars (UTF-8 lead bytes) for highlighting JS strings
+#[no_mangle]
+pub extern "C" fn getResultCharStart(index: u32) -> u32 {
+    unsafe { result_char_span(index).0 }
+}
+
+#[no_mangle]
+pub extern "C" fn getResultCharEnd(index: u32) -> u32 {
+    unsafe { result_char_span(index).1 }
+}
+
+/// Clear data, keeping configuration (`init` also restores defaults)
+#[no_mangle]
+pub extern "C" fn clearData() {
+    unsafe {
+        RECORD_COUNT = 0;
+        INACTIVE_COUNT = 0;
+        BLOCK_BLOOMS = [0; BLOOM_BLOCKS];
+        RECORD_HIGH_WATER = 0;
+        ALIAS_COUNT = 0;
+        INDEX_DIGEST = digest::EMPTY_DIGEST;
+        NEXT_SEQ = 0;
+        pool_alloc().reset();
+        prefix_index().reset();
+        PATTERN_LEN = 0;
+        SEARCHER = None;
+        EXCLUDE_LEN = 0;
+        RESULT_COUNT = 0;
+        SCRATCHPAD_LEN = 0;
+    }
+    clearMultiPatterns();
+    invalidate_candidates();
+}
+
+#[deprecated(note = "use clearData to keep configuration or init for a full reset")]
+#[no_mangle]
+pub extern "C" fn reset() {
+    clearData();
+}
+
+/// Drop removed records; results are renumbered, those of removed records dropped
+#[no_mangle]
+pub extern "C" fn compact() -> u32 {
+    unsafe {
+        let (count, result_count) = compact_records(&mut RECORDS[..RECORD_COUNT], &mut results()[..RESULT_COUNT]);
+        RECORD_COUNT = count;
+        RESULT_COUNT = result_count;
+        INACTIVE_COUNT = 0;
+        ALIAS_COUNT = RECORDS[..RECORD_COUNT].iter().filter(|r| r.alias_len > 0).count();
+
+        BLOCK_BLOOMS = [0; BLOOM_BLOCKS];
+        prefix_index().reset();
+        let pool = string_pool();
+        for (slot, record) in RECORDS[..RECORD_COUNT].iter().enumerate() {
+            BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+            prefix_index().insert(slot as u32, record.text(pool));
+        }
+        invalidate_candidates();
+        RECORD_COUNT as u32
+    }
+}
+
+fn auto_compact() {
+    unsafe {
+        if INACTIVE_COUNT > 0 && INACTIVE_COUNT as f32 > AUTO_COMPACT_RATIO * RECORD_COUNT as f32 {
+            compact();
+        }
+    }
+}
+
+/// Replace a record's text by the scratchpad; unchanged text skips the bloom recompute
+/// Changed text is rewritten in the record's slot, keeping its alias and
+/// insertion order. On error the record is unchanged.
+#[no_mangle]
+pub extern "C" fn updateRecord(id: u32) -> i32 {
+    unsafe {
+        let Some(slot) = RECORDS[..RECORD_COUNT].iter().rposition(|r| r.id == id && r.active) else {
+            return 0;
+        };
+
+        SCRATCHPAD_LEN = collapsed_len(&mut SCRATCHPAD[..SCRATCHPAD_LEN]);
+        if SCRATCHPAD_LEN == 0 {
+            return -2;
+        }
+        if *RECORDS[slot].text(string_pool()) == SCRATCHPAD[..SCRATCHPAD_LEN] {
+            SCRATCHPAD_LEN = 0;
+            return 1;
+        }
+
+        // Copy the new text before touching the record so a full pool leaves it intact
+        let text_len = SCRATCHPAD_LEN;
+        let Some(text_start) = pool_alloc().alloc(text_len) else {
+            return -3;
+        };
+        STRING_POOL[text_start..text_start + text_len].copy_from_slice(&SCRATCHPAD[..text_len]);
+        let text = &string_pool()[text_start..text_start + text_len];
+
+        let record = &mut RECORDS[slot];
+        let old = record.text(string_pool());
+        INDEX_DIGEST = digest::remove_record(INDEX_DIGEST, id, old);
+        INDEX_DIGEST = digest::add_record(INDEX_DIGEST, id, text);
+        prefix_index().relink(slot as u32, old, text);
+        pool_alloc().free(record.text_start as usize, record.text_len as usize);
+
+        record.text_start = text_start as u32;
+        record.text_len = text_len as u32;
+        record.bloom = text_bloom(text);
+        record.chars = CharSet::from_text(text);
+        if record.alias_len > 0 {
+            let alias = record.alias(string_pool());
+            record.bloom |= text_bloom(alias);
+            record.chars = record.chars.union(CharSet::from_text(alias));
+        }
+        record.phonetic = phonetic::encode(text);
+        BLOCK_BLOOMS[slot / BLOOM_BLOCK] |= record.bloom;
+        SCRATCHPAD_LEN = 0;
+        invalidate_candidates();
+        1
+    }
+}
+
+/// Remove a record by ID; with duplicate IDs the latest active one, as updateRecord
+#[no_mangle]
+pub extern "C" fn removeRecord(id: u32) -> i32 {
+    unsafe {
+        match RECORDS[..RECORD_COUNT].iter().rposition(|r| r.id == id && r.active) {
+            Some(slot) => {
+                remove_slot(slot);
+                1
+            }
+            None => 0,
+        }
+    }
+}
+
+unsafe fn remove_slot(slot: usize) {
+    let record = &mut RECORDS[slot];
+    record.active = false;
+    INACTIVE_COUNT += 1;
+    INDEX_DIGEST = digest::remove_record(INDEX_DIGEST, record.id, record.text(string_pool()));
+    pool_alloc().free(record.text_start as usize, record.text_len as usize);
+    pool_alloc().free(record.alias_start as usize, record.alias_len as usize);
+    if record.alias_len > 0 {
+        ALIAS_COUNT -= 1;
+    }
+    invalidate_candidates();
+}
+
+#[no_mangle]
+pub extern "C" fn getStringPoolUsed() -> u32 {
+    pool_alloc().used() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn getAvailableMemory() -> u32 {
+    pool_alloc().available() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn getPoolCapacity() -> u32 {
+    STRING_POOL_SIZE as u32
+}
+
+/// Pool bytes below the high-water mark not held by active records
+#[no_mangle]
+pub extern "C" fn getPoolReclaimable() -> u32 {
+    let records = unsafe { &RECORDS[..RECORD_COUNT] };
+    let live = RecordsView::new(records, string_pool()).live_bytes();
+    pool_alloc().used().saturating_sub(live) as u32
+}
+
+#[no_mangle]
+pub extern "C" fn getRecordHighWater() -> u32 {
+    unsafe { RECORD_HIGH_WATER as u32 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Tests share the engine's globals, so they take turns
+    fn engine() -> MutexGuard<'static, ()> {
+        static ENGINE: Mutex<()> = Mutex::new(());
+        ENGINE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(text: &[u8]) {
+        let buf = getWriteBuffer(text.len());
+        unsafe { core::ptr::copy_nonoverlapping(text.as_ptr(), buf, text.len()) };
+        commitWrite(text.len());
+    }
+
+    fn search_for(query: &[u8]) -> u32 {
+        write(query);
+        preparePattern();
+        search()
+    }
+
+    fn json(cap: usize) -> (i32, Vec<u8>) {
+        let mut out = vec![0xAA; cap + 8];
+        let len = unsafe { resultsToJson(out.as_mut_ptr(), cap as u32) };
+        (len, out)
+    }
+
+    #[test]
+    fn test_json_number() {
+        let mut out = [0u8; 32];
+        let mut writer = JsonWriter { out: &mut out, len: 0 };
+        for n in [0, 7, 1000, u32::MAX] {
+            writer.number(n);
+            writer.push(b",");
+        }
+        let len = writer.len;
+        assert_eq!(&out[..len], b"0,7,1000,4294967295,");
+    }
+
+    #[test]
+    fn test_results_to_json() {
+        let _engine = engine();
+        init();
+        write(b"hello world");
+        addRecord(7);
+        write(b"say hello");
+        addRecord(12);
+
+        assert_eq!(search_for(b"zzzz"), 0);
+        let (len, out) = json(16);
+        assert_eq!(&out[..len as usize], b"[]");
+
+        let expected = br#"[{"id":7,"score":1000,"start":0,"end":5},{"id":12,"score":1000,"start":4,"end":9}]"#;
+        assert_eq!(search_for(b"hello"), 2);
+        let (len, out) = json(128);
+        assert_eq!(&out[..len as usize], expected);
+
+        // Too small: the size needed, and nothing past `cap`
+        let (len, out) = json(10);
+        assert_eq!(len, -(expected.len() as i32));
+        assert!(out[10..].iter().all(|&b| b == 0xAA));
+
+        assert_eq!(unsafe { resultsToJson(core::ptr::null_mut(), 128) }, -(expected.len() as i32));
+    }
 }